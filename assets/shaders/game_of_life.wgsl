@@ -3,11 +3,26 @@
 // simulation.
 // Two textures are needed for the game of life as each pixel of step N depends on the state of its
 // neighbors at step N-1.
+//
+// The rule comes from `settings`: bit `n` of `birth` and `survival` is set when a cell with `n`
+// alive neighbours is born or survives. With more than 2 states, cells that don't survive go
+// through the dying states before they are dead. The state of a cell is encoded in its brightness.
+
+struct CellularAutomatonSettings {
+    birth: u32,
+    survival: u32,
+    states: u32,
+    size: vec2<u32>,
+    density: f32,
+    seed: u32,
+}
 
 @group(0) @binding(0) var input: texture_storage_2d<rgba32float, read>;
 
 @group(0) @binding(1) var output: texture_storage_2d<rgba32float, write>;
 
+@group(0) @binding(2) var<uniform> settings: CellularAutomatonSettings;
+
 fn hash(value: u32) -> u32 {
     var state = value;
     state = state ^ 2747636419u;
@@ -23,23 +38,45 @@ fn randomFloat(value: u32) -> f32 {
     return f32(hash(value)) / 4294967295.0;
 }
 
+// Dead cells are black, alive ones white and dying ones fade out.
+fn state_color(state: u32) -> vec4<f32> {
+    var brightness = 0.0;
+    if state == 1u {
+        brightness = 1.0;
+    } else if state > 1u {
+        brightness = 1.0 - f32(state - 1u) / f32(settings.states - 1u);
+    }
+
+    return vec4<f32>(vec3f(brightness), 1);
+}
+
+fn color_state(color: vec4<f32>) -> u32 {
+    if color.x <= 0.0 {
+        return 0u;
+    }
+
+    return 1u + u32(round((1.0 - color.x) * f32(settings.states - 1u)));
+}
+
 @compute @workgroup_size(8, 8, 1)
 fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>, @builtin(num_workgroups) num_workgroups: vec3<u32>) {
     let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
 
-    let randomNumber = randomFloat(invocation_id.y << 16u | invocation_id.x);
-    let alive = randomNumber > 0.94;
-    let color = vec4<f32>(vec3f(f32(alive)), 1);
+    let randomNumber = randomFloat(hash(settings.seed) ^ (invocation_id.y << 16u | invocation_id.x));
+    let alive = randomNumber < settings.density;
 
-    textureStore(output, location, color);
+    textureStore(output, location, state_color(u32(alive)));
 }
 
-fn is_alive(location: vec2<i32>, offset_x: i32, offset_y: i32) -> i32 {
-    let value: vec4<f32> = textureLoad(input, location + vec2<i32>(offset_x, offset_y));
-    return i32(value.x);
+// The grid wraps around
+fn is_alive(location: vec2<i32>, offset_x: i32, offset_y: i32) -> u32 {
+    let size = vec2<i32>(settings.size);
+    let neighbour = (location + vec2<i32>(offset_x, offset_y) + size) % size;
+    let value: vec4<f32> = textureLoad(input, neighbour);
+    return u32(color_state(value) == 1u);
 }
 
-fn count_alive(location: vec2<i32>) -> i32 {
+fn count_alive(location: vec2<i32>) -> u32 {
     return is_alive(location, -1, -1) + is_alive(location, -1, 0) + is_alive(location, -1, 1) + is_alive(location, 0, -1) + is_alive(location, 0, 1) + is_alive(location, 1, -1) + is_alive(location, 1, 0) + is_alive(location, 1, 1);
 }
 
@@ -48,10 +85,20 @@ fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
     let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
 
     let n_alive = count_alive(location);
-    let self_alive = is_alive(location, 0, 0);
-    let alive = (n_alive | self_alive) == 3;
+    let state = color_state(textureLoad(input, location));
 
-    let color = vec4<f32>(vec3f(f32(alive)), 1);
+    var next = 0u;
+    if state == 0u {
+        next = (settings.birth >> n_alive) & 1u;
+    } else if state == 1u {
+        if ((settings.survival >> n_alive) & 1u) == 1u {
+            next = 1u;
+        } else if settings.states > 2u {
+            next = 2u;
+        }
+    } else {
+        next = (state + 1u) % settings.states;
+    }
 
-    textureStore(output, location, color);
+    textureStore(output, location, state_color(next));
 }
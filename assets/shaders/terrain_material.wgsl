@@ -0,0 +1,94 @@
+#import bevy_pbr::{pbr_fragment::pbr_input_from_standard_material, pbr_functions::alpha_discard}
+#import bevy_pbr::{forward_io::{VertexOutput, FragmentOutput}, pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing}}
+
+struct TerrainMaterialSettings {
+    layer_count: u32,
+    texture_scale: f32,
+    triplanar_sharpness: f32,
+}
+
+struct TerrainLayer {
+    color: vec4<f32>,
+    height_range: vec2<f32>,
+    slope_range: vec2<f32>,
+    blend_sharpness: f32,
+    texture_index: i32,
+}
+
+@group(2) @binding(100) var<uniform> settings: TerrainMaterialSettings;
+@group(2) @binding(101) var<storage, read> layers: array<TerrainLayer>;
+@group(2) @binding(102) var layer_textures: texture_2d_array<f32>;
+@group(2) @binding(103) var layer_sampler: sampler;
+
+// 1 inside the range, fading to 0 within `1 / sharpness` outside of it
+fn range_weight(value: f32, range: vec2<f32>, sharpness: f32) -> f32 {
+    let lower = saturate((value - range.x) * sharpness + 1.);
+    let upper = saturate((range.y - value) * sharpness + 1.);
+    return lower * upper;
+}
+
+// Samples a texture layer projected along the three axes, so steep cliffs don't get stretched
+// textures. Gradients are passed explicitly because the call happens in non-uniform control flow.
+fn sample_triplanar(
+    index: i32,
+    position: vec3<f32>,
+    blend: vec3<f32>,
+    ddx: vec3<f32>,
+    ddy: vec3<f32>,
+) -> vec4<f32> {
+    let x = textureSampleGrad(layer_textures, layer_sampler, position.zy, index, ddx.zy, ddy.zy);
+    let y = textureSampleGrad(layer_textures, layer_sampler, position.xz, index, ddx.xz, ddy.xz);
+    let z = textureSampleGrad(layer_textures, layer_sampler, position.xy, index, ddx.xy, ddy.xy);
+
+    return x * blend.x + y * blend.y + z * blend.z;
+}
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> @location(0) vec4<f32> {
+    // ? generate a PbrInput struct from the StandardMaterial bindings
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+    let normal = normalize(pbr_input.world_normal);
+    let height = in.world_position.y;
+    let slope = 1. - normal.y;
+
+    let position = in.world_position.xyz * settings.texture_scale;
+    let position_dx = dpdx(position);
+    let position_dy = dpdy(position);
+    var blend = pow(abs(normal), vec3f(settings.triplanar_sharpness));
+    blend /= blend.x + blend.y + blend.z;
+
+    // ? splat layers in order, each one painted on top of the previous ones
+    var base_color = pbr_input.material.base_color;
+    for (var i = 0u; i < settings.layer_count; i++) {
+        let layer = layers[i];
+
+        var layer_color = layer.color;
+        if layer.texture_index >= 0 {
+            layer_color *= sample_triplanar(layer.texture_index, position, blend, position_dx, position_dy);
+        }
+
+        let weight = range_weight(height, layer.height_range, layer.blend_sharpness)
+            * range_weight(slope, layer.slope_range, layer.blend_sharpness);
+
+        base_color = mix(base_color, layer_color, weight);
+    }
+    pbr_input.material.base_color = base_color;
+
+    // ? alpha discard
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    var color: vec4<f32>;
+
+    // ? apply lighting
+    color = apply_pbr_lighting(pbr_input);
+
+    // ? apply in-shader post processing (fog, alpha-premultiply, and also tonemapping, debanding if the camera is non-hdr)
+    // ? note this does not include fullscreen postprocessing effects like bloom.
+    color = main_pass_post_lighting_processing(pbr_input, color);
+
+    return color;
+}
@@ -0,0 +1,170 @@
+use bevy::{
+    color::palettes::css::{DARK_OLIVEGREEN, FOREST_GREEN, SADDLE_BROWN},
+    input::common_conditions::input_toggle_active,
+    prelude::*,
+    window::PrimaryWindow,
+};
+use bevy_inspector_egui::{prelude::*, quick::ResourceInspectorPlugin, InspectorOptions};
+use procedural_generation::{common::CommonPlugin, terrain::lsystem::Species, utils::seed::Seed};
+
+const SPACING: f32 = 6.;
+
+#[derive(Reflect, Resource, InspectorOptions, Clone)]
+#[reflect(Resource, InspectorOptions)]
+struct Configuration {
+    seed: u64,
+    #[inspector(min = 1, max = 8)]
+    plants_per_species: usize,
+    #[inspector(min = 0.0, max = 1.0)]
+    rotation_speed: f32,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            seed: 0,
+            plants_per_species: 3,
+            rotation_speed: 0.1,
+        }
+    }
+}
+
+#[derive(Component)]
+struct Plant;
+
+#[derive(Resource)]
+struct PlantMaterials {
+    bark: Handle<StandardMaterial>,
+    leaves: Handle<StandardMaterial>,
+}
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: "L-system plants".into(),
+                visible: false, // false to make visible on Startup. Prevents long white window on start
+                present_mode: bevy::window::PresentMode::AutoNoVsync,
+                ..default()
+            }),
+            ..default()
+        }))
+        .init_resource::<Configuration>()
+        .add_plugins(
+            ResourceInspectorPlugin::<Configuration>::default()
+                .run_if(input_toggle_active(true, KeyCode::Escape)),
+        )
+        .add_plugins(CommonPlugin)
+        .add_plugins(bevy_framepace::FramepacePlugin)
+        .add_systems(Startup, (setup, make_visible))
+        .add_systems(Update, (spawn_plants, rotate_camera))
+        .run();
+}
+
+// Make visible on Startup. Prevents long white window on start
+fn make_visible(mut window: Query<&mut Window, With<PrimaryWindow>>) {
+    window.single_mut().visible = true;
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(0., 8., 24.).looking_at(Vec3::new(0., 3., 0.), Vec3::Y),
+        ..default()
+    });
+
+    commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            illuminance: 2000.,
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: Transform::from_xyz(4., 10., 6.).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(Plane3d::default().mesh().size(60., 60.)),
+        material: materials.add(Color::from(DARK_OLIVEGREEN)),
+        ..default()
+    });
+
+    commands.insert_resource(PlantMaterials {
+        bark: materials.add(Color::from(SADDLE_BROWN)),
+        leaves: materials.add(StandardMaterial {
+            base_color: Color::from(FOREST_GREEN),
+            cull_mode: None,
+            double_sided: true,
+            ..default()
+        }),
+    });
+}
+
+/// One row per species, generated again whenever the configuration changes.
+fn spawn_plants(
+    mut commands: Commands,
+    config: Res<Configuration>,
+    plant_materials: Res<PlantMaterials>,
+    plant_q: Query<Entity, With<Plant>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+
+    for plant in &plant_q {
+        commands.entity(plant).despawn_recursive();
+    }
+
+    let seed = Seed(config.seed);
+    let rows = Species::ALL.len() as f32;
+    let columns = config.plants_per_species as f32;
+
+    for (row, species) in Species::ALL.into_iter().enumerate() {
+        for column in 0..config.plants_per_species {
+            let plant = species.generate(seed.derive_index((row * 100 + column) as u64));
+            let position = Vec3::new(
+                (column as f32 - (columns - 1.) / 2.) * SPACING,
+                0.,
+                (row as f32 - (rows - 1.) / 2.) * SPACING,
+            );
+
+            commands
+                .spawn((
+                    SpatialBundle::from_transform(Transform::from_translation(position)),
+                    Plant,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(PbrBundle {
+                        mesh: meshes.add(plant.branches),
+                        material: plant_materials.bark.clone(),
+                        ..default()
+                    });
+                    // Some species have no leaves
+                    if plant.leaves.count_vertices() > 0 {
+                        parent.spawn(PbrBundle {
+                            mesh: meshes.add(plant.leaves),
+                            material: plant_materials.leaves.clone(),
+                            ..default()
+                        });
+                    }
+                });
+        }
+    }
+}
+
+fn rotate_camera(
+    time: Res<Time>,
+    config: Res<Configuration>,
+    mut camera_q: Query<&mut Transform, With<Camera>>,
+) {
+    for mut transform in &mut camera_q {
+        transform.rotate_around(
+            Vec3::ZERO,
+            Quat::from_rotation_y(config.rotation_speed * time.delta_seconds()),
+        );
+    }
+}
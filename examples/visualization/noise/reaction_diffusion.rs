@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{prelude::*, InspectorOptions};
+use common::{noise_playground, VecWrapper, IMAGE_DIMENSIONS};
+use procedural_generation::{
+    terrain::height_map::NOISE_EXTENT,
+    utils::{
+        noise::{
+            reaction_diffusion::{GrayScott, GrayScottSettings},
+            Noise,
+        },
+        seed::Seed,
+    },
+};
+
+#[path = "../../common/mod.rs"]
+mod common;
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Default)]
+enum Pattern {
+    #[default]
+    Coral,
+    Spots,
+    Maze,
+}
+
+#[derive(Reflect, Resource, InspectorOptions, Clone)]
+#[reflect(Resource, InspectorOptions)]
+struct Configuration {
+    pattern: Pattern,
+    seed: u64,
+    #[inspector(min = 0, max = 20000)]
+    iterations: usize,
+    /// Pixels per cell. The pattern wraps around, so it tiles the image.
+    #[inspector(min = 1., max = 16.)]
+    zoom: f32,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            pattern: Pattern::Coral,
+            seed: 0,
+            iterations: 4000,
+            zoom: 4.,
+        }
+    }
+}
+
+impl From<Configuration> for VecWrapper<u8> {
+    fn from(config: Configuration) -> Self {
+        let (image_width, image_height) = IMAGE_DIMENSIONS;
+
+        let settings = match config.pattern {
+            Pattern::Coral => GrayScottSettings::coral(),
+            Pattern::Spots => GrayScottSettings::spots(),
+            Pattern::Maze => GrayScottSettings::maze(),
+        };
+
+        // Noise coordinates of a pixel
+        let scale = Vec2::new(
+            NOISE_EXTENT / settings.width as f32,
+            NOISE_EXTENT / settings.height as f32,
+        ) / config.zoom;
+
+        let noise = GrayScott::new(
+            GrayScottSettings {
+                iterations: config.iterations,
+                ..settings
+            },
+            Seed(config.seed),
+        )
+        .map(|value: f32| {
+            // From dark rock to pale lichen
+            let rock = Vec3::new(40., 45., 50.);
+            let lichen = Vec3::new(190., 200., 120.);
+            let color = rock.lerp(lichen, value);
+
+            (color.x as u8, color.y as u8, color.z as u8)
+        });
+
+        let mut colors = Vec::with_capacity((image_width * image_height * 4) as usize);
+
+        for y in 0..image_height {
+            for x in 0..image_width {
+                let (r, g, b) = noise.get((x as f32 * scale.x, y as f32 * scale.y));
+
+                colors.extend([r, g, b, 255]);
+            }
+        }
+
+        VecWrapper { vec: colors }
+    }
+}
+
+fn main() {
+    noise_playground::<Configuration>();
+}
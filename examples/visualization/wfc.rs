@@ -0,0 +1,201 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{prelude::*, InspectorOptions};
+use common::{noise_playground, VecWrapper, IMAGE_DIMENSIONS};
+use procedural_generation::utils::{
+    seed::Seed,
+    wfc::{
+        overlapping::OverlappingModel,
+        solve,
+        tiled::{Tile, TiledModel},
+        WfcSettings,
+    },
+};
+
+#[path = "../common/mod.rs"]
+mod common;
+
+const GRASS: u32 = 0;
+const ROAD: u32 = 1;
+
+const WALL: u32 = 0;
+const FLOOR: u32 = 1;
+
+/// Color of the point `(u, v)` of a cell, from the value of the cell.
+type CellColor = Box<dyn Fn(usize, f32, f32) -> [u8; 3]>;
+
+/// Rooms separated by walls with doors. It wraps around, so the first row and column are also the
+/// walls after the last ones.
+const DUNGEON_SAMPLE: [&str; 11] = [
+    "###.#######",
+    "#.....#....",
+    "#.....#....",
+    "#..........",
+    "#.....#....",
+    "##.#####.##",
+    "#....#.....",
+    "#....#.....",
+    "#..........",
+    "#....#.....",
+    "#....#.....",
+];
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Default)]
+enum Model {
+    /// Village of roads, houses and trees from a tile set.
+    #[default]
+    Tiled,
+    /// Dungeon that looks like `DUNGEON_SAMPLE`.
+    Overlapping,
+}
+
+#[derive(Reflect, Resource, InspectorOptions, Clone)]
+#[reflect(Resource, InspectorOptions)]
+struct Configuration {
+    model: Model,
+    seed: u64,
+    #[inspector(min = 4, max = 64)]
+    cell_size: u32,
+    periodic: bool,
+    /// Size of the overlapping patterns.
+    #[inspector(min = 2, max = 4)]
+    n: usize,
+    /// Rotations and reflections of the overlapping patterns.
+    #[inspector(min = 1, max = 8)]
+    symmetry: usize,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration {
+            model: Model::Tiled,
+            seed: 0,
+            cell_size: 24,
+            periodic: false,
+            n: 3,
+            symmetry: 8,
+        }
+    }
+}
+
+fn village_tiles() -> TiledModel {
+    TiledModel::new(vec![
+        Tile::new_2d("grass", 10., [GRASS; 4]),
+        Tile::new_2d("tree", 3., [GRASS; 4]),
+        Tile::new_2d("house", 1., [GRASS; 4]),
+        Tile::new_2d("cross", 0.1, [ROAD; 4]),
+    ])
+    .with_rotations(Tile::new_2d("straight", 2., [ROAD, ROAD, GRASS, GRASS]))
+    .with_rotations(Tile::new_2d("corner", 0.5, [ROAD, GRASS, ROAD, GRASS]))
+    .with_rotations(Tile::new_2d("junction", 0.3, [ROAD, ROAD, ROAD, GRASS]))
+}
+
+/// Color of the point `(u, v)` of a village tile, both between 0 and 1.
+fn tile_color(tile: &Tile, u: f32, v: f32) -> [u8; 3] {
+    let (du, dv) = (u - 0.5, v - 0.5);
+    let road_width = 0.15;
+
+    // A bar from the center to every side with a road
+    let on_road = (tile.sockets[0] == ROAD && du >= -road_width && dv.abs() < road_width)
+        || (tile.sockets[1] == ROAD && du <= road_width && dv.abs() < road_width)
+        || (tile.sockets[2] == ROAD && dv >= -road_width && du.abs() < road_width)
+        || (tile.sockets[3] == ROAD && dv <= road_width && du.abs() < road_width);
+
+    if on_road {
+        return [150, 140, 120];
+    }
+
+    match tile.name.as_str() {
+        "tree" if du * du + dv * dv < 0.1 => [30, 90, 40],
+        "house" if du.abs() < 0.3 && dv.abs() < 0.3 => {
+            if dv < 0. {
+                [170, 60, 50]
+            } else {
+                [120, 80, 50]
+            }
+        }
+        _ => [90, 160, 70],
+    }
+}
+
+impl From<Configuration> for VecWrapper<u8> {
+    fn from(config: Configuration) -> Self {
+        let (image_width, image_height) = IMAGE_DIMENSIONS;
+        let cell_size = config.cell_size.max(1);
+        let (width, height) = (image_width / cell_size, image_height / cell_size);
+        let seed = Seed(config.seed);
+
+        let (cells, color): (_, CellColor) = match config.model {
+            Model::Tiled => {
+                let village = village_tiles();
+                let settings = WfcSettings {
+                    size: UVec3::new(width, height, 1),
+                    periodic: config.periodic,
+                    ..default()
+                };
+
+                (
+                    solve(&village.rules(), &settings, seed),
+                    Box::new(move |tile, u, v| tile_color(&village.tiles[tile], u, v)),
+                )
+            }
+            Model::Overlapping => {
+                let sample: Vec<u32> = DUNGEON_SAMPLE
+                    .iter()
+                    .flat_map(|row| row.chars())
+                    .map(|c| if c == '#' { WALL } else { FLOOR })
+                    .collect();
+                let dungeon = OverlappingModel::new(
+                    &sample,
+                    DUNGEON_SAMPLE[0].len(),
+                    DUNGEON_SAMPLE.len(),
+                    config.n,
+                    true,
+                    config.symmetry,
+                );
+
+                (
+                    dungeon
+                        .solve(width, height, config.periodic, seed)
+                        .map(|values| values.into_iter().map(|value| value as usize).collect()),
+                    Box::new(|value, _, _| {
+                        if value == WALL as usize {
+                            [40, 40, 50]
+                        } else {
+                            [200, 190, 160]
+                        }
+                    }),
+                )
+            }
+        };
+
+        let cells = cells.unwrap_or_else(|error| {
+            warn!("{error}");
+            Vec::new()
+        });
+
+        let mut colors = Vec::with_capacity((image_width * image_height * 4) as usize);
+
+        for y in 0..image_height {
+            for x in 0..image_width {
+                let (cell_x, cell_y) = (x / cell_size, y / cell_size);
+                let [r, g, b] = match cells.get((cell_x + cell_y * width) as usize) {
+                    Some(&value) if cell_x < width && cell_y < height => color(
+                        value,
+                        (x % cell_size) as f32 / cell_size as f32,
+                        (y % cell_size) as f32 / cell_size as f32,
+                    ),
+                    // Contradiction, or the part of the image that doesn't fit a whole cell
+                    _ => [0, 0, 0],
+                };
+
+                colors.extend([r, g, b, 255]);
+            }
+        }
+
+        VecWrapper { vec: colors }
+    }
+}
+
+fn main() {
+    noise_playground::<Configuration>();
+}
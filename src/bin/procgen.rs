@@ -0,0 +1,298 @@
+//! Headless terrain generator. Writes height maps, normal maps, biome maps and meshes to disk
+//! without opening a window or touching the GPU.
+//!
+//! ```sh
+//! cargo run --release --bin procgen -- --seed 42 --samples 512 --outputs height,biomes,glb
+//! ```
+
+use std::{env, fs, path::PathBuf, process::ExitCode, str::FromStr};
+
+use bevy::prelude::*;
+use image::{ImageBuffer, Rgb};
+
+use procedural_generation::{
+    terrain::{
+        biome::BiomeMap,
+        height_map::{
+            bake::{ambient_occlusion_map, normal_map, save_image, slope_map},
+            io::HeightMapFormat,
+            HeightMap,
+        },
+        mesh_export::{export_mesh, MeshExportFormat},
+    },
+    utils::{
+        noise::{cellular::Cellular, perlin::Perlin, Noise},
+        seed::Seed,
+    },
+};
+
+const USAGE: &str = "\
+Usage: procgen [OPTIONS]
+
+Options:
+  --seed <text>          World seed, a number or any text [default: 0]
+  --size <f32>           Side of the map in world units [default: 50]
+  --samples <usize>      Samples per side [default: 1000]
+  --min-depth <f32>      Lowest height [default: -3]
+  --max-depth <f32>      Highest height [default: 3]
+  --sea-level <f32>      Height of the sea, used for biomes [default: -0.5]
+  --preset <name>        Noise preset: smooth, detailed or cellular [default: smooth]
+  --smooth <passes>      Smoothing passes applied after generation [default: 0]
+  --outputs <list>       Comma separated: height, raw, hmap, normals, slope, ao, biomes,
+                         glb, obj
+                         [default: height,normals,biomes,glb]
+  --out <dir>            Output directory [default: .]
+  --name <prefix>        Prefix of the written files [default: terrain]
+  -h, --help             Print this help";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Preset {
+    /// Two octaves of Perlin noise, as used by the game.
+    Smooth,
+    /// Four octaves of Perlin noise.
+    Detailed,
+    Cellular,
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "smooth" => Ok(Preset::Smooth),
+            "detailed" => Ok(Preset::Detailed),
+            "cellular" => Ok(Preset::Cellular),
+            _ => Err(format!("unknown preset '{value}'")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Output {
+    Height(HeightMapFormat),
+    Normals,
+    Slope,
+    AmbientOcclusion,
+    Biomes,
+    Mesh(MeshExportFormat),
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "height" => Ok(Output::Height(HeightMapFormat::Png16)),
+            "raw" => Ok(Output::Height(HeightMapFormat::RawF32)),
+            "hmap" => Ok(Output::Height(HeightMapFormat::Binary)),
+            "normals" => Ok(Output::Normals),
+            "slope" => Ok(Output::Slope),
+            "ao" => Ok(Output::AmbientOcclusion),
+            "biomes" => Ok(Output::Biomes),
+            "glb" => Ok(Output::Mesh(MeshExportFormat::Glb)),
+            "obj" => Ok(Output::Mesh(MeshExportFormat::Obj)),
+            _ => Err(format!("unknown output '{value}'")),
+        }
+    }
+}
+
+struct Args {
+    seed: Seed,
+    size: f32,
+    samples: usize,
+    min_depth: f32,
+    max_depth: f32,
+    sea_level: f32,
+    preset: Preset,
+    smooth: usize,
+    outputs: Vec<Output>,
+    out: PathBuf,
+    name: String,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            seed: Seed::default(),
+            size: 50.,
+            samples: 1000,
+            min_depth: -3.,
+            max_depth: 3.,
+            sea_level: -0.5,
+            preset: Preset::Smooth,
+            smooth: 0,
+            outputs: vec![
+                Output::Height(HeightMapFormat::Png16),
+                Output::Normals,
+                Output::Biomes,
+                Output::Mesh(MeshExportFormat::Glb),
+            ],
+            out: PathBuf::from("."),
+            name: "terrain".into(),
+        }
+    }
+}
+
+impl Args {
+    /// `None` when the help was requested.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+        let mut parsed = Args::default();
+
+        while let Some(flag) = args.next() {
+            if flag == "-h" || flag == "--help" {
+                return Ok(None);
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for '{flag}'"))?;
+
+            match flag.as_str() {
+                "--seed" => parsed.seed = Seed::from_text(&value),
+                "--size" => parsed.size = parse_value(&flag, &value)?,
+                "--samples" => parsed.samples = parse_value(&flag, &value)?,
+                "--min-depth" => parsed.min_depth = parse_value(&flag, &value)?,
+                "--max-depth" => parsed.max_depth = parse_value(&flag, &value)?,
+                "--sea-level" => parsed.sea_level = parse_value(&flag, &value)?,
+                "--preset" => parsed.preset = value.parse()?,
+                "--smooth" => parsed.smooth = parse_value(&flag, &value)?,
+                "--outputs" => {
+                    parsed.outputs = value
+                        .split(',')
+                        .map(|output| output.trim().parse())
+                        .collect::<Result<_, _>>()?;
+                }
+                "--out" => parsed.out = PathBuf::from(value),
+                "--name" => parsed.name = value,
+                _ => return Err(format!("unknown option '{flag}'")),
+            }
+        }
+
+        if parsed.samples < 2 {
+            return Err("--samples must be at least 2".into());
+        }
+
+        Ok(Some(parsed))
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{value}' for '{flag}'"))
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("error: {error}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match generate(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn generate(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    // Same stream as the game uses for its terrain
+    let seed = Some(args.seed.derive("terrain").value());
+    let mut height_map = match args.preset {
+        Preset::Smooth => height_map(args, Perlin::new(&[(0.75, 1.), (0.25, 2.)], 256, seed)),
+        Preset::Detailed => height_map(
+            args,
+            Perlin::new(
+                &[(0.5, 1.), (0.25, 2.), (0.125, 4.), (0.075, 8.)],
+                256,
+                seed,
+            ),
+        ),
+        Preset::Cellular => height_map(args, Cellular::new(16, 16, seed)),
+    };
+
+    height_map.smooth(args.smooth);
+
+    fs::create_dir_all(&args.out)?;
+    let path = |suffix: &str| args.out.join(format!("{}_{suffix}", args.name));
+    let biome_map = BiomeMap::new(&height_map, args.sea_level, args.max_depth);
+
+    for output in &args.outputs {
+        let written = match output {
+            Output::Height(format) => {
+                let path = path(match format {
+                    HeightMapFormat::Png16 => "height.png",
+                    HeightMapFormat::RawF32 => "height.raw",
+                    HeightMapFormat::Binary => "height.hmap",
+                });
+                height_map.export(&path, *format)?;
+                path
+            }
+            Output::Normals => {
+                let path = path("normals.png");
+                save_image(&normal_map(&height_map), &path)?;
+                path
+            }
+            Output::Slope => {
+                let path = path("slope.png");
+                save_image(&slope_map(&height_map), &path)?;
+                path
+            }
+            Output::AmbientOcclusion => {
+                let path = path("ao.png");
+                save_image(&ambient_occlusion_map(&height_map, &default()), &path)?;
+                path
+            }
+            Output::Biomes => {
+                let path = path("biomes.png");
+                biome_image(&biome_map).save(&path)?;
+                path
+            }
+            Output::Mesh(format) => {
+                let path = path(match format {
+                    MeshExportFormat::Glb => "mesh.glb",
+                    MeshExportFormat::Obj => "mesh.obj",
+                });
+                let mesh = Mesh::from(height_map.clone())
+                    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, biome_map.vertex_colors());
+                export_mesh(&mesh, &path, *format)?;
+                path
+            }
+        };
+
+        println!("{}", written.display());
+    }
+
+    Ok(())
+}
+
+fn height_map(args: &Args, noise: impl Noise<Input = (f32, f32), Output = f32>) -> HeightMap {
+    HeightMap::new(
+        args.size,
+        args.samples,
+        args.min_depth,
+        args.max_depth,
+        noise,
+    )
+}
+
+/// Pixels go along x to the right and z downwards, like the samples of the height map.
+fn biome_image(biome_map: &BiomeMap) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let samples = biome_map.samples as u32;
+
+    ImageBuffer::from_fn(samples, samples, |x, z| {
+        let biome = biome_map.biomes[x as usize][z as usize];
+        let [r, g, b, _] = biome.color().to_srgba().to_u8_array();
+
+        Rgb([r, g, b])
+    })
+}
@@ -68,7 +68,7 @@ fn setup_camera(mut commands: Commands) {
 }
 
 #[derive(Resource)]
-struct CameraDistance(f32);
+pub struct CameraDistance(pub f32);
 
 fn change_camera_distance_with_mousewheel(
     mut camera_distance: ResMut<CameraDistance>,
@@ -4,32 +4,159 @@ use bevy::{
         extract_resource::{ExtractResource, ExtractResourcePlugin},
         render_asset::{RenderAssetUsages, RenderAssets},
         render_graph::{self, RenderGraph, RenderLabel},
-        render_resource::{binding_types::texture_storage_2d, *},
-        renderer::{RenderContext, RenderDevice},
+        render_resource::{
+            binding_types::{texture_storage_2d, uniform_buffer},
+            *,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
         texture::GpuImage,
         Render, RenderApp, RenderSet,
     },
 };
-use std::{borrow::Cow, f32::consts::PI};
+use std::{
+    borrow::Cow,
+    f32::consts::PI,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::utils::automaton::Rule;
 
 /// This example uses a shader source file from the assets subdirectory
 const SHADER_ASSET_PATH: &str = "shaders/game_of_life.wgsl";
 
-const DISPLAY_FACTOR: u32 = 1;
-const SIZE: (u32, u32) = (4096 / DISPLAY_FACTOR, 4096 / DISPLAY_FACTOR);
-const SCALE_FACTOR: f32 = 0.5;
+/// Must match `@workgroup_size` in the shader.
 const WORKGROUP_SIZE: u32 = 8;
 
 pub struct GameOfLifeComputePlugin;
 
+/// Rule and controls of the automaton. Changes apply from the next step, except `size` which is
+/// only read on startup.
+#[derive(Resource, Clone, Debug, ExtractResource)]
+pub struct CellularAutomatonSettings {
+    pub rule: Rule,
+    /// Cells along each side, both multiples of the workgroup size.
+    pub size: UVec2,
+    /// At most one step is run per frame. 0 or less steps every frame.
+    pub steps_per_second: f32,
+    pub paused: bool,
+    /// Runs one step, even if paused. Cleared once it has run.
+    pub single_step: bool,
+    /// Fills the grid randomly again. Cleared once it has run.
+    pub reset: bool,
+    /// Chance of each cell to be alive after a reset.
+    pub density: f32,
+    pub seed: u32,
+    /// Scale of the displayed grid.
+    pub scale: f32,
+}
+
+impl Default for CellularAutomatonSettings {
+    fn default() -> Self {
+        CellularAutomatonSettings {
+            rule: Rule::LIFE,
+            size: UVec2::splat(4096),
+            steps_per_second: 0.,
+            paused: false,
+            single_step: false,
+            reset: false,
+            density: 0.06,
+            seed: 0,
+            scale: 0.5,
+        }
+    }
+}
+
+impl CellularAutomatonSettings {
+    pub fn validate(&self) -> Result<(), CellularAutomatonSettingsError> {
+        if self.size.min_element() == 0 || self.size % WORKGROUP_SIZE != UVec2::ZERO {
+            return Err(CellularAutomatonSettingsError::InvalidSize(self.size));
+        }
+
+        if self.rule.states < 2 {
+            return Err(CellularAutomatonSettingsError::TooFewStates(
+                self.rule.states,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CellularAutomatonSettingsError {
+    /// Zero, or not a multiple of the workgroup size.
+    InvalidSize(UVec2),
+    TooFewStates(u8),
+}
+
+impl fmt::Display for CellularAutomatonSettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CellularAutomatonSettingsError::InvalidSize(size) => write!(
+                f,
+                "automaton size {size} is not a non-zero multiple of {WORKGROUP_SIZE}"
+            ),
+            CellularAutomatonSettingsError::TooFewStates(states) => {
+                write!(f, "automaton rules need at least 2 states, not {states}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CellularAutomatonSettingsError {}
+
+/// What the render world does with the automaton this frame, decided from
+/// `CellularAutomatonSettings`.
+#[derive(Resource, Clone, Copy, Debug, Default, ExtractResource)]
+struct CellularAutomatonControl {
+    step: bool,
+    reset: bool,
+    /// Time since the last step.
+    elapsed: f32,
+}
+
+/// `CellularAutomatonSettings` as the shader reads them.
+#[derive(ShaderType, Clone, Copy, Debug, Default)]
+struct CellularAutomatonUniform {
+    birth: u32,
+    survival: u32,
+    states: u32,
+    size: UVec2,
+    density: f32,
+    seed: u32,
+}
+
+impl From<&CellularAutomatonSettings> for CellularAutomatonUniform {
+    fn from(settings: &CellularAutomatonSettings) -> Self {
+        CellularAutomatonUniform {
+            birth: settings.rule.birth,
+            survival: settings.rule.survival,
+            states: settings.rule.states as u32,
+            size: settings.size,
+            density: settings.density,
+            seed: settings.seed,
+        }
+    }
+}
+
 impl Plugin for GameOfLifeComputePlugin {
     fn build(&self, app: &mut App) {
         // Extract the game of life image resource from the main world into the render world
         // for operation on by the compute shader and display on the sprite.
         app //
-            .add_plugins(ExtractResourcePlugin::<GameOfLifeImages>::default())
+            .init_resource::<CellularAutomatonSettings>()
+            .init_resource::<CellularAutomatonControl>()
+            .add_plugins((
+                ExtractResourcePlugin::<GameOfLifeImages>::default(),
+                ExtractResourcePlugin::<CellularAutomatonSettings>::default(),
+                ExtractResourcePlugin::<CellularAutomatonControl>::default(),
+            ))
             .add_systems(Startup, setup)
-            .add_systems(Update, switch_textures);
+            .add_systems(Update, (control_automaton, switch_textures));
 
         let render_app = app.sub_app_mut(RenderApp);
         render_app.add_systems(
@@ -53,14 +180,30 @@ struct GameOfLifeMarker;
 
 fn setup(
     mut commands: Commands,
+    mut settings: ResMut<CellularAutomatonSettings>,
     mut images: ResMut<Assets<Image>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
+    let defaults = CellularAutomatonSettings::default();
+    while let Err(error) = settings.validate() {
+        match error {
+            CellularAutomatonSettingsError::InvalidSize(_) => {
+                error!("{error}, using the default size");
+                settings.size = defaults.size;
+            }
+            CellularAutomatonSettingsError::TooFewStates(_) => {
+                error!("{error}, using the default rule");
+                settings.rule = defaults.rule;
+            }
+        }
+    }
+    let size = settings.size;
+
     let mut image = Image::new_fill(
         Extent3d {
-            width: SIZE.0,
-            height: SIZE.1,
+            width: size.x,
+            height: size.y,
             depth_or_array_layers: 1,
         },
         TextureDimension::D2,
@@ -81,11 +224,12 @@ fn setup(
     let image0 = images.add(image.clone());
     let image1 = images.add(image);
 
-    // let quad_handle = meshes.add(Rectangle::new(SIZE.0 as f32, SIZE.1 as f32));
-    let shape_handle = meshes.add(Sphere::new(SIZE.0 as f32 / PI).mesh().ico(20).unwrap());
+    // let quad_handle = meshes.add(Rectangle::new(size.x as f32, size.y as f32));
+    let shape_handle = meshes.add(Sphere::new(size.x as f32 / PI).mesh().ico(20).unwrap());
 
     let material_handle = materials.add(StandardMaterial {
-        base_color_texture: Some(image0.clone()),
+        // Written by the init pass
+        base_color_texture: Some(image1.clone()),
         alpha_mode: AlphaMode::Blend,
         unlit: true,
         cull_mode: Some(Face::Front),
@@ -98,22 +242,22 @@ fn setup(
             mesh: shape_handle.clone(),
             material: material_handle,
             transform: Transform::from_xyz(
-                0., 0., //SIZE.1 as f32 / 2. * SCALE_FACTOR * DISPLAY_FACTOR as f32 - 2.,
+                0., 0., //size.y as f32 / 2. * settings.scale - 2.,
                 0.,
             )
             .with_rotation(Quat::from_rotation_y(PI))
-            .with_scale(Vec3::splat(SCALE_FACTOR * DISPLAY_FACTOR as f32)),
+            .with_scale(Vec3::splat(settings.scale)),
             ..default()
         },
         GameOfLifeMarker,
     ));
 
     // commands.spawn((SpriteBundle {
-    //     transform: Transform::from_scale(Vec3::splat(DISPLAY_FACTOR as f32)),
+    //     transform: Transform::from_scale(Vec3::splat(settings.scale)),
     //     texture: image0.clone(),
     //     sprite: Sprite {
     //         // color: (),
-    //         custom_size: Some(Vec2::new(SIZE.0 as f32, SIZE.1 as f32)),
+    //         custom_size: Some(Vec2::new(size.x as f32, size.y as f32)),
     //         ..Default::default()
     //     },
     //     ..default()
@@ -122,32 +266,75 @@ fn setup(
     commands.insert_resource(GameOfLifeImages {
         texture_a: image0,
         texture_b: image1,
+        size,
+        written: Arc::new(AtomicUsize::new(1)),
     });
 }
 
-// Switch texture to display every frame to show the one that was written to most recently.
+/// Decides whether the automaton steps or resets this frame.
+fn control_automaton(
+    mut settings: ResMut<CellularAutomatonSettings>,
+    mut control: ResMut<CellularAutomatonControl>,
+    images: Option<Res<GameOfLifeImages>>,
+    time: Res<Time>,
+) {
+    // The textures can't be resized once created
+    if let Some(images) = images {
+        if settings.size != images.size {
+            warn!("Automaton size can only be set before startup");
+            settings.size = images.size;
+        }
+    }
+
+    if let Err(error) = settings.validate() {
+        error!("{error}, pausing the automaton");
+        settings.paused = true;
+        *control = CellularAutomatonControl::default();
+        return;
+    }
+
+    control.elapsed += time.delta_seconds();
+    let due = settings.steps_per_second <= 0. || control.elapsed * settings.steps_per_second >= 1.;
+
+    control.reset = settings.reset;
+    control.step = !settings.reset && (settings.single_step || (!settings.paused && due));
+
+    if control.step || control.reset {
+        control.elapsed = 0.;
+    }
+
+    // Only written when set, so the settings don't change every frame
+    if settings.reset || settings.single_step {
+        settings.reset = false;
+        settings.single_step = false;
+    }
+}
+
+/// Shows the texture the compute shader wrote to most recently, once it has run.
 fn switch_textures(
     images: Res<GameOfLifeImages>,
     material_q: Query<&Handle<StandardMaterial>, With<GameOfLifeMarker>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    let material_handle_r = material_q.get_single();
-
-    if material_handle_r.is_err() {
+    let Ok(handle) = material_q.get_single() else {
+        return;
+    };
+
+    let written = match images.written.load(Ordering::Relaxed) {
+        0 => &images.texture_a,
+        _ => &images.texture_b,
+    };
+
+    // Only written when it changes, so the material isn't prepared again every frame
+    let shown = materials
+        .get(handle)
+        .and_then(|material| material.base_color_texture.as_ref());
+    if shown == Some(written) {
         return;
     }
 
-    let material = materials
-        .get_mut(material_handle_r.unwrap())
-        .expect("Material Handle did not have a Material");
-
-    if let Some(image_handle) = &mut material.base_color_texture {
-        if *image_handle == images.texture_a {
-            *image_handle = images.texture_b.clone_weak();
-        } else {
-            *image_handle = images.texture_a.clone_weak();
-        }
-        // info!("CHANGEDD ASDASD");
+    if let Some(material) = materials.get_mut(handle) {
+        material.base_color_texture = Some(written.clone_weak());
     }
 }
 
@@ -158,29 +345,42 @@ struct GameOfLifeLabel;
 struct GameOfLifeImages {
     texture_a: Handle<Image>,
     texture_b: Handle<Image>,
+    size: UVec2,
+    /// Texture the last dispatch wrote to, 0 for A and 1 for B. Set by the render world.
+    written: Arc<AtomicUsize>,
 }
 
 #[derive(Resource)]
 struct GameOfLifeImageBindGroups([BindGroup; 2]);
 
+#[allow(clippy::too_many_arguments)]
 fn prepare_bind_group(
     mut commands: Commands,
     pipeline: Res<GameOfLifePipeline>,
     gpu_images: Res<RenderAssets<GpuImage>>,
     game_of_life_images: Res<GameOfLifeImages>,
+    settings: Res<CellularAutomatonSettings>,
+    mut uniform: Local<UniformBuffer<CellularAutomatonUniform>>,
     render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
 ) {
+    let mut values = CellularAutomatonUniform::from(&*settings);
+    // Always the size of the textures, even if the settings were changed after startup
+    values.size = game_of_life_images.size;
+    uniform.set(values);
+    uniform.write_buffer(&render_device, &render_queue);
+
     let view_a = gpu_images.get(&game_of_life_images.texture_a).unwrap();
     let view_b = gpu_images.get(&game_of_life_images.texture_b).unwrap();
     let bind_group_0 = render_device.create_bind_group(
         None,
         &pipeline.texture_bind_group_layout,
-        &BindGroupEntries::sequential((&view_a.texture_view, &view_b.texture_view)),
+        &BindGroupEntries::sequential((&view_a.texture_view, &view_b.texture_view, &*uniform)),
     );
     let bind_group_1 = render_device.create_bind_group(
         None,
         &pipeline.texture_bind_group_layout,
-        &BindGroupEntries::sequential((&view_b.texture_view, &view_a.texture_view)),
+        &BindGroupEntries::sequential((&view_b.texture_view, &view_a.texture_view, &*uniform)),
     );
     commands.insert_resource(GameOfLifeImageBindGroups([bind_group_0, bind_group_1]));
 }
@@ -202,6 +402,7 @@ impl FromWorld for GameOfLifePipeline {
                 (
                     texture_storage_2d(TextureFormat::Rgba32Float, StorageTextureAccess::ReadOnly),
                     texture_storage_2d(TextureFormat::Rgba32Float, StorageTextureAccess::WriteOnly),
+                    uniform_buffer::<CellularAutomatonUniform>(false),
                 ),
             ),
         );
@@ -234,20 +435,25 @@ impl FromWorld for GameOfLifePipeline {
     }
 }
 
+#[derive(Clone, Copy)]
 enum GameOfLifeState {
     Loading,
     Init,
+    /// Index of the bind group of the last step.
     Update(usize),
 }
 
 struct GameOfLifeNode {
     state: GameOfLifeState,
+    /// Whether the pipeline of `state` runs this frame.
+    dispatch: bool,
 }
 
 impl Default for GameOfLifeNode {
     fn default() -> Self {
         Self {
             state: GameOfLifeState::Loading,
+            dispatch: false,
         }
     }
 }
@@ -256,35 +462,35 @@ impl render_graph::Node for GameOfLifeNode {
     fn update(&mut self, world: &mut World) {
         let pipeline = world.resource::<GameOfLifePipeline>();
         let pipeline_cache = world.resource::<PipelineCache>();
+        let control = world.resource::<CellularAutomatonControl>();
 
         // if the corresponding pipeline has loaded, transition to the next stage
-        match self.state {
+        (self.state, self.dispatch) = match self.state {
             GameOfLifeState::Loading => {
                 match pipeline_cache.get_compute_pipeline_state(pipeline.init_pipeline) {
-                    CachedPipelineState::Ok(_) => {
-                        self.state = GameOfLifeState::Init;
-                    }
+                    CachedPipelineState::Ok(_) => (GameOfLifeState::Init, true),
                     CachedPipelineState::Err(err) => {
                         panic!("Initializing assets/{SHADER_ASSET_PATH}:\n{err}")
                     }
-                    _ => {}
+                    _ => (GameOfLifeState::Loading, false),
                 }
             }
+            _ if control.reset => (GameOfLifeState::Init, true),
+            GameOfLifeState::Init | GameOfLifeState::Update(_) if !control.step => {
+                (self.state, false)
+            }
             GameOfLifeState::Init => {
                 if let CachedPipelineState::Ok(_) =
                     pipeline_cache.get_compute_pipeline_state(pipeline.update_pipeline)
                 {
-                    self.state = GameOfLifeState::Update(1);
+                    // Init writes to texture B, like the step with bind group 0
+                    (GameOfLifeState::Update(1), true)
+                } else {
+                    (GameOfLifeState::Init, false)
                 }
             }
-            GameOfLifeState::Update(0) => {
-                self.state = GameOfLifeState::Update(1);
-            }
-            GameOfLifeState::Update(1) => {
-                self.state = GameOfLifeState::Update(0);
-            }
-            GameOfLifeState::Update(_) => unreachable!(),
-        }
+            GameOfLifeState::Update(index) => (GameOfLifeState::Update(1 - index), true),
+        };
     }
 
     fn run(
@@ -293,9 +499,15 @@ impl render_graph::Node for GameOfLifeNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), render_graph::NodeRunError> {
+        if !self.dispatch {
+            return Ok(());
+        }
+
         let bind_groups = &world.resource::<GameOfLifeImageBindGroups>().0;
         let pipeline_cache = world.resource::<PipelineCache>();
         let pipeline = world.resource::<GameOfLifePipeline>();
+        let images = world.resource::<GameOfLifeImages>();
+        let workgroups = images.size / WORKGROUP_SIZE;
 
         let mut pass = render_context
             .command_encoder()
@@ -310,8 +522,8 @@ impl render_graph::Node for GameOfLifeNode {
                     .unwrap();
                 pass.set_bind_group(0, &bind_groups[0], &[]);
                 pass.set_pipeline(init_pipeline);
-                // pass.dispatch_workgroups(SIZE.0 / WORKGROUP_SIZE, SIZE.1 / WORKGROUP_SIZE, 1);
-                pass.dispatch_workgroups(SIZE.0 / WORKGROUP_SIZE, SIZE.1 / WORKGROUP_SIZE, 1);
+                pass.dispatch_workgroups(workgroups.x, workgroups.y, 1);
+                images.written.store(1, Ordering::Relaxed);
             }
             GameOfLifeState::Update(index) => {
                 let update_pipeline = pipeline_cache
@@ -319,11 +531,39 @@ impl render_graph::Node for GameOfLifeNode {
                     .unwrap();
                 pass.set_bind_group(0, &bind_groups[index], &[]);
                 pass.set_pipeline(update_pipeline);
-                // pass.dispatch_workgroups(SIZE.0 / WORKGROUP_SIZE, SIZE.1 / WORKGROUP_SIZE, 1);
-                pass.dispatch_workgroups(SIZE.0 / WORKGROUP_SIZE, SIZE.1 / WORKGROUP_SIZE, 1);
+                pass.dispatch_workgroups(workgroups.x, workgroups.y, 1);
+                // Bind group 0 writes to texture B, and 1 to texture A
+                images.written.store(1 - index, Ordering::Relaxed);
             }
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        assert_eq!(CellularAutomatonSettings::default().validate(), Ok(()));
+
+        for size in [UVec2::new(100, 64), UVec2::new(64, 0)] {
+            let settings = CellularAutomatonSettings { size, ..default() };
+            assert_eq!(
+                settings.validate(),
+                Err(CellularAutomatonSettingsError::InvalidSize(size))
+            );
+        }
+
+        let settings = CellularAutomatonSettings {
+            rule: Rule::LIFE.with_states(1),
+            ..default()
+        };
+        assert_eq!(
+            settings.validate(),
+            Err(CellularAutomatonSettingsError::TooFewStates(1))
+        );
+    }
+}
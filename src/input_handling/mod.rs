@@ -13,6 +13,15 @@ pub struct KeyBindings {
     pub jump: Option<KeyCode>,
     // pub down: Option<KeyCode>,
     pub sprint: Option<KeyCode>,
+    pub swim_up: Option<KeyCode>,
+    pub swim_down: Option<KeyCode>,
+    pub editor: Option<KeyCode>,
+    pub undo: Option<KeyCode>,
+    pub redo: Option<KeyCode>,
+    pub save: Option<KeyCode>,
+    pub load: Option<KeyCode>,
+    /// Generates a new world with a random seed.
+    pub new_world: Option<KeyCode>,
     pub pause: Option<KeyCode>,
 }
 
@@ -26,6 +35,14 @@ impl Default for KeyBindings {
             jump: Some(KeyCode::Space),
             // down: Some(KeyCode::ControlLeft),
             sprint: Some(KeyCode::ShiftLeft),
+            swim_up: Some(KeyCode::Space),
+            swim_down: Some(KeyCode::ControlLeft),
+            editor: Some(KeyCode::F1),
+            undo: Some(KeyCode::KeyZ),
+            redo: Some(KeyCode::KeyY),
+            save: Some(KeyCode::F5),
+            load: Some(KeyCode::F9),
+            new_world: Some(KeyCode::F8),
             pause: Some(KeyCode::Escape),
         }
     }
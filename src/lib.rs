@@ -1,5 +1,18 @@
 pub mod utils {
+    pub mod automaton;
+    pub mod delaunay;
     pub mod noise;
+    pub mod pathfinding;
+    pub mod sampling;
+    pub mod seed;
+    pub mod wfc;
+}
+
+pub mod terrain {
+    pub mod biome;
+    pub mod height_map;
+    pub mod lsystem;
+    pub mod mesh_export;
 }
 
 pub mod common;
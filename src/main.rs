@@ -12,7 +12,9 @@ use common::CommonPlugin;
 use hud::HUDPlugin;
 use input_handling::InputHandlingPlugin;
 use player::PlayerPlugin;
+use save::SavePlugin;
 use terrain::TerrainPlugin;
+use ui::editor::EditorPlugin;
 
 mod camera;
 mod common;
@@ -21,6 +23,7 @@ mod hud;
 mod input_handling;
 mod player;
 mod post_processing;
+mod save;
 mod terrain;
 mod ui;
 mod utils;
@@ -71,6 +74,8 @@ fn main() {
             PlayerPlugin,
             InputHandlingPlugin,
             TerrainPlugin,
+            EditorPlugin,
+            SavePlugin,
             // PostProcessPlugin,
             // GameOfLifeComputePlugin,
         ))
@@ -1,12 +1,21 @@
 use bevy::{color::palettes::css::FUCHSIA, prelude::*};
 use bevy_rapier3d::prelude::*;
 
-use movement::{MovementStats, PlayerAcceleration, PlayerVelocity};
+use movement::{MovementMode, MovementStats, PlayerAcceleration, SwimFactor, SwimStats};
 
 use crate::AppState;
 
 mod movement;
 
+pub use movement::PlayerVelocity;
+
+/// Where the player starts, above the terrain.
+pub const SPAWN_POINT: Vec3 = Vec3::new(0., 8., 0.);
+/// Height of the player's capsule, from bottom to top.
+pub const HEIGHT: f32 = 1.5;
+/// Radius of the player's capsule.
+pub const RADIUS: f32 = HEIGHT / 5.5;
+
 pub struct PlayerPlugin;
 
 #[derive(Component)]
@@ -17,6 +26,8 @@ struct PlayerBundle {
     movement: MovementStats,
     velocity: PlayerVelocity,
     acceleration: PlayerAcceleration,
+    mode: MovementMode,
+    swim_factor: SwimFactor,
     // weapon: Weapon,
     player_mark: Player,
 }
@@ -43,8 +54,6 @@ fn setup_player(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    const HEIGHT: f32 = 1.5;
-
     commands
         .spawn(PlayerBundle {
             movement: MovementStats {
@@ -52,20 +61,28 @@ fn setup_player(
                 sprinting: false,
                 sprinting_factor: 1.5,
                 jump_height: 1.25,
+                swimming: SwimStats {
+                    speed_factor: 0.6,
+                    start_depth: 0.4,
+                    float_depth: 0.5,
+                    buoyancy: 1.5,
+                    drag: 3.,
+                    vertical_acceleration: 20.,
+                    transition_time: 0.3,
+                },
             },
             acceleration: PlayerAcceleration(Vec3::ZERO),
             velocity: PlayerVelocity(Vec3::ZERO),
+            mode: MovementMode::Falling,
+            swim_factor: SwimFactor(0.),
             // weapon: Weapon::new(20., 10.),
             player_mark: Player {},
         })
-        .insert(SpatialBundle::from_transform(Transform::from_xyz(
-            0., 8., 0.,
+        .insert(SpatialBundle::from_transform(Transform::from_translation(
+            SPAWN_POINT,
         )))
         .insert(RigidBody::KinematicPositionBased)
-        .insert({
-            let radius = HEIGHT / 5.5;
-            Collider::capsule_y(HEIGHT / 2. - radius, radius)
-        })
+        .insert(Collider::capsule_y(HEIGHT / 2. - RADIUS, RADIUS))
         // .insert(Collider::cuboid(HEIGHT / 5., HEIGHT / 2., HEIGHT / 8.))
         .insert(LockedAxes::ROTATION_LOCKED_X | LockedAxes::ROTATION_LOCKED_Z)
         .insert(KinematicCharacterController {
@@ -1,7 +1,10 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-use crate::input_handling::KeyBindings;
+use crate::{
+    input_handling::KeyBindings,
+    terrain::{planet::RadialGravity, water::Submerged},
+};
 
 use super::{CameraHolder, ModelHolder, Player};
 
@@ -11,13 +14,42 @@ pub(super) struct MovementStats {
     pub(super) sprinting: bool,
     pub(super) sprinting_factor: f32,
     pub(super) jump_height: f32,
+    pub(super) swimming: SwimStats,
 }
 
+#[derive(Clone, Copy)]
+pub(super) struct SwimStats {
+    /// Multiplier of the horizontal speed while swimming.
+    pub(super) speed_factor: f32,
+    /// Depth of the player center below the surface at which swimming starts.
+    pub(super) start_depth: f32,
+    /// Depth at which buoyancy and gravity cancel each other.
+    pub(super) float_depth: f32,
+    /// Buoyancy acceleration per unit of depth below `float_depth`, relative to gravity.
+    pub(super) buoyancy: f32,
+    pub(super) drag: f32,
+    pub(super) vertical_acceleration: f32,
+    /// Seconds it takes to fully switch between walking and swimming.
+    pub(super) transition_time: f32,
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum MovementMode {
+    Grounded,
+    Falling,
+    Swimming,
+}
+
+/// How much the player is swimming, from 0 (out of the water) to 1 (swimming).
+/// Blends between both movement modes to avoid abrupt changes when entering or leaving water.
+#[derive(Component)]
+pub(super) struct SwimFactor(pub f32);
+
 #[derive(Component)]
 pub(super) struct PlayerAcceleration(pub Vec3);
 
 #[derive(Component)]
-pub(super) struct PlayerVelocity(pub Vec3);
+pub struct PlayerVelocity(pub Vec3);
 
 pub(super) fn move_player(
     fixed_time: Res<Time<Fixed>>,
@@ -37,19 +69,38 @@ pub(super) fn move_player(
             &mut MovementStats,
             &mut PlayerAcceleration,
             &mut PlayerVelocity,
+            &mut MovementMode,
+            &mut SwimFactor,
         ),
         With<Player>,
     >,
     mut controller_q: Query<&mut KinematicCharacterController, With<Player>>,
     output_q: Query<&KinematicCharacterControllerOutput, With<Player>>,
+    submerged_q: Query<&Submerged, With<Player>>,
     rapier_configuration: Res<RapierConfiguration>,
+    radial_gravity: Option<Res<RadialGravity>>,
 ) {
     let camera_transform = cameraholder_q.single();
 
+    let (
+        mut player_transform,
+        mut movement_stats,
+        mut player_acceleration,
+        mut player_velocity,
+        mut movement_mode,
+        mut swim_factor,
+    ) = player_q.single_mut();
+
+    // Gravity points to the center of a planet, and up is away from it
+    let gravity = radial_gravity.map_or(rapier_configuration.gravity, |radial_gravity| {
+        radial_gravity.at(player_transform.translation)
+    });
+    let up = gravity.try_normalize().map_or(Vec3::Y, |down| -down);
+
     let mut input_vector = Vec3::ZERO;
     let local_z = camera_transform.local_z();
-    let forward = -Vec3::new(local_z.x, 0., local_z.z).normalize_or_zero();
-    let right = Vec3::new(local_z.z, 0., -local_z.x).normalize_or_zero();
+    let forward = -local_z.reject_from_normalized(up).normalize_or_zero();
+    let right = forward.cross(up);
 
     if let Some(forward_key) = keybindings.forward {
         if keyboard_input.pressed(forward_key) {
@@ -75,10 +126,21 @@ pub(super) fn move_player(
         }
     }
 
-    let (mut player_transform, mut movement_stats, mut player_acceleration, mut player_velocity) =
-        player_q.single_mut();
+    let swim_stats = movement_stats.swimming;
+    let submerged_depth = submerged_q
+        .get_single()
+        .ok()
+        .map(|submerged| submerged.depth);
+
+    // Ease in and out of swimming
+    let swim_target = match submerged_depth {
+        Some(depth) if depth > swim_stats.start_depth => 1.,
+        _ => 0.,
+    };
+    let max_step = fixed_time.delta_seconds() / swim_stats.transition_time;
+    swim_factor.0 += (swim_target - swim_factor.0).clamp(-max_step, max_step);
 
-    let mut speed = movement_stats.base_speed;
+    let mut speed = movement_stats.base_speed * 1_f32.lerp(swim_stats.speed_factor, swim_factor.0);
 
     if let Some(sprint_key) = keybindings.sprint {
         if keyboard_input.just_pressed(sprint_key) {
@@ -92,17 +154,30 @@ pub(super) fn move_player(
 
     let input_vector_normalized = input_vector.normalize_or_zero();
 
+    // Stand along the local up, which changes while walking around a planet
+    let tilt = Quat::from_rotation_arc(*player_transform.up(), up);
+    player_transform.rotate(tilt);
+
     // Rotate model by movement dir
     if input_vector_normalized.is_normalized() {
-        player_transform.look_to(input_vector_normalized, Vec3::Y);
+        player_transform.look_to(input_vector_normalized, up);
     }
 
     let mut movement_translation = input_vector_normalized * speed;
 
     let mut controller = controller_q.single_mut();
+    controller.up = up;
 
     if let Ok(output) = output_q.get_single() {
-        if output.grounded {
+        *movement_mode = if swim_factor.0 > 0.5 {
+            MovementMode::Swimming
+        } else if output.grounded {
+            MovementMode::Grounded
+        } else {
+            MovementMode::Falling
+        };
+
+        if *movement_mode == MovementMode::Grounded {
             player_acceleration.0 = Vec3::ZERO;
             player_velocity.0 = Vec3::ZERO;
 
@@ -113,14 +188,43 @@ pub(super) fn move_player(
                     // player_translation.y +=
                     //     movement_stats.jump_height / 2. / fixed_time.delta_seconds();
 
-                    player_velocity.0.y +=
-                        (2. * (-rapier_configuration.gravity.y) * movement_stats.jump_height)
-                            .sqrt();
+                    player_velocity.0 +=
+                        up * (2. * gravity.length() * movement_stats.jump_height).sqrt();
                 }
             }
         } else {
+            // Buoyancy pushes the player towards float_depth, swim keys move it up and down
+            let depth = submerged_depth.unwrap_or(0.);
+            let mut swim_input = 0.;
+
+            if let Some(swim_up_key) = keybindings.swim_up {
+                if keyboard_input.pressed(swim_up_key) {
+                    swim_input += 1.;
+                }
+            }
+
+            if let Some(swim_down_key) = keybindings.swim_down {
+                if keyboard_input.pressed(swim_down_key) {
+                    swim_input -= 1.;
+                }
+            }
+
+            let water_acceleration =
+                -gravity * swim_stats.buoyancy * (depth - swim_stats.float_depth).clamp(-1., 1.)
+                    + up * swim_input * swim_stats.vertical_acceleration;
+
             // Add gravity acceleration to player if not grounded
-            player_acceleration.0 = rapier_configuration.gravity;
+            player_acceleration.0 = gravity.lerp(water_acceleration, swim_factor.0);
+
+            // Water drag
+            player_velocity.0 *=
+                (-swim_stats.drag * swim_factor.0 * fixed_time.delta_seconds()).exp();
+
+            // Don't push into the sea floor
+            let vertical_velocity = player_velocity.0.dot(up);
+            if output.grounded && vertical_velocity < 0. {
+                player_velocity.0 -= up * vertical_velocity;
+            }
         }
 
         //Calculate velocity
@@ -0,0 +1,361 @@
+use std::{fmt, fs, io, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::CameraDistance,
+    input_handling::KeyBindings,
+    player::{CameraHolder, Player, PlayerVelocity, SPAWN_POINT},
+    terrain::{
+        generate_height_map, height_map::HeightDelta, height_map::HeightMap, spawn_terrain,
+        voxel::VoxelConfig, MapInfo, RegenerateTerrain, TerrainChunk,
+    },
+    ui::editor::sculpt::SculptHistory,
+    utils::seed::{Seed, WorldSeed},
+    AppState,
+};
+
+/// Increased whenever `SaveFile` changes in a way older files can't be read with.
+pub const SAVE_VERSION: u32 = 1;
+const SAVE_PATH: &str = "saves/world.ron";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                save_world,
+                load_world.before(spawn_terrain),
+                new_world.before(spawn_terrain),
+            )
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+/// Everything needed to reconstruct a world. The terrain is generated again from its seed and
+/// configuration, so only the samples that were edited are stored.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub version: u32,
+    pub seed: Seed,
+    pub map_info: MapInfo,
+    pub voxel_config: VoxelConfig,
+    pub terrain_edits: Vec<HeightDelta>,
+    pub player: PlayerState,
+    pub camera: CameraState,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub velocity: [f32; 3],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraState {
+    pub distance: f32,
+    /// Rotation of the `CameraHolder`, where the player is looking.
+    pub rotation: [f32; 4],
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "I/O error: {error}"),
+            SaveError::Serialize(error) => write!(f, "couldn't serialize the world: {error}"),
+            SaveError::Deserialize(error) => write!(f, "invalid save file: {error}"),
+            SaveError::UnsupportedVersion(version) => write!(
+                f,
+                "save file version {version} isn't supported, expected {SAVE_VERSION}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+impl SaveFile {
+    pub fn to_ron(&self) -> Result<String, SaveError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(SaveError::Serialize)
+    }
+
+    pub fn from_ron(contents: &str) -> Result<SaveFile, SaveError> {
+        let save: SaveFile = ron::from_str(contents).map_err(SaveError::Deserialize)?;
+
+        if save.version != SAVE_VERSION {
+            return Err(SaveError::UnsupportedVersion(save.version));
+        }
+
+        Ok(save)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        if let Some(directory) = path.as_ref().parent() {
+            fs::create_dir_all(directory)?;
+        }
+
+        fs::write(path, self.to_ron()?)?;
+
+        Ok(())
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<SaveFile, SaveError> {
+        SaveFile::from_ron(&fs::read_to_string(path)?)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_world(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keybindings: Res<KeyBindings>,
+    map_info: Res<MapInfo>,
+    world_seed: Res<WorldSeed>,
+    voxel_config: Res<VoxelConfig>,
+    camera_distance: Res<CameraDistance>,
+    chunk_q: Query<&HeightMap, With<TerrainChunk>>,
+    player_q: Query<(&Transform, &PlayerVelocity), With<Player>>,
+    cameraholder_q: Query<&Transform, With<CameraHolder>>,
+) {
+    let Some(save_key) = keybindings.save else {
+        return;
+    };
+
+    if !keyboard_input.just_pressed(save_key) {
+        return;
+    }
+
+    let (Ok((player_transform, player_velocity)), Ok(cameraholder_transform)) =
+        (player_q.get_single(), cameraholder_q.get_single())
+    else {
+        return;
+    };
+
+    // Voxel terrain can't be edited, so it has no height map to compare
+    let terrain_edits = chunk_q
+        .get_single()
+        .map(|height_map| height_map.deltas(&generate_height_map(&map_info, **world_seed)))
+        .unwrap_or_default();
+
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        seed: **world_seed,
+        map_info: *map_info,
+        voxel_config: *voxel_config,
+        terrain_edits,
+        player: PlayerState {
+            translation: player_transform.translation.to_array(),
+            rotation: player_transform.rotation.to_array(),
+            velocity: player_velocity.0.to_array(),
+        },
+        camera: CameraState {
+            distance: camera_distance.0,
+            rotation: cameraholder_transform.rotation.to_array(),
+        },
+    };
+
+    match save.write(SAVE_PATH) {
+        Ok(()) => info!("World saved to {SAVE_PATH}"),
+        Err(error) => error!("Couldn't save the world: {error}"),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_world(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keybindings: Res<KeyBindings>,
+    mut map_info: ResMut<MapInfo>,
+    mut world_seed: ResMut<WorldSeed>,
+    mut voxel_config: ResMut<VoxelConfig>,
+    mut camera_distance: ResMut<CameraDistance>,
+    mut sculpt_history: ResMut<SculptHistory>,
+    mut regenerate_terrain: EventWriter<RegenerateTerrain>,
+    mut player_q: Query<(&mut Transform, &mut PlayerVelocity), With<Player>>,
+    mut cameraholder_q: Query<&mut Transform, (With<CameraHolder>, Without<Player>)>,
+) {
+    let Some(load_key) = keybindings.load else {
+        return;
+    };
+
+    if !keyboard_input.just_pressed(load_key) {
+        return;
+    }
+
+    let save = match SaveFile::read(SAVE_PATH) {
+        Ok(save) => save,
+        Err(error) => {
+            error!("Couldn't load the world: {error}");
+            return;
+        }
+    };
+
+    **world_seed = save.seed;
+    *map_info = save.map_info;
+    *voxel_config = save.voxel_config;
+    camera_distance.0 = save.camera.distance;
+    // The edits refer to the chunks about to be despawned
+    sculpt_history.clear();
+    regenerate_terrain.send(RegenerateTerrain {
+        edits: save.terrain_edits,
+    });
+
+    if let Ok((mut player_transform, mut player_velocity)) = player_q.get_single_mut() {
+        player_transform.translation = Vec3::from_array(save.player.translation);
+        player_transform.rotation = Quat::from_array(save.player.rotation);
+        player_velocity.0 = Vec3::from_array(save.player.velocity);
+    }
+
+    if let Ok(mut cameraholder_transform) = cameraholder_q.get_single_mut() {
+        cameraholder_transform.rotation = Quat::from_array(save.camera.rotation);
+    }
+
+    info!("World loaded from {SAVE_PATH}");
+}
+
+/// Replaces the world with a new one generated from a random seed, with the player back at the
+/// spawn point.
+fn new_world(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keybindings: Res<KeyBindings>,
+    mut world_seed: ResMut<WorldSeed>,
+    mut sculpt_history: ResMut<SculptHistory>,
+    mut regenerate_terrain: EventWriter<RegenerateTerrain>,
+    mut player_q: Query<(&mut Transform, &mut PlayerVelocity), With<Player>>,
+) {
+    let Some(new_world_key) = keybindings.new_world else {
+        return;
+    };
+
+    if !keyboard_input.just_pressed(new_world_key) {
+        return;
+    }
+
+    **world_seed = Seed::random();
+    sculpt_history.clear();
+    regenerate_terrain.send_default();
+
+    if let Ok((mut player_transform, mut player_velocity)) = player_q.get_single_mut() {
+        player_transform.translation = SPAWN_POINT;
+        player_velocity.0 = Vec3::ZERO;
+    }
+
+    // Can be given back with `--seed`
+    info!("New world with seed {}", **world_seed);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn save() -> SaveFile {
+        SaveFile {
+            version: SAVE_VERSION,
+            seed: Seed(42),
+            map_info: MapInfo {
+                sea_level: 0.5,
+                ..default()
+            },
+            voxel_config: VoxelConfig::default(),
+            terrain_edits: vec![
+                HeightDelta {
+                    x: 3,
+                    z: 7,
+                    height: 1.25,
+                },
+                HeightDelta {
+                    x: 999,
+                    z: 0,
+                    height: -0.1,
+                },
+            ],
+            player: PlayerState {
+                translation: [1., 2., 3.],
+                rotation: Quat::from_rotation_y(1.).to_array(),
+                velocity: [0., -1., 0.],
+            },
+            camera: CameraState {
+                distance: 7.5,
+                rotation: Quat::from_rotation_x(-0.3).to_array(),
+            },
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = env::temp_dir().join(format!("save_round_trip_{}.ron", std::process::id()));
+        let save = save();
+
+        save.write(&path).unwrap();
+        let loaded = SaveFile::read(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded, save);
+    }
+
+    #[test]
+    fn world_round_trip() {
+        let path = env::temp_dir().join(format!("save_world_{}.ron", std::process::id()));
+        let map_info = MapInfo {
+            samples: 64,
+            ..default()
+        };
+        let seed = Seed(7);
+
+        let mut edited = generate_height_map(&map_info, seed);
+        for (x, z) in [(0, 0), (10, 20), (63, 63), (32, 5)] {
+            edited.height_map[x][z] += 1.5;
+        }
+
+        let save = SaveFile {
+            seed,
+            map_info,
+            terrain_edits: edited.deltas(&generate_height_map(&map_info, seed)),
+            ..save()
+        };
+        save.write(&path).unwrap();
+        let loaded = SaveFile::read(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        let mut height_map = generate_height_map(&loaded.map_info, loaded.seed);
+        height_map.apply_deltas(&loaded.terrain_edits);
+
+        assert_eq!(loaded.terrain_edits.len(), 4);
+        assert_eq!(height_map.height_map, edited.height_map);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let save = SaveFile {
+            version: SAVE_VERSION + 1,
+            ..save()
+        };
+
+        assert!(matches!(
+            SaveFile::from_ron(&save.to_ron().unwrap()),
+            Err(SaveError::UnsupportedVersion(version)) if version == SAVE_VERSION + 1
+        ));
+    }
+}
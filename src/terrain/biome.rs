@@ -0,0 +1,108 @@
+use bevy::{
+    color::palettes::css::{DARK_GREEN, DIM_GRAY, NAVY, SANDY_BROWN, SNOW, YELLOW_GREEN},
+    prelude::*,
+};
+
+use super::height_map::HeightMap;
+
+/// Heights where each biome starts, as a fraction of the height between the sea and `max_depth`.
+const BEACH_HEIGHT: f32 = 0.;
+const GRASSLAND_HEIGHT: f32 = 0.06;
+const FOREST_HEIGHT: f32 = 0.35;
+const MOUNTAIN_HEIGHT: f32 = 0.65;
+const SNOW_HEIGHT: f32 = 0.85;
+/// Surfaces whose normal points less upwards than this are bare rock.
+const CLIFF_NORMAL_Y: f32 = 0.7;
+
+/// Broad kind of terrain at a point, from its height above the sea and its slope.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Ocean,
+    Beach,
+    Grassland,
+    Forest,
+    Mountain,
+    Snow,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 6] = [
+        Biome::Ocean,
+        Biome::Beach,
+        Biome::Grassland,
+        Biome::Forest,
+        Biome::Mountain,
+        Biome::Snow,
+    ];
+
+    pub fn classify(height: f32, normal: Vec3, sea_level: f32, max_depth: f32) -> Biome {
+        let relative_height = (height - sea_level) / (max_depth - sea_level).max(f32::EPSILON);
+
+        if relative_height < BEACH_HEIGHT {
+            Biome::Ocean
+        } else if relative_height >= SNOW_HEIGHT {
+            Biome::Snow
+        } else if normal.y < CLIFF_NORMAL_Y || relative_height >= MOUNTAIN_HEIGHT {
+            Biome::Mountain
+        } else if relative_height >= FOREST_HEIGHT {
+            Biome::Forest
+        } else if relative_height >= GRASSLAND_HEIGHT {
+            Biome::Grassland
+        } else {
+            Biome::Beach
+        }
+    }
+
+    /// Color used for the biome in maps and vertex colors.
+    pub fn color(self) -> Color {
+        Color::from(match self {
+            Biome::Ocean => NAVY,
+            Biome::Beach => SANDY_BROWN,
+            Biome::Grassland => YELLOW_GREEN,
+            Biome::Forest => DARK_GREEN,
+            Biome::Mountain => DIM_GRAY,
+            Biome::Snow => SNOW,
+        })
+    }
+}
+
+/// Biome of every sample of a `HeightMap`.
+#[derive(Component, Clone, Debug)]
+pub struct BiomeMap {
+    pub samples: usize,
+    /// `biomes[x][z]`, like `HeightMap::height_map`.
+    pub biomes: Vec<Vec<Biome>>,
+}
+
+impl BiomeMap {
+    pub fn new(height_map: &HeightMap, sea_level: f32, max_depth: f32) -> Self {
+        let biomes = (0..height_map.samples)
+            .map(|x| {
+                (0..height_map.samples)
+                    .map(|z| {
+                        Biome::classify(
+                            height_map.height_map[x][z],
+                            height_map.normal(x, z),
+                            sea_level,
+                            max_depth,
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+
+        BiomeMap {
+            samples: height_map.samples,
+            biomes,
+        }
+    }
+
+    /// Vertex colors for a mesh built from the same height map, in the order of its vertices.
+    pub fn vertex_colors(&self) -> Vec<[f32; 4]> {
+        self.biomes
+            .iter()
+            .flatten()
+            .map(|biome| biome.color().to_linear().to_f32_array())
+            .collect()
+    }
+}
@@ -0,0 +1,343 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    color::palettes::css::{SIENNA, WHEAT, WHITE_SMOKE},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::{player::Player, utils::seed::Seed};
+
+use super::{height_map::HeightMap, scatter::ScatterConfig, MapInfo, TerrainChunk};
+
+/// Distance to a target under which a creature has reached it.
+const ARRIVAL_DISTANCE: f32 = 0.3;
+/// Seconds creatures stand still after reaching where they wandered to.
+const MIN_PAUSE: f32 = 1.;
+const MAX_PAUSE: f32 = 5.;
+/// Fleeing creatures calm down once the player is this many times farther than their alert
+/// distance, so they don't hesitate around it.
+const CALM_FACTOR: f32 = 1.5;
+/// How fast creatures face where they walk.
+const TURN_RATE: f32 = 6.;
+/// Vertical speed of creatures on ground without a height map, like voxel terrain.
+const FALL_SPEED: f32 = 5.;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CreatureKind {
+    Rabbit,
+    Deer,
+    Goat,
+}
+
+impl CreatureKind {
+    /// Height of the body at scale 1.
+    fn height(self) -> f32 {
+        match self {
+            CreatureKind::Rabbit => 0.4,
+            CreatureKind::Deer => 1.2,
+            CreatureKind::Goat => 0.8,
+        }
+    }
+
+    fn walk_speed(self) -> f32 {
+        match self {
+            CreatureKind::Rabbit => 1.5,
+            CreatureKind::Deer => 1.2,
+            CreatureKind::Goat => 1.,
+        }
+    }
+
+    fn run_speed(self) -> f32 {
+        match self {
+            CreatureKind::Rabbit => 5.,
+            CreatureKind::Deer => 6.,
+            CreatureKind::Goat => 4.,
+        }
+    }
+
+    /// Distance to the player under which the creature flees.
+    fn alert_distance(self) -> f32 {
+        match self {
+            CreatureKind::Rabbit => 5.,
+            CreatureKind::Deer => 9.,
+            CreatureKind::Goat => 4.,
+        }
+    }
+
+    /// How far from home the creature wanders.
+    fn wander_radius(self) -> f32 {
+        match self {
+            CreatureKind::Rabbit => 4.,
+            CreatureKind::Deer => 8.,
+            CreatureKind::Goat => 6.,
+        }
+    }
+}
+
+/// Animal roaming the terrain around where it was spawned, as a child of its chunk.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Creature {
+    pub kind: CreatureKind,
+    /// Where it was spawned, on the XZ plane of the chunk.
+    pub home: Vec2,
+    /// Height of the body, including the scale.
+    pub height: f32,
+    /// Picks where it wanders to.
+    seed: Seed,
+    wanders: u64,
+}
+
+impl Creature {
+    /// Wanders somewhere new around home, on the map within `half_size` of its center.
+    fn wander(&mut self, half_size: Vec2) -> Steering {
+        let mut rng = self.seed.derive_index(self.wanders).rng();
+        self.wanders += 1;
+
+        let offset = Vec2::from_angle(rng.gen::<f32>() * TAU)
+            * self.kind.wander_radius()
+            * rng.gen::<f32>().sqrt();
+
+        Steering::Wander {
+            target: (self.home + offset).clamp(-half_size, half_size),
+            pause: rng.gen_range(MIN_PAUSE..MAX_PAUSE),
+        }
+    }
+}
+
+/// What a creature is doing.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub enum Steering {
+    /// Walking to `target`, then standing there for `pause` seconds.
+    Wander { target: Vec2, pause: f32 },
+    /// Running away from the player.
+    Flee,
+    /// Walking back home once the player is far enough.
+    ReturnHome,
+}
+
+/// Meshes and materials shared by every creature of a kind, so they are drawn in batches.
+#[derive(Resource)]
+pub struct CreatureAssets {
+    cube: Handle<Mesh>,
+    rabbit_material: Handle<StandardMaterial>,
+    deer_material: Handle<StandardMaterial>,
+    goat_material: Handle<StandardMaterial>,
+}
+
+impl CreatureAssets {
+    fn material(&self, kind: CreatureKind) -> Handle<StandardMaterial> {
+        match kind {
+            CreatureKind::Rabbit => self.rabbit_material.clone(),
+            CreatureKind::Deer => self.deer_material.clone(),
+            CreatureKind::Goat => self.goat_material.clone(),
+        }
+    }
+}
+
+impl FromWorld for CreatureAssets {
+    fn from_world(world: &mut World) -> Self {
+        let cube = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Cuboid::from_size(Vec3::ONE));
+
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+
+        CreatureAssets {
+            cube,
+            rabbit_material: materials.add(Color::from(WHEAT)),
+            deer_material: materials.add(Color::from(SIENNA)),
+            goat_material: materials.add(Color::from(WHITE_SMOKE)),
+        }
+    }
+}
+
+/// Places the creatures of every rule on the terrain, as children of its chunk.
+pub(super) fn spawn_creatures(
+    parent: &mut ChildBuilder,
+    height_map: &HeightMap,
+    map_info: &MapInfo,
+    config: &ScatterConfig,
+    seed: Seed,
+    assets: &CreatureAssets,
+) {
+    if !config.enabled {
+        return;
+    }
+
+    for (index, rule) in config.creatures.iter().enumerate() {
+        let seed = seed.derive_index(index as u64);
+        let placements = rule.placements(height_map, map_info, seed);
+
+        for (number, placement) in placements.into_iter().enumerate() {
+            let mut creature = Creature {
+                kind: rule.kind,
+                home: placement.position.xz(),
+                height: rule.kind.height() * placement.scale,
+                seed: seed.derive("wander").derive_index(number as u64),
+                wanders: 0,
+            };
+            let steering = creature.wander(Vec2::splat(map_info.size / 2.));
+            let transform =
+                Transform::from_translation(placement.position + Vec3::Y * creature.height / 2.)
+                    .with_rotation(Quat::from_rotation_y(placement.yaw));
+
+            spawn_creature(parent, creature, steering, transform, assets);
+        }
+    }
+}
+
+fn spawn_creature(
+    parent: &mut ChildBuilder,
+    creature: Creature,
+    steering: Steering,
+    transform: Transform,
+    assets: &CreatureAssets,
+) {
+    let height = creature.height;
+    let radius = height / 3.;
+    let material = assets.material(creature.kind);
+
+    parent
+        .spawn(SpatialBundle::from_transform(transform))
+        .insert((creature, steering))
+        .insert(RigidBody::KinematicPositionBased)
+        .insert(Collider::capsule_y(height / 2. - radius, radius))
+        .insert(KinematicCharacterController::default())
+        .with_children(|body| {
+            // Forward is -Z, like `Transform::looking_to`
+            body.spawn(PbrBundle {
+                mesh: assets.cube.clone(),
+                material: material.clone(),
+                transform: Transform::from_xyz(0., -height / 8., 0.).with_scale(Vec3::new(
+                    height / 2.,
+                    height / 2.,
+                    height,
+                )),
+                ..default()
+            });
+            body.spawn(PbrBundle {
+                mesh: assets.cube.clone(),
+                material,
+                transform: Transform::from_xyz(0., height / 4., -height / 2.)
+                    .with_scale(Vec3::splat(height / 3.)),
+                ..default()
+            });
+        });
+}
+
+/// Steers every creature, and keeps it on the height map of its chunk.
+pub(super) fn move_creatures(
+    time: Res<Time>,
+    map_info: Res<MapInfo>,
+    player_q: Query<&GlobalTransform, With<Player>>,
+    chunk_q: Query<&HeightMap, With<TerrainChunk>>,
+    mut creature_q: Query<(
+        &mut Creature,
+        &mut Steering,
+        &mut Transform,
+        &mut KinematicCharacterController,
+        &Parent,
+    )>,
+) {
+    let delta = time.delta_seconds();
+    let half_size = Vec2::splat(map_info.size / 2.);
+    let player = player_q
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation().xz());
+
+    for (mut creature, mut steering, mut transform, mut controller, parent) in &mut creature_q {
+        let position = transform.translation.xz();
+        let kind = creature.kind;
+        let threat = player.filter(|player| {
+            let calm_distance = match *steering {
+                Steering::Flee => kind.alert_distance() * CALM_FACTOR,
+                _ => kind.alert_distance(),
+            };
+            player.distance(position) < calm_distance
+        });
+
+        *steering = match (*steering, threat) {
+            (_, Some(_)) => Steering::Flee,
+            (Steering::Flee, None) => Steering::ReturnHome,
+            (Steering::ReturnHome, None)
+                if position.distance(creature.home) < kind.wander_radius() =>
+            {
+                creature.wander(half_size)
+            }
+            (Steering::Wander { target, pause }, None)
+                if position.distance(target) < ARRIVAL_DISTANCE =>
+            {
+                if pause > delta {
+                    Steering::Wander {
+                        target,
+                        pause: pause - delta,
+                    }
+                } else {
+                    creature.wander(half_size)
+                }
+            }
+            (steering, None) => steering,
+        };
+
+        let velocity = match (*steering, threat) {
+            (Steering::Flee, Some(threat)) => {
+                (position - threat).normalize_or_zero() * kind.run_speed()
+            }
+            (Steering::Wander { target, .. }, _)
+                if position.distance(target) >= ARRIVAL_DISTANCE =>
+            {
+                (target - position).normalize_or_zero() * kind.walk_speed()
+            }
+            (Steering::ReturnHome, _) => {
+                (creature.home - position).normalize_or_zero() * kind.walk_speed()
+            }
+            _ => Vec2::ZERO,
+        };
+
+        let height_map = chunk_q.get(parent.get()).ok();
+        // Creatures stay on the map, even when running away from the edge
+        let mut step = (position + velocity * delta).clamp(-half_size, half_size) - position;
+        let next = position + step;
+
+        // Creatures don't swim or fall down shafts, they go somewhere else
+        if height_map.is_some_and(|height_map| {
+            height_map.height_at(next.x, next.y) < map_info.sea_level
+                || !height_map.has_ground(next.x, next.y)
+        }) {
+            step = Vec2::ZERO;
+
+            match *steering {
+                Steering::Wander { .. } => *steering = creature.wander(half_size),
+                // Settles where it is when the way home is cut off
+                Steering::ReturnHome => {
+                    creature.home = position;
+                    *steering = creature.wander(half_size);
+                }
+                Steering::Flee => {}
+            }
+        }
+
+        let next = position + step;
+        let vertical = match height_map {
+            Some(height_map) => {
+                height_map.height_at(next.x, next.y) + creature.height / 2.
+                    - transform.translation.y
+            }
+            None => -FALL_SPEED * delta,
+        };
+
+        controller.translation = Some(Vec3::new(step.x, vertical, step.y));
+
+        if let Some(direction) = step.try_normalize() {
+            let facing = Transform::IDENTITY
+                .looking_to(Vec3::new(direction.x, 0., direction.y), Vec3::Y)
+                .rotation;
+            transform.rotation = transform
+                .rotation
+                .slerp(facing, (TURN_RATE * delta).min(1.));
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use super::{carve_corridors, DungeonGrid, DungeonSettings, Room, RoomGraph};
+
+pub(super) fn generate(
+    settings: &DungeonSettings,
+    rng: &mut ChaCha8Rng,
+) -> (DungeonGrid, RoomGraph) {
+    let mut grid = DungeonGrid::new(settings.width, settings.height);
+    let mut graph = RoomGraph::default();
+
+    let bounds = IRect::new(0, 0, settings.width as i32, settings.height as i32);
+    split(bounds, settings, rng, &mut graph);
+
+    for room in &graph.rooms {
+        grid.carve_room(room.rect);
+    }
+    carve_corridors(&mut grid, &graph, rng);
+
+    (grid, graph)
+}
+
+/// Adds the rooms of `rect` and the edges between them to `graph`, returning their indices.
+fn split(
+    rect: IRect,
+    settings: &DungeonSettings,
+    rng: &mut ChaCha8Rng,
+    graph: &mut RoomGraph,
+) -> Vec<usize> {
+    // A room and the wall around it
+    let min_size = settings.min_room_size.max(1) as i32 + 2;
+    let max_size = settings.max_room_size.max(settings.min_room_size) as i32 + 2;
+    let size = rect.size();
+
+    let can_split_x = size.x >= min_size * 2;
+    let can_split_y = size.y >= min_size * 2;
+    let too_big = size.x > max_size || size.y > max_size;
+
+    if !too_big || !(can_split_x || can_split_y) {
+        return place_room(rect, settings, rng, graph).into_iter().collect();
+    }
+
+    // Across the longest side, so the parts don't get too thin
+    let split_x = match (can_split_x, can_split_y) {
+        (true, false) => true,
+        (false, true) => false,
+        _ if size.x * 4 > size.y * 5 => true,
+        _ if size.y * 4 > size.x * 5 => false,
+        _ => rng.gen(),
+    };
+
+    let (first, second) = if split_x {
+        let x = rng.gen_range(rect.min.x + min_size..=rect.max.x - min_size);
+        (
+            IRect::new(rect.min.x, rect.min.y, x, rect.max.y),
+            IRect::new(x, rect.min.y, rect.max.x, rect.max.y),
+        )
+    } else {
+        let y = rng.gen_range(rect.min.y + min_size..=rect.max.y - min_size);
+        (
+            IRect::new(rect.min.x, rect.min.y, rect.max.x, y),
+            IRect::new(rect.min.x, y, rect.max.x, rect.max.y),
+        )
+    };
+
+    let first = split(first, settings, rng, graph);
+    let second = split(second, settings, rng, graph);
+
+    // Closest pair of rooms between the two parts
+    let closest = first
+        .iter()
+        .flat_map(|&a| second.iter().map(move |&b| (a, b)))
+        .min_by_key(|&(a, b)| {
+            let offset = graph.rooms[a].center - graph.rooms[b].center;
+            offset.x.abs() + offset.y.abs()
+        });
+    graph.edges.extend(closest);
+
+    [first, second].concat()
+}
+
+/// Room of random size somewhere inside `rect`, leaving a wall around it.
+fn place_room(
+    rect: IRect,
+    settings: &DungeonSettings,
+    rng: &mut ChaCha8Rng,
+    graph: &mut RoomGraph,
+) -> Option<usize> {
+    let available = rect.size() - 2;
+    let min_size = (settings.min_room_size.max(1) as i32).min(available.min_element());
+    if min_size < 1 {
+        return None;
+    }
+
+    let max_size = IVec2::splat(settings.max_room_size as i32)
+        .min(available)
+        .max(IVec2::splat(min_size));
+    let size = IVec2::new(
+        rng.gen_range(min_size..=max_size.x),
+        rng.gen_range(min_size..=max_size.y),
+    );
+    let min = IVec2::new(
+        rng.gen_range(rect.min.x + 1..=rect.max.x - 1 - size.x),
+        rng.gen_range(rect.min.y + 1..=rect.max.y - 1 - size.y),
+    );
+
+    graph
+        .rooms
+        .push(Room::new(IRect::from_corners(min, min + size)));
+    Some(graph.rooms.len() - 1)
+}
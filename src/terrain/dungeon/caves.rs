@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::utils::{
+    automaton::{Automaton, Border, Neighbourhood, Rule, ALIVE},
+    seed::Seed,
+};
+
+use super::{carve_corridors, connect_rooms, Cell, DungeonGrid, DungeonSettings, Room, RoomGraph};
+
+const NEIGHBOURS: [IVec2; 4] = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y];
+
+pub(super) fn generate(
+    settings: &DungeonSettings,
+    rng: &mut ChaCha8Rng,
+) -> (DungeonGrid, RoomGraph) {
+    let mut grid = DungeonGrid::new(settings.width, settings.height);
+
+    // Walls are alive, and so is everything around the inside of the border
+    let mut automaton = Automaton::new(
+        settings.width.saturating_sub(2),
+        settings.height.saturating_sub(2),
+        Rule::CAVES,
+        Neighbourhood::Moore,
+        Border::Fixed(ALIVE),
+    );
+
+    automaton.randomize(settings.cave_fill, Seed(rng.gen()));
+    automaton.run(settings.cave_steps);
+
+    for y in 0..automaton.height as i32 {
+        for x in 0..automaton.width as i32 {
+            if !automaton.is_alive(x, y) {
+                grid.set(IVec2::new(x + 1, y + 1), Cell::Floor);
+            }
+        }
+    }
+
+    let rooms = regions(&mut grid, settings.min_cave_size);
+    let edges = connect_rooms(&rooms, settings.extra_connections, rng);
+    let graph = RoomGraph { rooms, edges };
+    carve_corridors(&mut grid, &graph, rng);
+
+    (grid, graph)
+}
+
+/// A room for each connected open region, filling the ones smaller than `min_size`.
+fn regions(grid: &mut DungeonGrid, min_size: usize) -> Vec<Room> {
+    let mut visited = vec![false; grid.cells.len()];
+    let mut rooms = Vec::new();
+
+    for y in 0..grid.height as i32 {
+        for x in 0..grid.width as i32 {
+            let start = IVec2::new(x, y);
+            if visited[grid.index(start)] || !grid.get(start).is_open() {
+                continue;
+            }
+
+            // Flood fill
+            let mut region = vec![start];
+            visited[grid.index(start)] = true;
+            let mut next = 0;
+
+            while let Some(&position) = region.get(next) {
+                next += 1;
+
+                for offset in NEIGHBOURS {
+                    let neighbour = position + offset;
+                    if grid.get(neighbour).is_open() && !visited[grid.index(neighbour)] {
+                        visited[grid.index(neighbour)] = true;
+                        region.push(neighbour);
+                    }
+                }
+            }
+
+            if region.len() < min_size {
+                for &position in &region {
+                    grid.set(position, Cell::Wall);
+                }
+                continue;
+            }
+
+            let rect = region
+                .iter()
+                .fold(IRect::from_corners(start, start + 1), |rect, &position| {
+                    rect.union(IRect::from_corners(position, position + 1))
+                });
+            let centroid = region
+                .iter()
+                .map(|position| position.as_vec2())
+                .sum::<Vec2>()
+                / region.len() as f32;
+            // Caves can be any shape, so the middle of the region may be a wall
+            let center = *region
+                .iter()
+                .min_by(|a, b| {
+                    let distance = |position: &IVec2| position.as_vec2().distance_squared(centroid);
+                    distance(a).total_cmp(&distance(b))
+                })
+                .expect("Regions have at least one cell");
+
+            rooms.push(Room { rect, center });
+        }
+    }
+
+    rooms
+}
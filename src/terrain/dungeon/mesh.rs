@@ -0,0 +1,240 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+
+use super::DungeonGrid;
+
+/// Rise of the shaft stairs per unit along them.
+const STAIR_SLOPE: f32 = 0.6;
+const STAIR_THICKNESS: f32 = 0.1;
+
+/// Floor, ceiling and walls around the open cells of `grid`, seen from inside. Cells in
+/// `open_ceiling`, `max` excluded, have no ceiling.
+///
+/// Cell `(x, y)` covers `x..x + 1` on X and `y..y + 1` on Z, times `cell_size`. The floor is at
+/// height 0.
+pub fn generate(
+    grid: &DungeonGrid,
+    open_ceiling: Option<IRect>,
+    cell_size: f32,
+    wall_height: f32,
+) -> Mesh {
+    let mut builder = MeshBuilder::default();
+
+    for y in 0..grid.height as i32 {
+        for x in 0..grid.width as i32 {
+            let cell = IVec2::new(x, y);
+            if !grid.get(cell).is_open() {
+                continue;
+            }
+
+            let min = Vec3::new(x as f32, 0., y as f32) * cell_size;
+            let max = min + Vec3::new(cell_size, 0., cell_size);
+
+            let floor = [
+                min,
+                Vec3::new(min.x, 0., max.z),
+                max,
+                Vec3::new(max.x, 0., min.z),
+            ];
+            builder.quad(floor, Vec3::Y);
+
+            let open = open_ceiling
+                .is_some_and(|rect| cell.cmpge(rect.min).all() && cell.cmplt(rect.max).all());
+            if !open {
+                builder.quad(
+                    floor.map(|corner| corner + Vec3::Y * wall_height),
+                    Vec3::NEG_Y,
+                );
+            }
+
+            for direction in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                if grid.get(cell + direction).is_open() {
+                    continue;
+                }
+
+                let outwards = Vec3::new(direction.x as f32, 0., direction.y as f32);
+                let along = Vec3::new(-outwards.z, 0., outwards.x) * cell_size / 2.;
+                let middle = (min + max) / 2. + outwards * cell_size / 2.;
+                let up = Vec3::Y * wall_height;
+
+                builder.quad(
+                    [
+                        middle - along,
+                        middle + along,
+                        middle + along + up,
+                        middle - along + up,
+                    ],
+                    -outwards,
+                );
+            }
+        }
+    }
+
+    builder.build()
+}
+
+/// Square spiral stairs around a pillar, lined with walls from `ceiling` up to the surface.
+///
+/// The shaft covers `0..size` on X and Z, with its floor at height 0. `surface` gives the height
+/// of the ground at a point of the shaft. The stairs end at the first landing above it.
+pub fn shaft(size: f32, ceiling: f32, surface: impl Fn(Vec2) -> f32) -> Mesh {
+    let mut builder = MeshBuilder::default();
+
+    let width = size * 3. / 8.;
+    let length = size - 2. * width;
+    let rise = length * STAIR_SLOPE;
+
+    let corners = [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y].map(|corner| corner * size);
+    // Start on the outer wall, direction and inwards side of each flight
+    let flights = [
+        (Vec2::new(width, 0.), Vec2::X, Vec2::Y),
+        (Vec2::new(size, width), Vec2::Y, Vec2::NEG_X),
+        (Vec2::new(size - width, size), Vec2::NEG_X, Vec2::NEG_Y),
+        (Vec2::new(0., size - width), Vec2::NEG_Y, Vec2::X),
+    ];
+    let top = corners
+        .iter()
+        .map(|&corner| surface(corner))
+        .fold(ceiling, f32::max);
+
+    let mut height = 0.;
+    for step in 0.. {
+        let (start, direction, side) = flights[step % 4];
+        let end = start + direction * length;
+        let side = side * width;
+        let ramp = [
+            at(start, height),
+            at(end, height + rise),
+            at(end + side, height + rise),
+            at(start + side, height),
+        ];
+        let normal = (Vec3::Y * length - at(direction, 0.) * rise).normalize();
+
+        builder.quad(ramp, normal);
+        builder.quad(
+            ramp.map(|corner| corner - Vec3::Y * STAIR_THICKNESS),
+            -normal,
+        );
+
+        height += rise;
+
+        // Landing in the corner at the end of the flight
+        let corner = corners[(step + 1) % 4];
+        let min = corner.min(end + side);
+        let max = corner.max(end + side);
+        let landing = [
+            at(min, height),
+            at(Vec2::new(min.x, max.y), height),
+            at(max, height),
+            at(Vec2::new(max.x, min.y), height),
+        ];
+
+        builder.quad(landing, Vec3::Y);
+        builder.quad(
+            landing.map(|corner| corner - Vec3::Y * STAIR_THICKNESS),
+            Vec3::NEG_Y,
+        );
+
+        if height >= surface(corner) || height >= top {
+            break;
+        }
+    }
+
+    // Pillar in the middle
+    let middle = Vec3::new(size, 0., size) / 2.;
+    let half = size / 2. - width;
+    for outwards in [Vec3::X, Vec3::NEG_X, Vec3::Z, Vec3::NEG_Z] {
+        let along = Vec3::new(-outwards.z, 0., outwards.x) * half;
+        let center = middle + outwards * half;
+        let up = Vec3::Y * height;
+
+        builder.quad(
+            [
+                center - along,
+                center + along,
+                center + along + up,
+                center - along + up,
+            ],
+            outwards,
+        );
+    }
+
+    // Walls from the ceiling of the dungeon up to the surface
+    let segments = size.ceil().max(1.) as usize;
+    for (index, &from) in corners.iter().enumerate() {
+        let to = corners[(index + 1) % 4];
+        let inwards = (middle.xz() - (from + to) / 2.).normalize();
+
+        for segment in 0..segments {
+            let a = from.lerp(to, segment as f32 / segments as f32);
+            let b = from.lerp(to, (segment + 1) as f32 / segments as f32);
+
+            builder.quad(
+                [
+                    at(a, ceiling),
+                    at(b, ceiling),
+                    at(b, surface(b).max(ceiling)),
+                    at(a, surface(a).max(ceiling)),
+                ],
+                Vec3::new(inwards.x, 0., inwards.y),
+            );
+        }
+    }
+
+    builder.build()
+}
+
+/// Point of the shaft at `height`.
+fn at(point: Vec2, height: f32) -> Vec3 {
+    Vec3::new(point.x, height, point.y)
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    /// Quad with corners in order around it, facing `normal`.
+    fn quad(&mut self, corners: [Vec3; 4], normal: Vec3) {
+        let first = self.positions.len() as u32;
+
+        for (corner, uv) in corners
+            .into_iter()
+            .zip([Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y])
+        {
+            self.positions.push(corner);
+            self.normals.push(normal);
+            self.uvs.push(uv);
+        }
+
+        let counter_clockwise = (corners[1] - corners[0])
+            .cross(corners[2] - corners[0])
+            .dot(normal)
+            > 0.;
+        let triangles = if counter_clockwise {
+            [0, 1, 2, 0, 2, 3]
+        } else {
+            [0, 2, 1, 0, 3, 2]
+        };
+
+        self.indices
+            .extend(triangles.into_iter().map(|index| first + index));
+    }
+
+    fn build(self) -> Mesh {
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+        .with_inserted_indices(Indices::U32(self.indices))
+    }
+}
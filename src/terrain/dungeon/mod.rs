@@ -0,0 +1,534 @@
+use bevy::{color::palettes::css::DIM_GRAY, prelude::*};
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::utils::{delaunay, seed::Seed};
+
+use super::{height_map::HeightMap, MapInfo};
+
+mod bsp;
+mod caves;
+pub mod mesh;
+mod scattered;
+
+/// Cells along each side of the shaft between the dungeon and the surface.
+const SHAFT_CELLS: i32 = 4;
+
+/// Cell of a dungeon grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Cell {
+    #[default]
+    Wall,
+    Floor,
+    Corridor,
+}
+
+impl Cell {
+    pub fn is_open(self) -> bool {
+        self != Cell::Wall
+    }
+}
+
+/// Cells of a dungeon, row by row. The cells on the border are always walls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DungeonGrid {
+    pub width: u32,
+    pub height: u32,
+    pub cells: Vec<Cell>,
+}
+
+impl DungeonGrid {
+    /// Grid of only walls.
+    pub fn new(width: u32, height: u32) -> Self {
+        DungeonGrid {
+            width,
+            height,
+            cells: vec![Cell::Wall; (width * height) as usize],
+        }
+    }
+
+    pub fn in_bounds(&self, position: IVec2) -> bool {
+        position.x >= 0
+            && position.y >= 0
+            && (position.x as u32) < self.width
+            && (position.y as u32) < self.height
+    }
+
+    /// Cell at `position`, walls outside of the grid.
+    pub fn get(&self, position: IVec2) -> Cell {
+        if self.in_bounds(position) {
+            self.cells[self.index(position)]
+        } else {
+            Cell::Wall
+        }
+    }
+
+    /// Sets the cell at `position`, unless it is on the border or outside of the grid.
+    pub fn set(&mut self, position: IVec2, cell: Cell) {
+        let inside = position.cmpgt(IVec2::ZERO).all()
+            && position.x < self.width as i32 - 1
+            && position.y < self.height as i32 - 1;
+
+        if inside {
+            let index = self.index(position);
+            self.cells[index] = cell;
+        }
+    }
+
+    fn index(&self, position: IVec2) -> usize {
+        (position.x as u32 + position.y as u32 * self.width) as usize
+    }
+
+    fn carve_room(&mut self, rect: IRect) {
+        for y in rect.min.y..rect.max.y {
+            for x in rect.min.x..rect.max.x {
+                self.set(IVec2::new(x, y), Cell::Floor);
+            }
+        }
+    }
+
+    /// L shaped corridor between two cells, only through walls.
+    fn carve_corridor(&mut self, from: IVec2, to: IVec2, horizontal_first: bool) {
+        let corner = if horizontal_first {
+            IVec2::new(to.x, from.y)
+        } else {
+            IVec2::new(from.x, to.y)
+        };
+
+        for (start, end) in [(from, corner), (corner, to)] {
+            let step = (end - start).signum();
+            let mut position = start;
+
+            loop {
+                if self.get(position) == Cell::Wall {
+                    self.set(position, Cell::Corridor);
+                }
+
+                if position == end {
+                    break;
+                }
+                position += step;
+            }
+        }
+    }
+}
+
+/// Room of a dungeon. Cave rooms are whole cave regions, so not every cell of `rect` is open.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Room {
+    /// Cells covered by the room, `max` excluded.
+    pub rect: IRect,
+    /// Open cell near the middle of the room, where corridors start.
+    pub center: IVec2,
+}
+
+impl Room {
+    fn new(rect: IRect) -> Self {
+        Room {
+            rect,
+            center: (rect.min + rect.max) / 2,
+        }
+    }
+}
+
+/// Rooms of a dungeon and the corridors between them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RoomGraph {
+    pub rooms: Vec<Room>,
+    /// Pairs of connected rooms, by index.
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl RoomGraph {
+    /// Rooms connected to `room` by a corridor.
+    pub fn neighbours(&self, room: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges.iter().filter_map(move |&(a, b)| {
+            if a == room {
+                Some(b)
+            } else if b == room {
+                Some(a)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Generated dungeon. Also kept on its spawned entity.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct Dungeon {
+    pub grid: DungeonGrid,
+    pub graph: RoomGraph,
+    /// Cells under the shaft up to the surface, if there are any rooms.
+    pub entrance: Option<IRect>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DungeonLayout {
+    /// Space split in two recursively, with a room in each part and corridors between siblings.
+    #[default]
+    Bsp,
+    /// Rooms at random places, connected by the minimum spanning tree of their Delaunay
+    /// triangulation.
+    Scattered,
+    /// Caves grown by a cellular automaton, connected like `Scattered` rooms.
+    Caves,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DungeonSettings {
+    pub width: u32,
+    pub height: u32,
+    pub layout: DungeonLayout,
+    pub min_room_size: u32,
+    pub max_room_size: u32,
+    /// Rooms tried by `Scattered`. The ones that overlap others are dropped.
+    pub room_attempts: u32,
+    /// Chance of keeping each Delaunay edge that isn't in the spanning tree, so there are loops.
+    pub extra_connections: f32,
+    /// Fraction of the cells that start as walls in `Caves`.
+    pub cave_fill: f32,
+    pub cave_steps: u32,
+    /// Caves with fewer cells than this are filled.
+    pub min_cave_size: usize,
+}
+
+impl Default for DungeonSettings {
+    fn default() -> Self {
+        DungeonSettings {
+            width: 48,
+            height: 48,
+            layout: DungeonLayout::Bsp,
+            min_room_size: 4,
+            max_room_size: 10,
+            room_attempts: 60,
+            extra_connections: 0.15,
+            cave_fill: 0.45,
+            cave_steps: 5,
+            min_cave_size: 12,
+        }
+    }
+}
+
+/// Generates a dungeon. The same settings and seed always give the same dungeon.
+pub fn generate(settings: &DungeonSettings, seed: Seed) -> Dungeon {
+    let mut rng = seed.rng();
+
+    let (mut grid, graph) = match settings.layout {
+        DungeonLayout::Bsp => bsp::generate(settings, &mut rng),
+        DungeonLayout::Scattered => scattered::generate(settings, &mut rng),
+        DungeonLayout::Caves => caves::generate(settings, &mut rng),
+    };
+    let entrance = carve_entrance(&mut grid, &graph);
+
+    Dungeon {
+        grid,
+        graph,
+        entrance,
+    }
+}
+
+/// Opens the bottom of the shaft in the room farthest from the middle of the dungeon, so it's
+/// away from where the player starts.
+fn carve_entrance(grid: &mut DungeonGrid, graph: &RoomGraph) -> Option<IRect> {
+    let middle = IVec2::new(grid.width as i32, grid.height as i32) / 2;
+    let room = graph
+        .rooms
+        .iter()
+        .max_by_key(|room| (room.center - middle).length_squared())?;
+
+    // Inside the border walls
+    let max = IVec2::new(grid.width as i32, grid.height as i32) - 1 - SHAFT_CELLS;
+    let min = (room.center - SHAFT_CELLS / 2).clamp(IVec2::ONE, max.max(IVec2::ONE));
+    let rect = IRect::from_corners(min, min + SHAFT_CELLS);
+
+    // The shaft is open down to the floor, so the room reaches all of it
+    grid.carve_room(rect);
+
+    Some(rect)
+}
+
+/// Minimum spanning tree of the Delaunay triangulation of the rooms, plus some of the other edges.
+fn connect_rooms(
+    rooms: &[Room],
+    extra_connections: f32,
+    rng: &mut ChaCha8Rng,
+) -> Vec<(usize, usize)> {
+    let centers: Vec<Vec2> = rooms.iter().map(|room| room.center.as_vec2()).collect();
+
+    let mut candidates = delaunay::edges(&delaunay::triangulate(&centers));
+    if candidates.is_empty() {
+        // Fewer than 3 rooms, or all of them in a line
+        candidates = (0..rooms.len())
+            .flat_map(|a| (a + 1..rooms.len()).map(move |b| (a, b)))
+            .collect();
+    }
+
+    let length = |&(a, b): &(usize, usize)| centers[a].distance_squared(centers[b]);
+    candidates.sort_by(|first, second| length(first).total_cmp(&length(second)));
+
+    // Kruskal
+    let mut sets: Vec<usize> = (0..rooms.len()).collect();
+    let mut edges = Vec::new();
+
+    for (a, b) in candidates {
+        let (root_a, root_b) = (find(&mut sets, a), find(&mut sets, b));
+
+        // Drawn for every edge so the dungeon doesn't depend on which ones are in the tree
+        let extra = rng.gen::<f32>() < extra_connections;
+
+        if root_a != root_b {
+            sets[root_a] = root_b;
+            edges.push((a, b));
+        } else if extra {
+            edges.push((a, b));
+        }
+    }
+
+    // Rooms the triangulation missed, like ones on top of each other, join the closest room of
+    // another group until there is only one
+    loop {
+        let roots: Vec<usize> = (0..rooms.len()).map(|room| find(&mut sets, room)).collect();
+        let closest = (0..rooms.len())
+            .filter(|&a| roots[a] == roots[0])
+            .flat_map(|a| {
+                (0..rooms.len())
+                    .filter(|&b| roots[b] != roots[0])
+                    .map(move |b| (a, b))
+            })
+            .min_by(|first, second| length(first).total_cmp(&length(second)));
+
+        let Some((a, b)) = closest else {
+            break;
+        };
+
+        sets[roots[a]] = roots[b];
+        edges.push((a, b));
+    }
+
+    edges
+}
+
+fn find(sets: &mut [usize], mut element: usize) -> usize {
+    while sets[element] != element {
+        sets[element] = sets[sets[element]];
+        element = sets[element];
+    }
+
+    element
+}
+
+fn carve_corridors(grid: &mut DungeonGrid, graph: &RoomGraph, rng: &mut ChaCha8Rng) {
+    for &(a, b) in &graph.edges {
+        grid.carve_corridor(graph.rooms[a].center, graph.rooms[b].center, rng.gen());
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct DungeonConfig {
+    pub enabled: bool,
+    pub settings: DungeonSettings,
+    /// Size of a grid cell in the world.
+    pub cell_size: f32,
+    pub wall_height: f32,
+    /// How far below `MapInfo::min_depth` the floor is.
+    pub depth: f32,
+}
+
+impl Default for DungeonConfig {
+    fn default() -> Self {
+        DungeonConfig {
+            enabled: true,
+            settings: DungeonSettings::default(),
+            cell_size: 1.,
+            wall_height: 2.5,
+            depth: 12.,
+        }
+    }
+}
+
+impl DungeonConfig {
+    /// Position of the corner of `grid` at cell `(0, 0)`, on the floor, so the dungeon is
+    /// centered under the map.
+    pub fn origin(&self, grid: &DungeonGrid, map_info: &MapInfo) -> Vec3 {
+        let extent = UVec2::new(grid.width, grid.height).as_vec2() * self.cell_size;
+
+        Vec3::new(
+            -extent.x / 2.,
+            map_info.min_depth - self.depth,
+            -extent.y / 2.,
+        )
+    }
+}
+
+/// Generates a dungeon and removes the ground over its entrance, so there is a way down.
+pub(super) fn dig_dungeon(
+    height_map: &mut HeightMap,
+    map_info: &MapInfo,
+    config: &DungeonConfig,
+    seed: Seed,
+) -> Option<Dungeon> {
+    if !config.enabled {
+        return None;
+    }
+
+    let dungeon = generate(&config.settings, seed);
+
+    if let Some(entrance) = dungeon.entrance {
+        let origin = config.origin(&dungeon.grid, map_info).xz();
+        height_map.add_hole(
+            origin + entrance.min.as_vec2() * config.cell_size,
+            origin + entrance.max.as_vec2() * config.cell_size,
+        );
+    }
+
+    Some(dungeon)
+}
+
+/// Spawns a dug dungeon under the map, with a shaft up to the surface and trimesh colliders.
+pub(super) fn spawn_dungeon(
+    parent: &mut ChildBuilder,
+    dungeon: Dungeon,
+    height_map: &HeightMap,
+    map_info: &MapInfo,
+    config: &DungeonConfig,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    let mesh = mesh::generate(
+        &dungeon.grid,
+        dungeon.entrance,
+        config.cell_size,
+        config.wall_height,
+    );
+
+    if mesh.count_vertices() == 0 {
+        return;
+    }
+
+    let collider = Collider::from_bevy_mesh(&mesh, &ComputedColliderShape::TriMesh)
+        .expect("Dungeon mesh is not a valid trimesh");
+    let origin = config.origin(&dungeon.grid, map_info);
+    let material = materials.add(StandardMaterial {
+        base_color: Color::from(DIM_GRAY),
+        perceptual_roughness: 1.,
+        ..default()
+    });
+    let entrance = dungeon.entrance;
+
+    parent
+        .spawn(PbrBundle {
+            mesh: meshes.add(mesh),
+            material: material.clone(),
+            transform: Transform::from_translation(origin),
+            ..default()
+        })
+        .insert((dungeon, collider))
+        .with_children(|children| {
+            let Some(entrance) = entrance else {
+                return;
+            };
+
+            let corner = entrance.min.as_vec2() * config.cell_size;
+            let shaft = mesh::shaft(
+                entrance.width() as f32 * config.cell_size,
+                config.wall_height,
+                |point| {
+                    let world = origin.xz() + corner + point;
+                    height_map.height_at(world.x, world.y) - origin.y
+                },
+            );
+            let collider = Collider::from_bevy_mesh(&shaft, &ComputedColliderShape::TriMesh)
+                .expect("Shaft mesh is not a valid trimesh");
+
+            children
+                .spawn(PbrBundle {
+                    mesh: meshes.add(shaft),
+                    material,
+                    transform: Transform::from_xyz(corner.x, 0., corner.y),
+                    ..default()
+                })
+                .insert(collider);
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connected(rooms: usize, edges: &[(usize, usize)]) -> bool {
+        let mut sets: Vec<usize> = (0..rooms).collect();
+        for &(a, b) in edges {
+            let root = find(&mut sets, a);
+            sets[root] = find(&mut sets, b);
+        }
+
+        (0..rooms).all(|room| find(&mut sets, room) == find(&mut sets, 0))
+    }
+
+    #[test]
+    fn degenerate_rooms_are_connected() {
+        let room = |x: i32, y: i32| Room::new(IRect::new(x, y, x + 4, y + 4));
+        let layouts = [
+            vec![room(0, 0), room(0, 0), room(0, 0)],
+            vec![room(0, 0), room(10, 0), room(20, 0), room(30, 0)],
+            vec![
+                room(0, 0),
+                room(20, 0),
+                room(0, 20),
+                room(0, 20),
+                room(20, 0),
+            ],
+        ];
+
+        for rooms in layouts {
+            let mut rng = Seed(0).rng();
+            let edges = connect_rooms(&rooms, 0., &mut rng);
+
+            assert!(connected(rooms.len(), &edges), "{rooms:?}: {edges:?}");
+        }
+    }
+
+    #[test]
+    fn entrance_reaches_every_room() {
+        for layout in [
+            DungeonLayout::Bsp,
+            DungeonLayout::Scattered,
+            DungeonLayout::Caves,
+        ] {
+            for seed in 0..4 {
+                let settings = DungeonSettings {
+                    layout,
+                    ..default()
+                };
+                let dungeon = generate(&settings, Seed(seed));
+                let grid = &dungeon.grid;
+                let entrance = dungeon.entrance.expect("Dungeon without rooms");
+
+                // Flood fill from the entrance
+                let mut reached = vec![false; (grid.width * grid.height) as usize];
+                let mut open = vec![entrance.min];
+                while let Some(cell) = open.pop() {
+                    if !grid.get(cell).is_open() || reached[grid.index(cell)] {
+                        continue;
+                    }
+
+                    reached[grid.index(cell)] = true;
+                    open.extend([IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y].map(|d| cell + d));
+                }
+
+                for y in 0..grid.height as i32 {
+                    for x in 0..grid.width as i32 {
+                        let cell = IVec2::new(x, y);
+                        assert_eq!(
+                            grid.get(cell).is_open(),
+                            reached[grid.index(cell)],
+                            "{layout:?} {seed} {cell}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
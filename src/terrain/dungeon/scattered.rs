@@ -0,0 +1,52 @@
+use bevy::prelude::*;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use super::{carve_corridors, connect_rooms, DungeonGrid, DungeonSettings, Room, RoomGraph};
+
+pub(super) fn generate(
+    settings: &DungeonSettings,
+    rng: &mut ChaCha8Rng,
+) -> (DungeonGrid, RoomGraph) {
+    let mut grid = DungeonGrid::new(settings.width, settings.height);
+    let mut rooms: Vec<Room> = Vec::new();
+
+    // Inside the border walls
+    let available = IVec2::new(settings.width as i32, settings.height as i32) - 2;
+    let min_size = settings.min_room_size.max(1) as i32;
+    let max_size = (settings.max_room_size as i32).max(min_size);
+
+    for _ in 0..settings.room_attempts {
+        let size = IVec2::new(
+            rng.gen_range(min_size..=max_size),
+            rng.gen_range(min_size..=max_size),
+        );
+        if size.cmpgt(available).any() {
+            continue;
+        }
+
+        let min = IVec2::new(
+            rng.gen_range(1..=1 + available.x - size.x),
+            rng.gen_range(1..=1 + available.y - size.y),
+        );
+        let rect = IRect::from_corners(min, min + size);
+
+        // Always a wall between two rooms
+        if rooms
+            .iter()
+            .all(|room| room.rect.inflate(1).intersect(rect).is_empty())
+        {
+            rooms.push(Room::new(rect));
+        }
+    }
+
+    for room in &rooms {
+        grid.carve_room(room.rect);
+    }
+
+    let edges = connect_rooms(&rooms, settings.extra_connections, rng);
+    let graph = RoomGraph { rooms, edges };
+    carve_corridors(&mut grid, &graph, rng);
+
+    (grid, graph)
+}
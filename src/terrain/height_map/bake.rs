@@ -0,0 +1,169 @@
+use std::{f32::consts::FRAC_PI_2, f32::consts::TAU, fmt, path::Path};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::TextureFormatPixelInfo,
+    },
+};
+use image::{GrayImage, RgbaImage};
+
+use super::HeightMap;
+
+/// Settings of the horizon based ambient occlusion bake.
+#[derive(Clone, Copy, Debug)]
+pub struct AmbientOcclusionSettings {
+    /// Directions around each sample searched for the horizon.
+    pub directions: usize,
+    /// How far the horizon is searched, in world units.
+    pub radius: f32,
+    /// Height samples along each direction.
+    pub steps: usize,
+}
+
+impl Default for AmbientOcclusionSettings {
+    fn default() -> Self {
+        AmbientOcclusionSettings {
+            directions: 8,
+            radius: 2.,
+            steps: 12,
+        }
+    }
+}
+
+/// Tangent space normal map in `Rgba8Unorm`, for a mesh built from the height map.
+/// Tangents follow x and bitangents follow -z, so with normals along y the basis is
+/// right-handed, like the OpenGL style (green up) normal maps Bevy and glTF expect.
+pub fn normal_map(height_map: &HeightMap) -> Image {
+    bake(height_map, TextureFormat::Rgba8Unorm, |x, z| {
+        let normal = height_map.normal(x, z);
+        let encoded = (Vec3::new(normal.x, -normal.z, normal.y) * 0.5 + 0.5) * 255.;
+        let [r, g, b] = encoded.round().to_array().map(|channel| channel as u8);
+
+        vec![r, g, b, u8::MAX]
+    })
+}
+
+/// Angle between the surface and the horizontal plane in `R8Unorm`, from 0 for flat ground to 1
+/// for vertical walls.
+pub fn slope_map(height_map: &HeightMap) -> Image {
+    bake(height_map, TextureFormat::R8Unorm, |x, z| {
+        let slope = height_map.normal(x, z).y.clamp(-1., 1.).acos() / FRAC_PI_2;
+
+        vec![(slope * 255.).round() as u8]
+    })
+}
+
+/// Ambient occlusion in `R8Unorm`, from 0 for fully occluded to 1 for open sky.
+///
+/// For each sample, the highest point of the terrain within `radius` is searched in several
+/// directions, and the sky hidden below those horizons darkens the sample.
+pub fn ambient_occlusion_map(height_map: &HeightMap, settings: &AmbientOcclusionSettings) -> Image {
+    let directions: Vec<Vec2> = (0..settings.directions)
+        .map(|i| Vec2::from_angle(i as f32 * TAU / settings.directions as f32))
+        .collect();
+    let half_size = height_map.size / 2.;
+
+    bake(height_map, TextureFormat::R8Unorm, |x, z| {
+        let origin = height_map.position(x, z);
+        let mut occlusion = 0.;
+
+        for direction in &directions {
+            let mut horizon_tangent: f32 = 0.;
+
+            for step in 1..=settings.steps {
+                let distance = settings.radius * step as f32 / settings.steps as f32;
+                let position = origin.xz() + *direction * distance;
+
+                if position.abs().max_element() > half_size {
+                    break;
+                }
+
+                let height = height_map.height_at(position.x, position.y);
+                horizon_tangent = horizon_tangent.max((height - origin.y) / distance);
+            }
+
+            // Sine of the horizon angle
+            occlusion += horizon_tangent / (1. + horizon_tangent * horizon_tangent).sqrt();
+        }
+
+        let visibility = 1. - occlusion / directions.len().max(1) as f32;
+
+        vec![(visibility * 255.).round() as u8]
+    })
+}
+
+/// Image with one pixel per sample, x to the right and z downwards.
+fn bake(
+    height_map: &HeightMap,
+    format: TextureFormat,
+    pixel: impl Fn(usize, usize) -> Vec<u8>,
+) -> Image {
+    let samples = height_map.samples;
+    let mut data = Vec::with_capacity(samples * samples * format.pixel_size());
+
+    for z in 0..samples {
+        for x in 0..samples {
+            data.extend(pixel(x, z));
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: samples as u32,
+            height: samples as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        format,
+        RenderAssetUsages::default(),
+    )
+}
+
+#[derive(Debug)]
+pub enum SaveImageError {
+    UnsupportedFormat(TextureFormat),
+    Image(image::ImageError),
+}
+
+impl fmt::Display for SaveImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveImageError::UnsupportedFormat(format) => {
+                write!(f, "can't save images in {format:?} format")
+            }
+            SaveImageError::Image(error) => write!(f, "image error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveImageError {}
+
+impl From<image::ImageError> for SaveImageError {
+    fn from(error: image::ImageError) -> Self {
+        SaveImageError::Image(error)
+    }
+}
+
+/// Saves a baked image to a file, with the format given by the extension of `path`.
+pub fn save_image(image: &Image, path: impl AsRef<Path>) -> Result<(), SaveImageError> {
+    let (width, height) = (image.width(), image.height());
+    let data = image.data.clone();
+
+    match image.texture_descriptor.format {
+        TextureFormat::R8Unorm => GrayImage::from_raw(width, height, data)
+            .expect("Image data matches its size")
+            .save(path)?,
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+            RgbaImage::from_raw(width, height, data)
+                .expect("Image data matches its size")
+                .save(path)?
+        }
+        format => return Err(SaveImageError::UnsupportedFormat(format)),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,271 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use image::{ImageBuffer, Luma};
+
+use crate::utils::noise::{grid::Grid, Noise};
+
+use super::HeightMap;
+
+/// Magic number at the start of `HeightMapFormat::Binary` files.
+const BINARY_MAGIC: &[u8; 4] = b"HMAP";
+const BINARY_VERSION: u32 = 1;
+
+/// File formats a `HeightMap` can be exported to and imported from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeightMapFormat {
+    /// 16-bit grayscale PNG. Heights are rescaled to fill the whole range of values.
+    Png16,
+    /// Little endian `f32` heights with no header. Only square maps can be read back.
+    RawF32,
+    /// `HMAP` magic, `u32` version, `u32` width, `u32` depth, then little endian `f32` heights.
+    Binary,
+}
+
+impl HeightMapFormat {
+    /// Guesses the format from the extension of `path`.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "png" => Some(HeightMapFormat::Png16),
+            "raw" | "r32" => Some(HeightMapFormat::RawF32),
+            "hmap" | "bin" => Some(HeightMapFormat::Binary),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum HeightMapIoError {
+    Io(io::Error),
+    Image(image::ImageError),
+    InvalidData(String),
+}
+
+impl fmt::Display for HeightMapIoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeightMapIoError::Io(error) => write!(f, "I/O error: {error}"),
+            HeightMapIoError::Image(error) => write!(f, "image error: {error}"),
+            HeightMapIoError::InvalidData(message) => write!(f, "invalid height map: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for HeightMapIoError {}
+
+impl From<io::Error> for HeightMapIoError {
+    fn from(error: io::Error) -> Self {
+        HeightMapIoError::Io(error)
+    }
+}
+
+impl From<image::ImageError> for HeightMapIoError {
+    fn from(error: image::ImageError) -> Self {
+        HeightMapIoError::Image(error)
+    }
+}
+
+impl HeightMap {
+    /// Resamples `grid` to a square map, rescaling its values from [0, 1] to
+    /// [`min_depth`, `max_depth`].
+    pub fn from_grid(size: f32, grid: &Grid, min_depth: f32, max_depth: f32) -> Self {
+        let samples = grid.width().max(grid.depth());
+        let (extent_x, extent_z) = grid.extent();
+        let step_x = extent_x / (samples - 1).max(1) as f32;
+        let step_z = extent_z / (samples - 1).max(1) as f32;
+
+        let height_map = (0..samples)
+            .map(|x| {
+                (0..samples)
+                    .map(|z| {
+                        min_depth
+                            + grid.get((x as f32 * step_x, z as f32 * step_z))
+                                * (max_depth - min_depth)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        HeightMap {
+            size,
+            samples,
+            unit_size: size / samples as f32,
+            height_map,
+            holes: Vec::new(),
+        }
+    }
+
+    pub fn to_grid(&self) -> Grid {
+        let mut values = Vec::with_capacity(self.samples * self.samples);
+
+        for z in 0..self.samples {
+            for x in 0..self.samples {
+                values.push(self.height_map[x][z]);
+            }
+        }
+
+        Grid::new(self.samples, self.samples, values)
+    }
+
+    pub fn export(
+        &self,
+        path: impl AsRef<Path>,
+        format: HeightMapFormat,
+    ) -> Result<(), HeightMapIoError> {
+        write_grid(&self.to_grid(), path, format)
+    }
+
+    /// Reads a height map of `size` units, rescaling its heights to [`min_depth`, `max_depth`].
+    pub fn import(
+        path: impl AsRef<Path>,
+        format: HeightMapFormat,
+        size: f32,
+        min_depth: f32,
+        max_depth: f32,
+    ) -> Result<Self, HeightMapIoError> {
+        let grid = read_grid(path, format)?;
+
+        Ok(HeightMap::from_grid(
+            size,
+            &grid.normalized(),
+            min_depth,
+            max_depth,
+        ))
+    }
+}
+
+pub fn write_grid(
+    grid: &Grid,
+    path: impl AsRef<Path>,
+    format: HeightMapFormat,
+) -> Result<(), HeightMapIoError> {
+    match format {
+        HeightMapFormat::Png16 => {
+            let normalized = grid.normalized();
+            let pixels = normalized
+                .values()
+                .iter()
+                .map(|value| (value * u16::MAX as f32).round() as u16)
+                .collect();
+            let image: ImageBuffer<Luma<u16>, Vec<u16>> =
+                ImageBuffer::from_raw(grid.width() as u32, grid.depth() as u32, pixels)
+                    .expect("Grid has one value per pixel");
+
+            image.save_with_format(path, image::ImageFormat::Png)?;
+        }
+        HeightMapFormat::RawF32 => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            write_values(&mut writer, grid.values())?;
+            writer.flush()?;
+        }
+        HeightMapFormat::Binary => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            writer.write_all(BINARY_MAGIC)?;
+            writer.write_all(&BINARY_VERSION.to_le_bytes())?;
+            writer.write_all(&(grid.width() as u32).to_le_bytes())?;
+            writer.write_all(&(grid.depth() as u32).to_le_bytes())?;
+            write_values(&mut writer, grid.values())?;
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the values of a height map file as they are stored. PNG values are in [0, 1].
+pub fn read_grid(
+    path: impl AsRef<Path>,
+    format: HeightMapFormat,
+) -> Result<Grid, HeightMapIoError> {
+    match format {
+        HeightMapFormat::Png16 => {
+            let image = image::open(path)?.into_luma16();
+            let (width, depth) = (image.width() as usize, image.height() as usize);
+            let values = image
+                .into_raw()
+                .into_iter()
+                .map(|value| value as f32 / u16::MAX as f32)
+                .collect();
+
+            grid(width, depth, values)
+        }
+        HeightMapFormat::RawF32 => {
+            let mut bytes = Vec::new();
+            File::open(path)?.read_to_end(&mut bytes)?;
+            let values = read_values(&bytes)?;
+            let width = (values.len() as f64).sqrt() as usize;
+
+            if width * width != values.len() {
+                return Err(HeightMapIoError::InvalidData(format!(
+                    "RAW file with {} values isn't square",
+                    values.len()
+                )));
+            }
+
+            grid(width, width, values)
+        }
+        HeightMapFormat::Binary => {
+            let mut reader = BufReader::new(File::open(path)?);
+            let mut header = [0; 16];
+            reader.read_exact(&mut header)?;
+
+            if &header[0..4] != BINARY_MAGIC {
+                return Err(HeightMapIoError::InvalidData("missing HMAP magic".into()));
+            }
+
+            let field = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+            let (version, width, depth) = (field(4), field(8) as usize, field(12) as usize);
+
+            if version != BINARY_VERSION {
+                return Err(HeightMapIoError::InvalidData(format!(
+                    "unsupported version {version}"
+                )));
+            }
+
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+
+            grid(width, depth, read_values(&bytes)?)
+        }
+    }
+}
+
+/// `Grid::new`, with an error instead of a panic for mismatched dimensions.
+fn grid(width: usize, depth: usize, values: Vec<f32>) -> Result<Grid, HeightMapIoError> {
+    if width == 0 || depth == 0 || values.len() != width * depth {
+        return Err(HeightMapIoError::InvalidData(format!(
+            "expected {width}x{depth} heights, found {}",
+            values.len()
+        )));
+    }
+
+    Ok(Grid::new(width, depth, values))
+}
+
+fn write_values(writer: &mut impl Write, values: &[f32]) -> io::Result<()> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn read_values(bytes: &[u8]) -> Result<Vec<f32>, HeightMapIoError> {
+    let chunks = bytes.chunks_exact(4);
+
+    if !chunks.remainder().is_empty() {
+        return Err(HeightMapIoError::InvalidData(
+            "data isn't a whole number of f32 values".into(),
+        ));
+    }
+
+    Ok(chunks
+        .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
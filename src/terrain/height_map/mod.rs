@@ -0,0 +1,399 @@
+use crate::utils::noise::{perlin::Perlin, Noise};
+use bevy::render::render_resource::PrimitiveTopology;
+use bevy::{math::FloatExt, prelude::*, render::render_asset::RenderAssetUsages};
+use bevy_rapier3d::{geometry::shape_views::HeightFieldCellStatus, prelude::Collider};
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+pub mod bake;
+pub mod io;
+
+/// Cells along each side of the heightfield colliders a terrain is split in, so editing it only
+/// rebuilds the colliders around the edit.
+pub const COLLIDER_TILE_CELLS: usize = 64;
+
+/// Sample of a `HeightMap` that differs from the height it was generated with.
+/// Stores the new height rather than the difference, so applying it gives the exact same value.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HeightDelta {
+    pub x: u32,
+    pub z: u32,
+    pub height: f32,
+}
+
+#[derive(Component, Clone)]
+pub struct HeightMap {
+    pub size: f32,
+    pub samples: usize,
+    unit_size: f32,
+    pub height_map: Vec<Vec<f32>>,
+    /// Cells without ground, like the mouth of a shaft. Cell `(x, z)` is the square between the
+    /// samples `(x, z)` and `(x + 1, z + 1)`.
+    holes: Vec<URect>,
+}
+
+impl HeightMap {
+    pub fn new<T: Noise<Input = (f32, f32), Output = f32>>(
+        size: f32,
+        samples: usize,
+        min_depth: f32,
+        max_depth: f32,
+        noise: T,
+    ) -> Self {
+        let mut height_map = Vec::with_capacity(samples);
+        let conv_factor = noise_scale(samples);
+        let unit_size = size / samples as f32;
+
+        for i in 0..samples {
+            let mut row = Vec::with_capacity(samples);
+            for j in 0..samples {
+                row.push(
+                    min_depth
+                        + noise.get((i as f32 * conv_factor, j as f32 * conv_factor))
+                            * (max_depth - min_depth),
+                );
+            }
+            height_map.push(row);
+        }
+
+        HeightMap {
+            size,
+            samples,
+            unit_size,
+            height_map,
+            holes: Vec::new(),
+        }
+    }
+
+    /// Adds `noise`, rescaled from [0, 1] to [-amplitude, amplitude], to every sample. Sampled at
+    /// the same coordinates as the noise given to `new`.
+    pub fn add_noise<T: Noise<Input = (f32, f32), Output = f32>>(
+        &mut self,
+        noise: &T,
+        amplitude: f32,
+    ) {
+        let conv_factor = noise_scale(self.samples);
+
+        for (i, row) in self.height_map.iter_mut().enumerate() {
+            for (j, height) in row.iter_mut().enumerate() {
+                let value = noise.get((i as f32 * conv_factor, j as f32 * conv_factor));
+                *height += (value * 2. - 1.) * amplitude;
+            }
+        }
+    }
+
+    /// Position of the sample `(x, z)` relative to the center of the map.
+    pub fn position(&self, x: usize, z: usize) -> Vec3 {
+        Vec3::new(
+            x as f32 * self.unit_size - self.size / 2.,
+            self.height_map[x][z],
+            z as f32 * self.unit_size - self.size / 2.,
+        )
+    }
+
+    pub fn unit_size(&self) -> f32 {
+        self.unit_size
+    }
+
+    /// Fractional sample coordinates of `(x, z)`, relative to the center of the map.
+    pub fn sample_coordinates(&self, x: f32, z: f32) -> Vec2 {
+        Vec2::new(x + self.size / 2., z + self.size / 2.) / self.unit_size
+    }
+
+    /// Removes the ground of every cell overlapping the rectangle from `min` to `max`, relative
+    /// to the center of the map.
+    pub fn add_hole(&mut self, min: Vec2, max: Vec2) {
+        let cells = Vec2::splat((self.samples - 1) as f32);
+        let min = self
+            .sample_coordinates(min.x, min.y)
+            .floor()
+            .clamp(Vec2::ZERO, cells);
+        let max = self
+            .sample_coordinates(max.x, max.y)
+            .ceil()
+            .clamp(Vec2::ZERO, cells);
+
+        if min.x < max.x && min.y < max.y {
+            self.holes
+                .push(URect::from_corners(min.as_uvec2(), max.as_uvec2()));
+        }
+    }
+
+    /// Whether the cell `(x, z)` has no ground.
+    pub fn is_hole(&self, x: usize, z: usize) -> bool {
+        let (x, z) = (x as u32, z as u32);
+
+        self.holes.iter().any(|hole| {
+            (hole.min.x..hole.max.x).contains(&x) && (hole.min.y..hole.max.y).contains(&z)
+        })
+    }
+
+    /// Whether there is ground at `(x, z)`, relative to the center of the map.
+    pub fn has_ground(&self, x: f32, z: f32) -> bool {
+        let cell = self.sample_coordinates(x, z).floor();
+
+        cell.cmplt(Vec2::ZERO).any() || !self.is_hole(cell.x as usize, cell.y as usize)
+    }
+
+    /// Distance from `(x, z)`, relative to the center of the map, to the closest hole. Negative
+    /// inside of it.
+    pub fn hole_distance(&self, x: f32, z: f32) -> f32 {
+        let point = Vec2::new(x, z);
+
+        self.holes
+            .iter()
+            .map(|hole| {
+                let min = hole.min.as_vec2() * self.unit_size - self.size / 2.;
+                let max = hole.max.as_vec2() * self.unit_size - self.size / 2.;
+                let offset = (point - (min + max) / 2.).abs() - (max - min) / 2.;
+
+                offset.max(Vec2::ZERO).length() + offset.max_element().min(0.)
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Collider tiles along each side of the map.
+    pub fn collider_tiles(&self) -> usize {
+        (self.samples - 1).div_ceil(COLLIDER_TILE_CELLS).max(1)
+    }
+
+    /// Samples covered by a collider tile along one axis. Neighbouring tiles share their border.
+    pub fn collider_tile_samples(&self, tile: u32) -> RangeInclusive<usize> {
+        let first = tile as usize * COLLIDER_TILE_CELLS;
+        first..=(first + COLLIDER_TILE_CELLS).min(self.samples - 1)
+    }
+
+    /// Heightfield collider of the samples of the tile `(x, z)`, and where it goes relative to
+    /// the center of the map.
+    pub fn tile_collider(&self, tile: UVec2) -> (Collider, Transform) {
+        let (xs, zs) = (
+            self.collider_tile_samples(tile.x),
+            self.collider_tile_samples(tile.y),
+        );
+        let heights = xs
+            .clone()
+            .flat_map(|x| self.height_map[x][zs.clone()].iter().copied())
+            .collect();
+        let cells = Vec2::new(
+            (xs.end() - xs.start()) as f32,
+            (zs.end() - zs.start()) as f32,
+        );
+        let center = Vec2::new(
+            (xs.start() + xs.end()) as f32,
+            (zs.start() + zs.end()) as f32,
+        ) / 2.
+            * self.unit_size
+            - self.size / 2.;
+
+        let mut collider = Collider::heightfield(
+            heights,
+            zs.clone().count(),
+            xs.clone().count(),
+            Vec3::new(cells.x * self.unit_size, 1., cells.y * self.unit_size),
+        );
+
+        if let Some(mut heightfield) = collider.as_heightfield_mut() {
+            // Rows go along z and columns along x
+            for x in *xs.start()..*xs.end() {
+                for z in *zs.start()..*zs.end() {
+                    if self.is_hole(x, z) {
+                        heightfield.set_cell_status(
+                            z - zs.start(),
+                            x - xs.start(),
+                            HeightFieldCellStatus::CELL_REMOVED,
+                        );
+                    }
+                }
+            }
+        }
+
+        (collider, Transform::from_xyz(center.x, 0., center.y))
+    }
+
+    /// Normal of the surface at the sample `(x, z)`, from the heights of its neighbours.
+    pub fn normal(&self, x: usize, z: usize) -> Vec3 {
+        let last = self.samples - 1;
+        let heights = &self.height_map;
+        let (x0, x1) = (x.saturating_sub(1), (x + 1).min(last));
+        let (z0, z1) = (z.saturating_sub(1), (z + 1).min(last));
+
+        let slope_x = (heights[x1][z] - heights[x0][z]) / ((x1 - x0) as f32 * self.unit_size);
+        let slope_z = (heights[x][z1] - heights[x][z0]) / ((z1 - z0) as f32 * self.unit_size);
+
+        Vec3::new(-slope_x, 1., -slope_z).normalize()
+    }
+
+    /// Bilinearly interpolated height at `(x, z)`, relative to the center of the map.
+    /// Positions outside of the map are clamped to its border.
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        let last = self.samples - 1;
+        let coordinates = self
+            .sample_coordinates(x, z)
+            .clamp(Vec2::ZERO, Vec2::splat(last as f32));
+        let (fx, fz) = (coordinates.x, coordinates.y);
+        let (x0, z0) = (fx.floor() as usize, fz.floor() as usize);
+        let (x1, z1) = ((x0 + 1).min(last), (z0 + 1).min(last));
+        let (tx, tz) = (fx - x0 as f32, fz - z0 as f32);
+        let heights = &self.height_map;
+
+        heights[x0][z0]
+            .lerp(heights[x1][z0], tx)
+            .lerp(heights[x0][z1].lerp(heights[x1][z1], tx), tz)
+    }
+
+    /// Samples that differ from `original`, which must have the same number of samples.
+    pub fn deltas(&self, original: &HeightMap) -> Vec<HeightDelta> {
+        let mut deltas = Vec::new();
+
+        for x in 0..self.samples {
+            for z in 0..self.samples {
+                let height = self.height_map[x][z];
+
+                if height != original.height_map[x][z] {
+                    deltas.push(HeightDelta {
+                        x: x as u32,
+                        z: z as u32,
+                        height,
+                    });
+                }
+            }
+        }
+
+        deltas
+    }
+
+    /// Sets the heights changed by `deltas`. Deltas outside of the map are ignored.
+    pub fn apply_deltas(&mut self, deltas: &[HeightDelta]) {
+        for &HeightDelta { x, z, height } in deltas {
+            if let Some(sample) = self
+                .height_map
+                .get_mut(x as usize)
+                .and_then(|row| row.get_mut(z as usize))
+            {
+                *sample = height;
+            }
+        }
+    }
+
+    /// Replaces every height with the average of its 3x3 neighbourhood, `passes` times.
+    pub fn smooth(&mut self, passes: usize) {
+        let last = self.samples - 1;
+
+        for _ in 0..passes {
+            let heights = self.height_map.clone();
+
+            for x in 0..self.samples {
+                for z in 0..self.samples {
+                    let mut sum = 0.;
+                    let mut count = 0.;
+
+                    for row in &heights[x.saturating_sub(1)..=(x + 1).min(last)] {
+                        for height in &row[z.saturating_sub(1)..=(z + 1).min(last)] {
+                            sum += height;
+                            count += 1.;
+                        }
+                    }
+
+                    self.height_map[x][z] = sum / count;
+                }
+            }
+        }
+    }
+}
+
+/// Noise coordinates go from 0 to this along each side of a `HeightMap`, whatever its samples.
+pub const NOISE_EXTENT: f32 = 256. / 30.;
+
+/// Distance between the noise coordinates of two samples.
+fn noise_scale(samples: usize) -> f32 {
+    256. / samples as f32 / 30.
+}
+
+impl Default for HeightMap {
+    fn default() -> HeightMap {
+        HeightMap::new(
+            100.,
+            1000,
+            -0.2,
+            5.,
+            Perlin::new(
+                &[(0.5, 1.), (0.25, 2.), (0.125, 4.), (0.075, 8.)],
+                256,
+                None,
+            ),
+        )
+    }
+}
+
+impl From<HeightMap> for Mesh {
+    fn from(value: HeightMap) -> Mesh {
+        let mut indices = Vec::new();
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let samples = value.samples;
+
+        for x in 0..(samples - 1) {
+            for z in 0..(samples - 1) {
+                if value.is_hole(x, z) {
+                    continue;
+                }
+
+                // Triangle 1
+                indices.push((x * samples + z) as u32);
+                indices.push((x * samples + z + 1) as u32);
+                indices.push(((x + 1) * samples + z) as u32);
+                // Triangle 2
+                indices.push(((x + 1) * samples + z) as u32);
+                indices.push((x * samples + z + 1) as u32);
+                indices.push(((x + 1) * samples + z + 1) as u32);
+            }
+        }
+
+        // Same normals as the ones updated while sculpting, so edits don't leave seams
+        for x in 0..value.samples {
+            for z in 0..value.samples {
+                normals.push(value.normal(x, z));
+            }
+        }
+
+        let HeightMap {
+            size,
+            samples,
+            unit_size,
+            height_map,
+            ..
+        } = value;
+
+        for x in 0..samples {
+            for z in 0..samples {
+                vertices.push([
+                    x as f32 * unit_size - size / 2.,
+                    height_map[x][z],
+                    z as f32 * unit_size - size / 2.,
+                ]);
+                uvs.push([x as f32 / samples as f32, z as f32 / samples as f32]);
+            }
+        }
+
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(bevy::render::mesh::Indices::U32(indices))
+    }
+}
+
+impl From<&HeightMap> for Collider {
+    fn from(value: &HeightMap) -> Collider {
+        Collider::heightfield(
+            value.height_map.concat(),
+            value.samples,
+            value.samples,
+            Vec3::new(value.size, 1., value.size),
+        )
+    }
+}
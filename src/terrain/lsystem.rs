@@ -0,0 +1,504 @@
+use std::{f32::consts::TAU, fmt};
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+use rand::Rng;
+
+use crate::utils::seed::Seed;
+
+/// Symbol of an L-system string, with its parameters, like `F(1.5, 0.1)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+    pub symbol: char,
+    pub parameters: Vec<f32>,
+}
+
+impl Module {
+    pub fn new(symbol: char) -> Self {
+        Module {
+            symbol,
+            parameters: Vec::new(),
+        }
+    }
+
+    pub fn with_parameters(symbol: char, parameters: &[f32]) -> Self {
+        Module {
+            symbol,
+            parameters: parameters.to_vec(),
+        }
+    }
+
+    fn parameter(&self, index: usize) -> Option<f32> {
+        self.parameters.get(index).copied()
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol)?;
+
+        if let Some((first, rest)) = self.parameters.split_first() {
+            write!(f, "({first}")?;
+            for parameter in rest {
+                write!(f, ",{parameter}")?;
+            }
+            write!(f, ")")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads modules written like `F(1,0.1)[+A(0.5)]`. Whitespace is ignored, and so are parameters
+/// that aren't numbers.
+pub fn parse_modules(text: &str) -> Vec<Module> {
+    let mut modules: Vec<Module> = Vec::new();
+    let mut chars = text.chars().filter(|c| !c.is_whitespace()).peekable();
+
+    while let Some(symbol) = chars.next() {
+        let mut module = Module::new(symbol);
+
+        if chars.peek() == Some(&'(') {
+            chars.next();
+            let parameters: String = chars.by_ref().take_while(|&c| c != ')').collect();
+            module.parameters = parameters
+                .split(',')
+                .filter_map(|parameter| parameter.parse().ok())
+                .collect();
+        }
+
+        modules.push(module);
+    }
+
+    modules
+}
+
+pub fn modules_to_string(modules: &[Module]) -> String {
+    modules.iter().map(Module::to_string).collect()
+}
+
+type Condition = Box<dyn Fn(&[f32]) -> bool + Send + Sync>;
+type Successor = Box<dyn Fn(&[f32]) -> Vec<Module> + Send + Sync>;
+
+/// Replaces a symbol by a list of modules.
+///
+/// When several rules apply to the same module, one of them is picked at random according to
+/// their weights.
+pub struct Rule {
+    pub predecessor: char,
+    pub weight: f32,
+    condition: Option<Condition>,
+    successor: Successor,
+}
+
+impl Rule {
+    /// Rule with a fixed successor, like `Rule::new('F', "FF")`.
+    pub fn new(predecessor: char, successor: &str) -> Self {
+        let successor = parse_modules(successor);
+
+        Rule::parametric(predecessor, move |_| successor.clone())
+    }
+
+    /// Rule whose successor is computed from the parameters of the module it replaces.
+    pub fn parametric(
+        predecessor: char,
+        successor: impl Fn(&[f32]) -> Vec<Module> + Send + Sync + 'static,
+    ) -> Self {
+        Rule {
+            predecessor,
+            weight: 1.,
+            condition: None,
+            successor: Box::new(successor),
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Only applies the rule to modules whose parameters satisfy `condition`.
+    pub fn when(mut self, condition: impl Fn(&[f32]) -> bool + Send + Sync + 'static) -> Self {
+        self.condition = Some(Box::new(condition));
+        self
+    }
+
+    fn applies_to(&self, module: &Module) -> bool {
+        module.symbol == self.predecessor
+            && self
+                .condition
+                .iter()
+                .all(|condition| condition(&module.parameters))
+    }
+}
+
+pub struct LSystem {
+    pub axiom: Vec<Module>,
+    pub rules: Vec<Rule>,
+}
+
+impl LSystem {
+    pub fn new(axiom: &str, rules: Vec<Rule>) -> Self {
+        LSystem {
+            axiom: parse_modules(axiom),
+            rules,
+        }
+    }
+
+    /// Rewrites the axiom `iterations` times. Modules without a rule are kept as they are.
+    pub fn expand(&self, iterations: usize, seed: Seed) -> Vec<Module> {
+        let mut rng = seed.rng();
+        let mut modules = self.axiom.clone();
+
+        for _ in 0..iterations {
+            let mut next = Vec::with_capacity(modules.len() * 2);
+
+            for module in modules {
+                let candidates: Vec<&Rule> = self
+                    .rules
+                    .iter()
+                    .filter(|rule| rule.applies_to(&module))
+                    .collect();
+
+                let total_weight: f32 = candidates.iter().map(|rule| rule.weight).sum();
+                if candidates.is_empty() || total_weight <= 0. {
+                    next.push(module);
+                    continue;
+                }
+
+                let mut choice = rng.gen::<f32>() * total_weight;
+                let rule = candidates
+                    .iter()
+                    .find(|rule| {
+                        choice -= rule.weight;
+                        choice < 0.
+                    })
+                    .unwrap_or(&candidates[candidates.len() - 1]);
+
+                next.extend((rule.successor)(&module.parameters));
+            }
+
+            modules = next;
+        }
+
+        modules
+    }
+}
+
+/// How the modules of an L-system are drawn.
+///
+/// The turtle starts at the origin facing up, and understands:
+///
+/// | Symbol | Meaning |
+/// | --- | --- |
+/// | `F(length, radius)` | Branch forward. Both parameters are optional |
+/// | `f(length)` | Move forward without drawing |
+/// | `+(angle)` `-(angle)` | Turn left / right |
+/// | `&(angle)` `^(angle)` | Pitch down / up |
+/// | `\(angle)` `/(angle)` | Roll left / right |
+/// | `\|` | Turn around |
+/// | `!(radius)` | Set the radius, or multiply it by `radius_decay` |
+/// | `L(size)` | Leaf |
+/// | `[` `]` | Save / restore the state of the turtle |
+///
+/// Angles are in degrees. Any other symbol is ignored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TurtleSettings {
+    /// Default angle of turns, in degrees.
+    pub angle: f32,
+    pub length: f32,
+    pub radius: f32,
+    pub radius_decay: f32,
+    pub leaf_size: f32,
+    /// Sides of the branch cylinders.
+    pub segments: u32,
+}
+
+impl Default for TurtleSettings {
+    fn default() -> Self {
+        TurtleSettings {
+            angle: 22.5,
+            length: 0.3,
+            radius: 0.05,
+            radius_decay: 0.7,
+            leaf_size: 0.2,
+            segments: 6,
+        }
+    }
+}
+
+/// Meshes of a plant. Kept apart so branches and leaves can have different materials.
+pub struct PlantMesh {
+    pub branches: Mesh,
+    pub leaves: Mesh,
+}
+
+#[derive(Clone, Copy)]
+struct Turtle {
+    position: Vec3,
+    /// Heading along local Y, left along local X and up along local Z.
+    rotation: Quat,
+    radius: f32,
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn build(self) -> Mesh {
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+        .with_inserted_indices(Indices::U32(self.indices))
+    }
+
+    /// Open cylinder from `start` to `end`, around `rotation`'s heading.
+    fn cylinder(&mut self, start: Vec3, end: Vec3, rotation: Quat, radius: f32, segments: u32) {
+        let first = self.positions.len() as u32;
+
+        for (v, center) in [(0., start), (1., end)] {
+            for i in 0..=segments {
+                let angle = i as f32 / segments as f32 * TAU;
+                let normal = rotation * Vec3::new(angle.cos(), 0., angle.sin());
+
+                self.positions.push((center + normal * radius).to_array());
+                self.normals.push(normal.to_array());
+                self.uvs.push([i as f32 / segments as f32, v]);
+            }
+        }
+
+        let ring = segments + 1;
+        for i in 0..segments {
+            let (a, b) = (first + i, first + i + 1);
+            let (c, d) = (a + ring, b + ring);
+
+            self.indices.extend([a, c, b, b, c, d]);
+        }
+    }
+
+    /// Quad growing from `position` along `rotation`'s heading, facing its up direction.
+    fn leaf(&mut self, position: Vec3, rotation: Quat, size: f32) {
+        let first = self.positions.len() as u32;
+        let heading = rotation * Vec3::Y * size;
+        let side = rotation * Vec3::X * size * 0.25;
+        let normal = rotation * Vec3::Z;
+
+        let corners = [
+            (position - side, [0., 0.]),
+            (position + side, [1., 0.]),
+            (position + heading - side, [0., 1.]),
+            (position + heading + side, [1., 1.]),
+        ];
+
+        for (corner, uv) in corners {
+            self.positions.push(corner.to_array());
+            self.normals.push(normal.to_array());
+            self.uvs.push(uv);
+        }
+
+        self.indices
+            .extend([first, first + 1, first + 2, first + 1, first + 3, first + 2]);
+    }
+}
+
+/// Draws `modules` with a turtle, as described in `TurtleSettings`.
+pub fn interpret(modules: &[Module], settings: &TurtleSettings) -> PlantMesh {
+    let mut branches = MeshBuilder::default();
+    let mut leaves = MeshBuilder::default();
+    let mut stack = Vec::new();
+    let mut turtle = Turtle {
+        position: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        radius: settings.radius,
+    };
+
+    for module in modules {
+        let angle = module.parameter(0).unwrap_or(settings.angle).to_radians();
+
+        match module.symbol {
+            'F' => {
+                let length = module.parameter(0).unwrap_or(settings.length);
+                if let Some(radius) = module.parameter(1) {
+                    turtle.radius = radius;
+                }
+
+                let end = turtle.position + turtle.rotation * Vec3::Y * length;
+                branches.cylinder(
+                    turtle.position,
+                    end,
+                    turtle.rotation,
+                    turtle.radius,
+                    settings.segments,
+                );
+                turtle.position = end;
+            }
+            'f' => {
+                let length = module.parameter(0).unwrap_or(settings.length);
+                turtle.position += turtle.rotation * Vec3::Y * length;
+            }
+            '+' => turtle.rotation *= Quat::from_rotation_z(angle),
+            '-' => turtle.rotation *= Quat::from_rotation_z(-angle),
+            '&' => turtle.rotation *= Quat::from_rotation_x(angle),
+            '^' => turtle.rotation *= Quat::from_rotation_x(-angle),
+            '\\' => turtle.rotation *= Quat::from_rotation_y(angle),
+            '/' => turtle.rotation *= Quat::from_rotation_y(-angle),
+            '|' => turtle.rotation *= Quat::from_rotation_z(std::f32::consts::PI),
+            '!' => {
+                turtle.radius = module
+                    .parameter(0)
+                    .unwrap_or(turtle.radius * settings.radius_decay);
+            }
+            'L' => {
+                let size = module.parameter(0).unwrap_or(settings.leaf_size);
+                leaves.leaf(turtle.position, turtle.rotation, size);
+            }
+            '[' => stack.push(turtle),
+            ']' => {
+                if let Some(saved) = stack.pop() {
+                    turtle = saved;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    PlantMesh {
+        branches: branches.build(),
+        leaves: leaves.build(),
+    }
+}
+
+/// Built-in plants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default, Reflect)]
+pub enum Species {
+    /// Bushy plant from "The Algorithmic Beauty of Plants", with leaves along its branches and
+    /// some randomness in how they split.
+    #[default]
+    Bush,
+    /// Parametric tree whose branches get shorter and thinner, with random branching.
+    Oak,
+    /// Flat stochastic weed.
+    Weed,
+}
+
+impl Species {
+    pub const ALL: [Species; 3] = [Species::Bush, Species::Oak, Species::Weed];
+
+    pub fn lsystem(self) -> LSystem {
+        match self {
+            Species::Bush => LSystem::new(
+                "A",
+                vec![
+                    Rule::new('A', "[&FL!A]/////[&FL!A]///////[&FL!A]").with_weight(2.),
+                    Rule::new('A', "[&FL!A]////[&FL!A]////////[&FL!A]"),
+                    Rule::new('A', "[&FL!A]///////[&FL!A]"),
+                    Rule::new('F', "S/////F"),
+                    Rule::new('S', "FL"),
+                ],
+            ),
+            Species::Oak => LSystem::new(
+                "A(1)",
+                vec![
+                    Rule::parametric('A', |p| {
+                        let s = oak_size(p);
+                        parse_modules(&format!(
+                            "F({},{})[&(30)+(20)A({})][&(35)-(25)A({})]/(90)A({})",
+                            s,
+                            s * 0.06,
+                            s * 0.75,
+                            s * 0.7,
+                            s * 0.85
+                        ))
+                    })
+                    .when(|p| oak_size(p) >= 0.3)
+                    .with_weight(2.),
+                    Rule::parametric('A', |p| {
+                        let s = oak_size(p);
+                        parse_modules(&format!(
+                            "F({},{})[&(40)A({})]/(137)A({})",
+                            s,
+                            s * 0.06,
+                            s * 0.7,
+                            s * 0.8
+                        ))
+                    })
+                    .when(|p| oak_size(p) >= 0.3),
+                    Rule::parametric('A', |p| {
+                        let s = oak_size(p);
+                        parse_modules(&format!("F({s})L({s})"))
+                    })
+                    .when(|p| oak_size(p) < 0.3),
+                ],
+            ),
+            Species::Weed => LSystem::new(
+                "F",
+                vec![
+                    Rule::new('F', "F[+F]F[-F]F"),
+                    Rule::new('F', "F[+F]F"),
+                    Rule::new('F', "F[-F]F"),
+                ],
+            ),
+        }
+    }
+
+    pub fn iterations(self) -> usize {
+        match self {
+            Species::Bush => 5,
+            Species::Oak => 8,
+            Species::Weed => 4,
+        }
+    }
+
+    pub fn turtle(self) -> TurtleSettings {
+        match self {
+            Species::Bush => TurtleSettings {
+                angle: 22.5,
+                length: 0.25,
+                radius: 0.06,
+                radius_decay: 0.7,
+                leaf_size: 0.15,
+                segments: 6,
+            },
+            Species::Oak => TurtleSettings {
+                angle: 30.,
+                length: 1.,
+                radius: 0.06,
+                radius_decay: 0.7,
+                leaf_size: 0.3,
+                segments: 8,
+            },
+            Species::Weed => TurtleSettings {
+                angle: 25.7,
+                length: 0.05,
+                radius: 0.01,
+                radius_decay: 0.8,
+                leaf_size: 0.1,
+                segments: 4,
+            },
+        }
+    }
+
+    /// Expands and draws the plant. Different seeds give different plants of the same species.
+    pub fn generate(self, seed: Seed) -> PlantMesh {
+        let modules = self.lsystem().expand(self.iterations(), seed);
+
+        interpret(&modules, &self.turtle())
+    }
+}
+
+/// Size of an oak branch, from the parameters of its `A` module. Modules written without one
+/// grow like the trunk.
+fn oak_size(parameters: &[f32]) -> f32 {
+    parameters.first().copied().unwrap_or(1.)
+}
@@ -0,0 +1,172 @@
+use bevy::{
+    pbr::{ExtendedMaterial, MaterialExtension, OpaqueRendererMethod},
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef, ShaderType},
+};
+
+const SHADER_ASSET_PATH: &str = "shaders/terrain_material.wgsl";
+
+pub type TerrainMaterial = ExtendedMaterial<StandardMaterial, TerrainMaterialExtension>;
+
+/// A single splatting layer. Layers are blended in order, so later layers are painted on top
+/// of earlier ones wherever both their height and slope ranges match.
+///
+/// Slope goes from 0 (flat ground) to 1 (vertical wall), computed as `1 - normal.y`.
+#[derive(Clone, Copy, Debug, ShaderType, Reflect)]
+pub struct TerrainLayer {
+    pub color: Vec4,
+    pub height_range: Vec2,
+    pub slope_range: Vec2,
+    /// How fast the layer fades in at the edges of its ranges. Higher is sharper.
+    pub blend_sharpness: f32,
+    /// Layer of `TerrainMaterialConfig::textures` to use instead of `color`, `-1` for none.
+    pub texture_index: i32,
+}
+
+impl TerrainLayer {
+    pub fn from_color(color: Color) -> Self {
+        TerrainLayer {
+            color: LinearRgba::from(color).to_vec4(),
+            ..default()
+        }
+    }
+
+    pub fn from_texture(texture_index: u32) -> Self {
+        TerrainLayer {
+            texture_index: texture_index as i32,
+            ..default()
+        }
+    }
+
+    pub fn with_height(mut self, min: f32, max: f32) -> Self {
+        self.height_range = Vec2::new(min, max);
+        self
+    }
+
+    pub fn with_slope(mut self, min: f32, max: f32) -> Self {
+        self.slope_range = Vec2::new(min, max);
+        self
+    }
+
+    pub fn with_sharpness(mut self, blend_sharpness: f32) -> Self {
+        self.blend_sharpness = blend_sharpness;
+        self
+    }
+}
+
+impl Default for TerrainLayer {
+    fn default() -> Self {
+        TerrainLayer {
+            color: Vec4::ONE,
+            height_range: Vec2::new(-1e6, 1e6),
+            slope_range: Vec2::new(0., 1.),
+            blend_sharpness: 50.,
+            texture_index: -1,
+        }
+    }
+}
+
+/// Look of the terrain. Modify this resource to change the terrain material at runtime.
+#[derive(Resource, Clone, Debug)]
+pub struct TerrainMaterialConfig {
+    pub layers: Vec<TerrainLayer>,
+    /// Optional 2d array texture, one array layer per `TerrainLayer::texture_index`.
+    pub textures: Option<Handle<Image>>,
+    /// World units to texture coordinates.
+    pub texture_scale: f32,
+    /// Higher values reduce the blending between the three triplanar projections.
+    pub triplanar_sharpness: f32,
+}
+
+impl Default for TerrainMaterialConfig {
+    fn default() -> Self {
+        TerrainMaterialConfig {
+            layers: vec![
+                // Dirt
+                TerrainLayer::from_color(Color::srgb_u8(45, 25, 20)),
+                // Grass
+                TerrainLayer::from_color(Color::srgb_u8(19, 109, 21))
+                    .with_slope(0., 0.11)
+                    .with_sharpness(200.),
+            ],
+            textures: None,
+            texture_scale: 0.25,
+            triplanar_sharpness: 4.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ShaderType, Reflect)]
+pub struct TerrainMaterialSettings {
+    layer_count: u32,
+    texture_scale: f32,
+    triplanar_sharpness: f32,
+}
+
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct TerrainMaterialExtension {
+    // We need to ensure that the bindings of the base material and the extension do not conflict,
+    // so we start from binding slot 100, leaving slots 0-99 for the base material.
+    #[uniform(100)]
+    settings: TerrainMaterialSettings,
+    #[storage(101, read_only)]
+    layers: Vec<TerrainLayer>,
+    #[texture(102, dimension = "2d_array")]
+    #[sampler(103)]
+    textures: Option<Handle<Image>>,
+}
+
+impl From<&TerrainMaterialConfig> for TerrainMaterialExtension {
+    fn from(config: &TerrainMaterialConfig) -> Self {
+        let mut layers = config.layers.clone();
+
+        // Storage buffers can't be empty
+        if layers.is_empty() {
+            layers.push(TerrainLayer::default());
+        }
+
+        TerrainMaterialExtension {
+            settings: TerrainMaterialSettings {
+                layer_count: config.layers.len() as u32,
+                texture_scale: config.texture_scale,
+                triplanar_sharpness: config.triplanar_sharpness,
+            },
+            layers,
+            textures: config.textures.clone(),
+        }
+    }
+}
+
+impl MaterialExtension for TerrainMaterialExtension {
+    fn fragment_shader() -> ShaderRef {
+        SHADER_ASSET_PATH.into()
+    }
+}
+
+pub(super) fn create_terrain_material(config: &TerrainMaterialConfig) -> TerrainMaterial {
+    ExtendedMaterial {
+        base: StandardMaterial {
+            // can be used in forward or deferred mode.
+            opaque_render_method: OpaqueRendererMethod::Forward,
+            perceptual_roughness: 1.,
+            ..default()
+        },
+        extension: TerrainMaterialExtension::from(config),
+    }
+}
+
+pub(super) fn apply_material_config(
+    config: Res<TerrainMaterialConfig>,
+    terrain_material_q: Query<&Handle<TerrainMaterial>>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+
+    for handle in terrain_material_q.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.extension = TerrainMaterialExtension::from(config.as_ref());
+        }
+    }
+}
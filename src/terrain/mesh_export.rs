@@ -0,0 +1,449 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use bevy::{
+    prelude::*,
+    render::{mesh::VertexAttributeValues, render_resource::PrimitiveTopology},
+};
+
+const GLB_MAGIC: u32 = 0x4654_6C67; // "glTF"
+const GLB_VERSION: u32 = 2;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A; // "JSON"
+const GLB_CHUNK_BIN: u32 = 0x004E_4942; // "BIN\0"
+const GL_FLOAT: u32 = 5126;
+const GL_UNSIGNED_INT: u32 = 5125;
+const GL_ARRAY_BUFFER: u32 = 34962;
+const GL_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// File formats a terrain mesh can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshExportFormat {
+    /// glTF 2.0 binary (`.glb`), with everything in a single file.
+    Glb,
+    /// Wavefront OBJ. Vertex colors are written after the position, as Blender and MeshLab read them.
+    Obj,
+}
+
+impl MeshExportFormat {
+    /// Guesses the format from the extension of `path`.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "glb" => Some(MeshExportFormat::Glb),
+            "obj" => Some(MeshExportFormat::Obj),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MeshExportError {
+    Io(io::Error),
+    UnsupportedMesh(String),
+}
+
+impl fmt::Display for MeshExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshExportError::Io(error) => write!(f, "I/O error: {error}"),
+            MeshExportError::UnsupportedMesh(message) => write!(f, "unsupported mesh: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for MeshExportError {}
+
+impl From<io::Error> for MeshExportError {
+    fn from(error: io::Error) -> Self {
+        MeshExportError::Io(error)
+    }
+}
+
+/// Vertex data of a mesh, in the shape both formats need.
+struct MeshData<'a> {
+    positions: &'a [[f32; 3]],
+    normals: Option<&'a [[f32; 3]]>,
+    uvs: Option<&'a [[f32; 2]]>,
+    colors: Option<&'a [[f32; 4]]>,
+    indices: Vec<u32>,
+}
+
+impl<'a> MeshData<'a> {
+    fn new(mesh: &'a Mesh) -> Result<Self, MeshExportError> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return Err(MeshExportError::UnsupportedMesh(format!(
+                "{:?} topology, only triangle lists can be exported",
+                mesh.primitive_topology()
+            )));
+        }
+
+        let Some(positions) = mesh
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .and_then(VertexAttributeValues::as_float3)
+        else {
+            return Err(MeshExportError::UnsupportedMesh(
+                "missing Float32x3 positions".into(),
+            ));
+        };
+
+        if positions.is_empty() {
+            return Err(MeshExportError::UnsupportedMesh("no vertices".into()));
+        }
+
+        let normals = mesh
+            .attribute(Mesh::ATTRIBUTE_NORMAL)
+            .and_then(VertexAttributeValues::as_float3);
+        let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) => Some(uvs.as_slice()),
+            _ => None,
+        };
+        let colors = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+            Some(VertexAttributeValues::Float32x4(colors)) => Some(colors.as_slice()),
+            _ => None,
+        };
+        let indices = match mesh.indices() {
+            Some(indices) => indices.iter().map(|index| index as u32).collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+
+        Ok(MeshData {
+            positions,
+            normals,
+            uvs,
+            colors,
+            indices,
+        })
+    }
+}
+
+pub fn export_mesh(
+    mesh: &Mesh,
+    path: impl AsRef<Path>,
+    format: MeshExportFormat,
+) -> Result<(), MeshExportError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    match format {
+        MeshExportFormat::Glb => write_glb(mesh, &mut writer)?,
+        MeshExportFormat::Obj => write_obj(mesh, &mut writer)?,
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes `mesh` as a glTF 2.0 binary with a single node, including normals, UVs and vertex
+/// colors when the mesh has them.
+pub fn write_glb(mesh: &Mesh, writer: &mut impl Write) -> Result<(), MeshExportError> {
+    let data = MeshData::new(mesh)?;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut attributes = Vec::new();
+
+    let mut add_accessor = |buffer: &mut Vec<u8>,
+                            values: &[f32],
+                            components: usize,
+                            accessor_type: &str,
+                            bounds: Option<(Vec3, Vec3)>| {
+        let offset = buffer.len();
+        buffer.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+        buffer_views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{offset},"byteLength":{},"target":{GL_ARRAY_BUFFER}}}"#,
+            buffer.len() - offset
+        ));
+
+        // Positions need their bounds
+        let bounds = bounds
+            .map(|(min, max)| {
+                format!(
+                    r#","min":[{},{},{}],"max":[{},{},{}]"#,
+                    min.x, min.y, min.z, max.x, max.y, max.z
+                )
+            })
+            .unwrap_or_default();
+        accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{GL_FLOAT},"count":{},"type":"{accessor_type}"{bounds}}}"#,
+            buffer_views.len() - 1,
+            values.len() / components,
+        ));
+
+        accessors.len() - 1
+    };
+
+    let bounds = data.positions.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), position| {
+            let position = Vec3::from_array(*position);
+            (min.min(position), max.max(position))
+        },
+    );
+    let position = add_accessor(
+        &mut buffer,
+        data.positions.as_flattened(),
+        3,
+        "VEC3",
+        Some(bounds),
+    );
+    attributes.push(format!(r#""POSITION":{position}"#));
+
+    if let Some(normals) = data.normals {
+        let normal = add_accessor(&mut buffer, normals.as_flattened(), 3, "VEC3", None);
+        attributes.push(format!(r#""NORMAL":{normal}"#));
+    }
+    if let Some(uvs) = data.uvs {
+        let uv = add_accessor(&mut buffer, uvs.as_flattened(), 2, "VEC2", None);
+        attributes.push(format!(r#""TEXCOORD_0":{uv}"#));
+    }
+    if let Some(colors) = data.colors {
+        let color = add_accessor(&mut buffer, colors.as_flattened(), 4, "VEC4", None);
+        attributes.push(format!(r#""COLOR_0":{color}"#));
+    }
+
+    let offset = buffer.len();
+    buffer.extend(data.indices.iter().flat_map(|index| index.to_le_bytes()));
+    buffer_views.push(format!(
+        r#"{{"buffer":0,"byteOffset":{offset},"byteLength":{},"target":{GL_ELEMENT_ARRAY_BUFFER}}}"#,
+        buffer.len() - offset
+    ));
+    accessors.push(format!(
+        r#"{{"bufferView":{},"componentType":{GL_UNSIGNED_INT},"count":{},"type":"SCALAR"}}"#,
+        buffer_views.len() - 1,
+        data.indices.len()
+    ));
+
+    let mut json = format!(
+        concat!(
+            r#"{{"asset":{{"version":"2.0","generator":"procedural_generation"}},"#,
+            r#""scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0,"name":"Terrain"}}],"#,
+            r#""meshes":[{{"primitives":[{{"attributes":{{{}}},"indices":{},"mode":4}}]}}],"#,
+            r#""accessors":[{}],"bufferViews":[{}],"buffers":[{{"byteLength":{}}}]}}"#
+        ),
+        attributes.join(","),
+        accessors.len() - 1,
+        accessors.join(","),
+        buffer_views.join(","),
+        buffer.len(),
+    )
+    .into_bytes();
+
+    // Chunks are aligned to 4 bytes, JSON with spaces and binary with zeros
+    json.resize(json.len().next_multiple_of(4), b' ');
+    buffer.resize(buffer.len().next_multiple_of(4), 0);
+
+    let total_length = 12 + 8 + json.len() + 8 + buffer.len();
+
+    writer.write_all(&GLB_MAGIC.to_le_bytes())?;
+    writer.write_all(&GLB_VERSION.to_le_bytes())?;
+    writer.write_all(&(total_length as u32).to_le_bytes())?;
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(&GLB_CHUNK_JSON.to_le_bytes())?;
+    writer.write_all(&json)?;
+    writer.write_all(&(buffer.len() as u32).to_le_bytes())?;
+    writer.write_all(&GLB_CHUNK_BIN.to_le_bytes())?;
+    writer.write_all(&buffer)?;
+
+    Ok(())
+}
+
+/// Writes `mesh` as a Wavefront OBJ, including normals, UVs and vertex colors when the mesh
+/// has them.
+pub fn write_obj(mesh: &Mesh, writer: &mut impl Write) -> Result<(), MeshExportError> {
+    let data = MeshData::new(mesh)?;
+
+    writeln!(writer, "# procedural_generation terrain")?;
+    writeln!(writer, "o Terrain")?;
+
+    for (i, [x, y, z]) in data.positions.iter().enumerate() {
+        match data.colors {
+            Some(colors) => {
+                let [r, g, b, _] = colors[i];
+                writeln!(writer, "v {x} {y} {z} {r} {g} {b}")?;
+            }
+            None => writeln!(writer, "v {x} {y} {z}")?,
+        }
+    }
+
+    if let Some(uvs) = data.uvs {
+        for [u, v] in uvs {
+            // OBJ puts the origin of the texture at the bottom
+            writeln!(writer, "vt {u} {}", 1. - v)?;
+        }
+    }
+
+    if let Some(normals) = data.normals {
+        for [x, y, z] in normals {
+            writeln!(writer, "vn {x} {y} {z}")?;
+        }
+    }
+
+    for triangle in data.indices.chunks_exact(3) {
+        write!(writer, "f")?;
+
+        for index in triangle {
+            // OBJ indices start at 1
+            let index = index + 1;
+
+            match (data.uvs.is_some(), data.normals.is_some()) {
+                (true, true) => write!(writer, " {index}/{index}/{index}")?,
+                (true, false) => write!(writer, " {index}/{index}")?,
+                (false, true) => write!(writer, " {index}//{index}")?,
+                (false, false) => write!(writer, " {index}")?,
+            }
+        }
+
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use crate::{terrain::height_map::HeightMap, utils::noise::grid::Grid};
+
+    use super::*;
+
+    /// Terrain of 3x3 samples, so 9 vertices and 8 triangles.
+    fn terrain_mesh() -> Mesh {
+        let grid = Grid::new(3, 3, vec![0., 0.5, 1., 0.25, 0.75, 0.5, 1., 0., 0.25]);
+        Mesh::from(HeightMap::from_grid(4., &grid, 0., 2.))
+    }
+
+    fn export(mesh: &Mesh, format: MeshExportFormat, extension: &str) -> Vec<u8> {
+        let path = env::temp_dir().join(format!(
+            "mesh_export_{}_{extension}.{extension}",
+            std::process::id()
+        ));
+        export_mesh(mesh, &path, format).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Every number after `"key":` in `json`, in order.
+    fn numbers(json: &str, key: &str) -> Vec<usize> {
+        json.split(&format!(r#""{key}":"#))
+            .skip(1)
+            .map(|rest| {
+                let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap();
+                rest[..end].parse().unwrap()
+            })
+            .collect()
+    }
+
+    /// Positions and indices of `mesh`, to compare the exported ones with.
+    fn source(mesh: &Mesh) -> (Vec<[f32; 3]>, Vec<u32>) {
+        let positions = mesh
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .and_then(VertexAttributeValues::as_float3)
+            .unwrap()
+            .to_vec();
+        let indices = mesh
+            .indices()
+            .unwrap()
+            .iter()
+            .map(|index| index as u32)
+            .collect();
+
+        (positions, indices)
+    }
+
+    #[test]
+    fn glb_round_trip() {
+        let mesh = terrain_mesh();
+        let bytes = export(&mesh, MeshExportFormat::Glb, "glb");
+
+        assert_eq!(u32_at(&bytes, 0), GLB_MAGIC);
+        assert_eq!(u32_at(&bytes, 4), GLB_VERSION);
+        assert_eq!(u32_at(&bytes, 8) as usize, bytes.len());
+
+        let json_length = u32_at(&bytes, 12) as usize;
+        assert_eq!(u32_at(&bytes, 16), GLB_CHUNK_JSON);
+        let json = std::str::from_utf8(&bytes[20..20 + json_length]).unwrap();
+
+        let binary = 20 + json_length;
+        assert_eq!(u32_at(&bytes, binary + 4), GLB_CHUNK_BIN);
+        let binary_length = u32_at(&bytes, binary) as usize;
+        assert_eq!(binary + 8 + binary_length, bytes.len());
+        let buffer = &bytes[binary + 8..];
+
+        // Positions are the first accessor and buffer view, and indices the last ones
+        let counts = numbers(json, "count");
+        let offsets = numbers(json, "byteOffset");
+        assert_eq!(counts.first(), Some(&9));
+        assert_eq!(counts.last(), Some(&24));
+
+        let (positions, indices) = source(&mesh);
+        let read_positions: Vec<[f32; 3]> = (0..counts[0])
+            .map(|vertex| {
+                [0, 1, 2].map(|component| {
+                    f32::from_bits(u32_at(buffer, offsets[0] + vertex * 12 + component * 4))
+                })
+            })
+            .collect();
+        let read_indices: Vec<u32> = (0..counts[counts.len() - 1])
+            .map(|index| u32_at(buffer, offsets[offsets.len() - 1] + index * 4))
+            .collect();
+
+        assert_eq!(read_positions, positions);
+        assert_eq!(read_indices, indices);
+    }
+
+    #[test]
+    fn obj_round_trip() {
+        let mesh = terrain_mesh();
+        let bytes = export(&mesh, MeshExportFormat::Obj, "obj");
+        let obj = String::from_utf8(bytes).unwrap();
+
+        let lines = |prefix: &'static str| {
+            obj.lines()
+                .filter_map(move |line| line.strip_prefix(prefix))
+                .map(|line| line.split_whitespace())
+        };
+        let positions: Vec<[f32; 3]> = lines("v ")
+            .map(|mut values| [0; 3].map(|_| values.next().unwrap().parse().unwrap()))
+            .collect();
+        // OBJ indices start at 1
+        let indices: Vec<u32> = lines("f ")
+            .flatten()
+            .map(|corner| corner.split('/').next().unwrap().parse::<u32>().unwrap() - 1)
+            .collect();
+
+        assert_eq!(lines("vn ").count(), 9);
+        assert_eq!(lines("vt ").count(), 9);
+        assert_eq!((positions, indices), source(&mesh));
+    }
+
+    #[test]
+    fn empty_mesh() {
+        let mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            bevy::render::render_asset::RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, Vec::<[f32; 3]>::new());
+
+        assert!(matches!(
+            write_glb(&mesh, &mut Vec::new()),
+            Err(MeshExportError::UnsupportedMesh(_))
+        ));
+        assert!(matches!(
+            write_obj(&mesh, &mut Vec::new()),
+            Err(MeshExportError::UnsupportedMesh(_))
+        ));
+    }
+}
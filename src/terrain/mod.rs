@@ -1,24 +1,86 @@
-use bevy::{
-    pbr::{ExtendedMaterial, MaterialExtension, OpaqueRendererMethod},
-    prelude::*,
-    render::render_resource::{AsBindGroup, ShaderRef},
+use std::{env, time::Duration};
+
+use bevy::{prelude::*, time::common_conditions::on_timer, transform::TransformSystem};
+use serde::{Deserialize, Serialize};
+
+use creatures::{move_creatures, spawn_creatures, CreatureAssets};
+use dungeon::{dig_dungeon, spawn_dungeon, DungeonConfig};
+use height_map::{HeightDelta, HeightMap};
+use material::{
+    apply_material_config, create_terrain_material, TerrainMaterial, TerrainMaterialConfig,
 };
-use bevy_rapier3d::prelude::*;
+use navmesh::{build_navmeshes, invalidate_navmeshes, NavMeshSettings};
+use planet::{planet_center, spawn_planet, PlanetConfig, RadialGravity};
+use roads::{carve_road, plan_roads, spawn_roads, RoadNetwork, RoadSettings};
+use scatter::{snap_props, spawn_props, PropAssets, ScatterConfig};
+use tectonics::{continents, TectonicSettings};
+use voxel::{spawn_voxel_chunks, VoxelConfig};
+use water::{spawn_water, update_submerged, Shoreline};
 
-use height_map::HeightMap;
+use crate::{
+    utils::{
+        noise::perlin::Perlin,
+        seed::{Seed, WorldSeed},
+    },
+    AppState,
+};
 
-use crate::utils::noise::perlin::Perlin;
+pub mod biome;
+pub mod creatures;
+pub mod dungeon;
+pub mod height_map;
+pub mod lsystem;
+pub mod material;
+pub mod mesh_export;
+pub mod navmesh;
+pub mod planet;
+pub mod roads;
+pub mod scatter;
+pub mod tectonics;
+pub mod voxel;
+pub mod water;
 
-mod height_map;
+/// Seconds between checks for edited chunks whose navmesh must be built again.
+const NAVMESH_UPDATE_INTERVAL: f32 = 0.5;
 
 pub struct TerrainPlugin;
 
-#[derive(Resource, Clone, Copy, Debug)]
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MapInfo {
     pub size: f32,
     pub samples: usize,
     pub min_depth: f32,
     pub max_depth: f32,
+    pub sea_level: f32,
+    pub mode: TerrainMode,
+    #[serde(default)]
+    pub elevation: Elevation,
+    /// Roads between settlements, carved into the terrain. Older saves have none.
+    #[serde(default)]
+    pub roads: Option<RoadSettings>,
+}
+
+/// How the terrain is represented and meshed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TerrainMode {
+    /// Height map meshed as a grid, with a heightfield collider. Can't have caves or overhangs.
+    #[default]
+    Heightfield,
+    /// Height map combined with 3D noise, meshed with marching cubes and trimesh colliders.
+    Voxel,
+    /// Cube-sphere displaced by 3D noise, with gravity towards its center. Has no water volume,
+    /// props or dungeon.
+    Planet,
+}
+
+/// Where the heights of the terrain come from.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum Elevation {
+    /// Octaves of Perlin noise, hills without any large scale structure.
+    #[default]
+    Hills,
+    /// Continents and mountain ranges from tectonic plates, detailed with Perlin noise.
+    Continents(TectonicSettings),
 }
 
 impl Default for MapInfo {
@@ -28,117 +90,271 @@ impl Default for MapInfo {
             samples: 1000,
             min_depth: -3.,
             max_depth: 3.,
+            sea_level: -0.5,
+            mode: TerrainMode::Heightfield,
+            elevation: Elevation::Hills,
+            roads: Some(RoadSettings::default()),
         }
     }
 }
 
+/// Root entity of a piece of terrain. Everything that belongs to the chunk is spawned as its child.
+#[derive(Component)]
+pub struct TerrainChunk;
+
+/// Heightfield collider of a tile of a `TerrainChunk`, spawned as its child.
+#[derive(Component)]
+pub struct TerrainCollider {
+    /// Tile of the height map covered, see `HeightMap::tile_collider`.
+    pub tile: UVec2,
+}
+
+/// Despawns the terrain and generates it again from `MapInfo` and `VoxelConfig`, with `edits`
+/// applied on top of the generated heights.
+#[derive(Event, Clone, Debug, Default)]
+pub struct RegenerateTerrain {
+    pub edits: Vec<HeightDelta>,
+}
+
 impl Plugin for TerrainPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<MapInfo>()
-            .add_plugins(MaterialPlugin::<
-                ExtendedMaterial<StandardMaterial, MyCustomExtension>,
-            >::default())
+            .init_resource::<TerrainMaterialConfig>()
+            .init_resource::<VoxelConfig>()
+            .init_resource::<DungeonConfig>()
+            .init_resource::<PlanetConfig>()
+            .init_resource::<ScatterConfig>()
+            .init_resource::<PropAssets>()
+            .init_resource::<CreatureAssets>()
+            .init_resource::<NavMeshSettings>()
+            .insert_resource(WorldSeed::from_args(env::args().skip(1)))
+            .add_plugins(MaterialPlugin::<TerrainMaterial>::default())
+            .add_event::<RegenerateTerrain>()
             .add_systems(Startup, setup)
-            // .add_systems(Update, change_uniform)
-            ;
+            .add_systems(
+                Update,
+                (
+                    spawn_terrain,
+                    apply_material_config,
+                    update_submerged,
+                    snap_props,
+                    invalidate_navmeshes
+                        .run_if(on_timer(Duration::from_secs_f32(NAVMESH_UPDATE_INTERVAL))),
+                    move_creatures.run_if(in_state(AppState::InGame)),
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                build_navmeshes.after(TransformSystem::TransformPropagate),
+            );
     }
 }
 
-fn setup(
+/// Height map of the terrain as generated from `map_info`, before any edit.
+pub fn generate_height_map(map_info: &MapInfo, world_seed: Seed) -> HeightMap {
+    generate_terrain(map_info, world_seed).0
+}
+
+/// Height map of the terrain before any edit, with the roads carved into it.
+pub fn generate_terrain(map_info: &MapInfo, world_seed: Seed) -> (HeightMap, RoadNetwork) {
+    let mut height_map = match map_info.elevation {
+        Elevation::Hills => HeightMap::new(
+            map_info.size,
+            map_info.samples,
+            map_info.min_depth,
+            map_info.max_depth,
+            Perlin::new(
+                &[(0.75, 1.), (0.25, 2.)],
+                // &[(0.5, 1.), (0.25, 2.), (0.125, 4.), (0.075, 8.)],
+                256,
+                Some(world_seed.derive("terrain").value()),
+            ),
+        ),
+        Elevation::Continents(settings) => {
+            continents(map_info, &settings, world_seed.derive("continents"))
+        }
+    };
+
+    // A planet doesn't use the height map
+    let roads = match map_info.roads {
+        Some(settings) if map_info.mode != TerrainMode::Planet => {
+            let network = plan_roads(&height_map, map_info, &settings, world_seed.derive("roads"));
+
+            for road in &network.roads {
+                carve_road(&mut height_map, road, settings.falloff);
+            }
+
+            network
+        }
+        _ => RoadNetwork::default(),
+    };
+
+    (height_map, roads)
+}
+
+fn setup(mut regenerate_terrain: EventWriter<RegenerateTerrain>) {
+    regenerate_terrain.send_default();
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_terrain(
     mut commands: Commands,
+    mut regenerate_terrain: EventReader<RegenerateTerrain>,
+    chunk_q: Query<Entity, With<TerrainChunk>>,
     map_info: Res<MapInfo>,
-    // mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, MyExtension>>>,
-    mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, MyCustomExtension>>>,
+    world_seed: Res<WorldSeed>,
+    material_config: Res<TerrainMaterialConfig>,
+    voxel_config: Res<VoxelConfig>,
+    scatter_config: Res<ScatterConfig>,
+    dungeon_config: Res<DungeonConfig>,
+    planet_config: Res<PlanetConfig>,
+    prop_assets: Res<PropAssets>,
+    creature_assets: Res<CreatureAssets>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
 ) {
-    let height_map = HeightMap::new(
-        map_info.size,
-        map_info.samples,
-        map_info.min_depth,
-        map_info.max_depth,
-        Perlin::new(
-            &[(0.75, 1.), (0.25, 2.)],
-            // &[(0.5, 1.), (0.25, 2.), (0.125, 4.), (0.075, 8.)],
-            256,
-            None,
+    // Only the last request matters
+    let Some(RegenerateTerrain { edits }) = regenerate_terrain.read().last() else {
+        return;
+    };
+
+    for chunk in &chunk_q {
+        commands.entity(chunk).despawn_recursive();
+    }
+
+    let (mut height_map, roads) = generate_terrain(&map_info, **world_seed);
+    height_map.apply_deltas(edits);
+
+    let dungeon = match map_info.mode {
+        TerrainMode::Planet => None,
+        _ => dig_dungeon(
+            &mut height_map,
+            &map_info,
+            &dungeon_config,
+            world_seed.derive("dungeon"),
         ),
-    );
-    let height_collider = Collider::heightfield(
-        height_map.height_map.concat(),
-        height_map.samples,
-        height_map.samples,
-        Vec3::new(map_info.size, 1., map_info.size),
-    );
-    let terrain_mesh = Mesh::from(height_map);
-    // let mesh_collider = Collider::from_bevy_mesh(&terrain_mesh, &ComputedColliderShape::TriMesh)
-    //     .expect("es una pija");
-
-    commands
-        .spawn(MaterialMeshBundle {
-            transform: Transform::from_xyz(0., 0., 0.),
-            mesh: meshes.add(terrain_mesh),
-            material: materials.add(ExtendedMaterial {
-                base: StandardMaterial {
-                    // base_color: RED.into(),
-                    // can be used in forward or deferred mode.
-                    opaque_render_method: OpaqueRendererMethod::Forward,
-                    // in deferred mode, only the PbrInput can be modified (uvs, color and other material properties),
-                    // in forward mode, the output can also be modified after lighting is applied.
-                    // see the fragment shader `extended_material.wgsl` for more info.
-                    // Note: to run in deferred mode, you must also add a `DeferredPrepass` component to the camera and either
-                    // change the above to `OpaqueRendererMethod::Deferred` or add the `DefaultOpaqueRendererMethod` resource.
-                    // ? weird alpha
-                    // alpha_mode: AlphaMode::Blend,
-                    perceptual_roughness: 1.,
-                    ..Default::default()
-                },
-                extension: MyCustomExtension {
-                    quantize_steps: 100,
-                },
-            }),
-            ..default()
-        })
-        .with_children(|children| {
-            children
-                .spawn(height_collider)
-                // .insert(Restitution::coefficient(0.5))
-                .insert(TransformBundle::from(Transform::from_xyz(0.0, 0.0, 0.0)));
-        });
-}
+    };
 
-// fn change_uniform(
-//     q_my_custom_extension: Query<&Handle<ExtendedMaterial<StandardMaterial, MyCustomExtension>>>,
-//     mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, MyCustomExtension>>>,
-// ) {
-//     for handle in q_my_custom_extension.iter() {
-//         // info!("{:?}", h);
-//         let mat = materials
-//             .get_mut(handle)
-//             .expect("ExtendedMaterial not in assets.");
-//         // info!("{:?}", mat.extension.log);
-//         mat.extension.log = 1.;
-//     }
-// }
-
-#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
-struct MyCustomExtension {
-    // We need to ensure that the bindings of the base material and the extension do not conflict,
-    // so we start from binding slot 100, leaving slots 0-99 for the base material.
-    #[uniform(100)]
-    quantize_steps: u32,
-}
+    let shoreline = Shoreline::from_height_map(&height_map, map_info.sea_level);
+    let material = materials.add(create_terrain_material(&material_config));
+
+    let mut chunk = match map_info.mode {
+        TerrainMode::Heightfield => {
+            let terrain_mesh = Mesh::from(height_map.clone());
+            // let mesh_collider = Collider::from_bevy_mesh(&terrain_mesh, &ComputedColliderShape::TriMesh)
+            //     .expect("es una pija");
+
+            let mut chunk = commands.spawn(MaterialMeshBundle {
+                transform: Transform::from_xyz(0., 0., 0.),
+                mesh: meshes.add(terrain_mesh),
+                material,
+                ..default()
+            });
+
+            // Kept around so the terrain can be edited
+            chunk.insert(height_map.clone());
+
+            chunk.with_children(|children| {
+                let tiles = height_map.collider_tiles() as u32;
+
+                for z in 0..tiles {
+                    for x in 0..tiles {
+                        let tile = UVec2::new(x, z);
+                        let (collider, transform) = height_map.tile_collider(tile);
 
-impl MaterialExtension for MyCustomExtension {
-    fn fragment_shader() -> ShaderRef {
-        "shaders/custom_extension.wgsl".into()
-        // "shaders/extended_material.wgsl".into()
+                        children
+                            .spawn((collider, TerrainCollider { tile }))
+                            // .insert(Restitution::coefficient(0.5))
+                            .insert(TransformBundle::from(transform));
+                    }
+                }
+            });
+
+            chunk
+        }
+        TerrainMode::Voxel => {
+            let mut chunk = commands.spawn(SpatialBundle::default());
+
+            chunk.with_children(|children| {
+                spawn_voxel_chunks(
+                    children,
+                    &height_map,
+                    &map_info,
+                    &voxel_config,
+                    world_seed.derive("caves"),
+                    material,
+                    &mut meshes,
+                );
+            });
+
+            chunk
+        }
+        TerrainMode::Planet => {
+            let mut chunk = commands.spawn(SpatialBundle::default());
+
+            chunk.with_children(|children| {
+                spawn_planet(
+                    children,
+                    &map_info,
+                    &planet_config,
+                    world_seed.derive("planet"),
+                    &mut meshes,
+                    &mut standard_materials,
+                );
+            });
+
+            chunk
+        }
+    };
+
+    let chunk = chunk.insert((TerrainChunk, shoreline)).id();
+
+    if map_info.mode == TerrainMode::Planet {
+        commands.insert_resource(RadialGravity {
+            center: planet_center(&planet_config),
+            strength: planet_config.gravity,
+        });
+        return;
     }
 
-    // fn deferred_fragment_shader() -> ShaderRef {
-    //     "shaders/custom_extension.wgsl".into()
-    // }
+    commands.remove_resource::<RadialGravity>();
 
-    // fn vertex_shader() -> ShaderRef {
-    //     "shaders/custom_extension.wgsl".into()
-    // }
+    commands.entity(chunk).with_children(|children| {
+        spawn_water(children, &map_info, &mut meshes, &mut standard_materials);
+        spawn_roads(
+            children,
+            &height_map,
+            &roads,
+            &mut meshes,
+            &mut standard_materials,
+        );
+        spawn_props(
+            children,
+            &height_map,
+            &map_info,
+            &scatter_config,
+            world_seed.derive("props"),
+            &prop_assets,
+        );
+        spawn_creatures(
+            children,
+            &height_map,
+            &map_info,
+            &scatter_config,
+            world_seed.derive("creatures"),
+            &creature_assets,
+        );
+        if let Some(dungeon) = dungeon {
+            spawn_dungeon(
+                children,
+                dungeon,
+                &height_map,
+                &map_info,
+                &dungeon_config,
+                &mut meshes,
+                &mut standard_materials,
+            );
+        }
+    });
 }
@@ -0,0 +1,1071 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use bevy::{prelude::*, render::mesh::VertexAttributeValues};
+use bevy_rapier3d::prelude::*;
+
+use crate::{player, utils::pathfinding::astar};
+
+use super::{height_map::HeightMap, voxel::VoxelChunk, MapInfo, TerrainChunk, TerrainMode};
+
+/// Points of a triangle's edges are inside of it, even with rounding errors.
+const EDGE_TOLERANCE: f32 = 1e-4;
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct NavMeshSettings {
+    pub enabled: bool,
+    /// Side of the cells walkable space is sampled with.
+    pub cell_size: f32,
+    /// Cells along each side of a tile.
+    pub tile_cells: u32,
+    /// Steepest walkable ground, in radians.
+    pub max_slope: f32,
+    /// Highest step agents can climb, on top of the slope between two cells.
+    pub step_height: f32,
+    pub agent_radius: f32,
+    pub agent_height: f32,
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        NavMeshSettings {
+            enabled: true,
+            cell_size: 0.5,
+            tile_cells: 16,
+            max_slope: 0.,
+            step_height: 0.,
+            agent_radius: 0.,
+            agent_height: 0.,
+        }
+        .with_controller(
+            &KinematicCharacterController::default(),
+            // The player is the agent navmeshes are built for by default
+            player::HEIGHT,
+            player::RADIUS,
+        )
+    }
+}
+
+impl NavMeshSettings {
+    /// Walks where `controller` can, for an agent of `height` and `radius`. Lengths relative to
+    /// the shape of the controller are relative to `height`.
+    pub fn with_controller(
+        self,
+        controller: &KinematicCharacterController,
+        height: f32,
+        radius: f32,
+    ) -> Self {
+        let length = |length: CharacterLength| match length {
+            CharacterLength::Relative(fraction) => fraction * height,
+            CharacterLength::Absolute(length) => length,
+        };
+
+        NavMeshSettings {
+            max_slope: controller.max_slope_climb_angle,
+            step_height: controller
+                .autostep
+                .map_or(0., |autostep| length(autostep.max_height)),
+            agent_radius: radius,
+            agent_height: height,
+            ..self
+        }
+    }
+
+    /// Largest height difference between two neighbouring cells agents can walk across.
+    fn max_climb(&self) -> f32 {
+        self.step_height + self.cell_size * self.max_slope.min(1.5).tan()
+    }
+}
+
+/// Vertical cylinder agents can't walk through, like the collider of a tree.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Obstacle {
+    pub center: Vec2,
+    pub radius: f32,
+    pub bottom: f32,
+    pub top: f32,
+}
+
+impl Obstacle {
+    /// Cylinder around a ball, cylinder, capsule or cuboid collider, ignoring its rotation.
+    /// Other shapes, like the trimeshes and heightfields of the ground, aren't obstacles.
+    pub fn from_collider(collider: &Collider, transform: &Transform) -> Option<Self> {
+        let (radius, half_height) = if let Some(ball) = collider.as_ball() {
+            (ball.radius(), ball.radius())
+        } else if let Some(cylinder) = collider.as_cylinder() {
+            (cylinder.radius(), cylinder.half_height())
+        } else if let Some(capsule) = collider.as_capsule() {
+            (capsule.radius(), capsule.half_height() + capsule.radius())
+        } else if let Some(cuboid) = collider.as_cuboid() {
+            let half_extents = cuboid.half_extents();
+            (half_extents.xz().length(), half_extents.y)
+        } else {
+            return None;
+        };
+
+        // Shapes are scaled by rapier once it has seen them, undone to scale them the same way
+        let scale = transform.scale.abs() / collider.scale().abs().max(Vec3::splat(f32::EPSILON));
+        let half_height = half_height * scale.y;
+        let center = transform.translation;
+
+        Some(Obstacle {
+            center: center.xz(),
+            radius: radius * scale.x.max(scale.z),
+            bottom: center.y - half_height,
+            top: center.y + half_height,
+        })
+    }
+}
+
+/// Geometry a navmesh is built from, in the coordinates of the navmesh.
+#[derive(Clone, Debug)]
+pub struct NavMeshInput {
+    /// Area covered by the navmesh on the XZ plane.
+    pub bounds: Rect,
+    /// Ground, walkable where it isn't too steep. Only the highest ground of each cell is kept.
+    pub triangles: Vec<[Vec3; 3]>,
+    pub obstacles: Vec<Obstacle>,
+    /// Ground below this is under water, and not walkable.
+    pub sea_level: f32,
+}
+
+impl NavMeshInput {
+    pub fn new(bounds: Rect, sea_level: f32) -> Self {
+        NavMeshInput {
+            bounds,
+            triangles: Vec::new(),
+            obstacles: Vec::new(),
+            sea_level,
+        }
+    }
+
+    /// Adds the triangles of `mesh`, moved by `transform`.
+    pub fn add_mesh(&mut self, mesh: &Mesh, transform: &Transform) {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return;
+        };
+
+        let positions: Vec<Vec3> = positions
+            .iter()
+            .map(|&position| transform.transform_point(Vec3::from(position)))
+            .collect();
+        let indices: Vec<usize> = match mesh.indices() {
+            Some(indices) => indices.iter().collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        self.triangles.extend(
+            indices
+                .chunks_exact(3)
+                .map(|triangle| [0, 1, 2].map(|corner| positions[triangle[corner]])),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NavCell {
+    /// Height of the highest ground in the cell, if there's any.
+    pub height: Option<f32>,
+    pub walkable: bool,
+}
+
+/// Square of `NavMesh::tile_cells` cells, whose polygons don't cross its sides.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NavTile {
+    pub coordinates: UVec2,
+    /// Polygons of the tile, as indices into `NavMesh::polygons`.
+    pub polygons: Range<usize>,
+}
+
+/// Rectangle of walkable cells agents can go straight across.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NavPolygon {
+    /// Cells covered, the maximum excluded.
+    pub cells: URect,
+    pub tile: usize,
+    pub center: Vec3,
+    pub portals: Vec<Portal>,
+}
+
+/// Edge shared by two polygons, that agents can walk through.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Portal {
+    /// Polygon on the other side.
+    pub to: usize,
+    pub start: Vec3,
+    pub end: Vec3,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NavPath {
+    /// Polygons walked through, from the one of the start to the one of the goal.
+    pub corridor: Vec<usize>,
+    /// Shortest way through the corridor, from the start to the goal, on the ground.
+    pub waypoints: Vec<Vec3>,
+}
+
+/// Walkable space of a terrain chunk, in its coordinates, split in tiles of polygons.
+#[derive(Component, Clone, Debug)]
+pub struct NavMesh {
+    pub settings: NavMeshSettings,
+    origin: Vec2,
+    /// Cells along X and Z.
+    size: UVec2,
+    cells: Vec<NavCell>,
+    /// Polygon covering each cell, if it's walkable.
+    cell_polygons: Vec<Option<usize>>,
+    pub tiles: Vec<NavTile>,
+    pub polygons: Vec<NavPolygon>,
+}
+
+impl NavMesh {
+    pub fn build(input: &NavMeshInput, settings: &NavMeshSettings) -> Self {
+        let settings = NavMeshSettings {
+            cell_size: settings.cell_size.max(0.01),
+            tile_cells: settings.tile_cells.max(1),
+            ..*settings
+        };
+        let size = (input.bounds.size() / settings.cell_size)
+            .ceil()
+            .as_uvec2()
+            .max(UVec2::ONE);
+        let cells = (size.x * size.y) as usize;
+
+        let mut navmesh = NavMesh {
+            settings,
+            origin: input.bounds.min,
+            size,
+            cells: vec![NavCell::default(); cells],
+            cell_polygons: vec![None; cells],
+            tiles: Vec::new(),
+            polygons: Vec::new(),
+        };
+
+        navmesh.rasterize(&input.triangles);
+        navmesh.block(input);
+        navmesh.build_tiles();
+        navmesh.connect();
+        navmesh
+    }
+
+    /// Cells along X and Z.
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    pub fn cell(&self, cell: UVec2) -> &NavCell {
+        &self.cells[self.index(cell)]
+    }
+
+    fn index(&self, cell: UVec2) -> usize {
+        (cell.x + cell.y * self.size.x) as usize
+    }
+
+    fn cell_center(&self, cell: UVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + 0.5) * self.settings.cell_size
+    }
+
+    /// Cell that contains `point`, if it's in the navmesh.
+    pub fn cell_at(&self, point: Vec2) -> Option<UVec2> {
+        let cell = ((point - self.origin) / self.settings.cell_size)
+            .floor()
+            .as_ivec2();
+
+        (cell.cmpge(IVec2::ZERO).all() && cell.cmplt(self.size.as_ivec2()).all())
+            .then(|| cell.as_uvec2())
+    }
+
+    /// Height of the ground of the cell that contains `point`.
+    pub fn height_at(&self, point: Vec2) -> Option<f32> {
+        self.cell(self.cell_at(point)?).height
+    }
+
+    /// Keeps the highest ground of each cell, and whether it's flat enough to walk on.
+    fn rasterize(&mut self, triangles: &[[Vec3; 3]]) {
+        let min_normal_y = self.settings.max_slope.cos();
+        let last = self.size.as_ivec2() - 1;
+
+        for &[a, b, c] in triangles {
+            let Some(normal) = (b - a).cross(c - a).try_normalize() else {
+                continue;
+            };
+            // Either winding, not every mesh of the ground is wound the same way
+            let walkable = normal.y.abs() >= min_normal_y;
+
+            let min = a.xz().min(b.xz()).min(c.xz());
+            let max = a.xz().max(b.xz()).max(c.xz());
+            let first = ((min - self.origin) / self.settings.cell_size - 0.5)
+                .ceil()
+                .as_ivec2()
+                .max(IVec2::ZERO);
+            let end = ((max - self.origin) / self.settings.cell_size - 0.5)
+                .floor()
+                .as_ivec2()
+                .min(last);
+
+            for z in first.y..=end.y {
+                for x in first.x..=end.x {
+                    let cell = UVec2::new(x as u32, z as u32);
+                    let Some(height) = height_in_triangle(self.cell_center(cell), a, b, c) else {
+                        continue;
+                    };
+
+                    let index = self.index(cell);
+                    if self.cells[index]
+                        .height
+                        .is_some_and(|highest| highest >= height)
+                    {
+                        continue;
+                    }
+
+                    self.cells[index] = NavCell {
+                        height: Some(height),
+                        walkable,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Removes the cells under water, and those too close to an obstacle or to ground agents
+    /// can't walk on.
+    fn block(&mut self, input: &NavMeshInput) {
+        for cell in &mut self.cells {
+            if cell.height.is_some_and(|height| height < input.sea_level) {
+                cell.walkable = false;
+            }
+        }
+
+        self.erode();
+
+        for obstacle in &input.obstacles {
+            let reach = obstacle.radius + self.settings.agent_radius;
+            let corners = [obstacle.center - reach, obstacle.center + reach].map(|corner| {
+                ((corner - self.origin) / self.settings.cell_size)
+                    .floor()
+                    .as_ivec2()
+                    .clamp(IVec2::ZERO, self.size.as_ivec2() - 1)
+                    .as_uvec2()
+            });
+
+            for z in corners[0].y..=corners[1].y {
+                for x in corners[0].x..=corners[1].x {
+                    let cell = UVec2::new(x, z);
+                    let center = self.cell_center(cell);
+                    let index = self.index(cell);
+                    let Some(height) = self.cells[index].height else {
+                        continue;
+                    };
+
+                    // Low enough to step over, or high enough to walk under
+                    let in_the_way = obstacle.top > height + self.settings.step_height
+                        && obstacle.bottom < height + self.settings.agent_height;
+
+                    if in_the_way && center.distance(obstacle.center) < reach {
+                        self.cells[index].walkable = false;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes the walkable cells whose center is closer than the radius of agents to a cell that
+    /// isn't, or to a ledge between two cells, so they don't brush against walls and cliffs.
+    fn erode(&mut self) {
+        let cell_size = self.settings.cell_size;
+        let reach = (self.settings.agent_radius / cell_size).ceil() as i32 + 1;
+        let in_bounds =
+            |cell: IVec2| cell.cmpge(IVec2::ZERO).all() && cell.cmplt(self.size.as_ivec2()).all();
+        let mut eroded = Vec::new();
+
+        for cell in (0..self.size.y).flat_map(|z| (0..self.size.x).map(move |x| UVec2::new(x, z))) {
+            if !self.cell(cell).walkable {
+                continue;
+            }
+
+            let center = self.cell_center(cell);
+            // Distance from the center to a box around `middle`, `half_size` from it
+            let gap = |middle: Vec2, half_size: Vec2| {
+                ((middle - center).abs() - half_size)
+                    .max(Vec2::ZERO)
+                    .length()
+            };
+
+            let too_close = (-reach..=reach)
+                .flat_map(|z| (-reach..=reach).map(move |x| IVec2::new(x, z)))
+                .map(|offset| cell.as_ivec2() + offset)
+                .filter(|&other| in_bounds(other))
+                .map(|other| other.as_uvec2())
+                .any(|other| {
+                    let middle = self.cell_center(other);
+                    if !self.cell(other).walkable {
+                        return gap(middle, Vec2::splat(cell_size / 2.))
+                            < self.settings.agent_radius;
+                    }
+
+                    [IVec2::X, IVec2::Y].into_iter().any(|direction| {
+                        let next = other.as_ivec2() + direction;
+                        in_bounds(next)
+                            && self.cell(next.as_uvec2()).walkable
+                            && !self.connected(other, next.as_uvec2())
+                            && gap(
+                                middle + direction.as_vec2() * cell_size / 2.,
+                                direction.yx().as_vec2() * cell_size / 2.,
+                            ) < self.settings.agent_radius
+                    })
+                });
+
+            if too_close {
+                eroded.push(cell);
+            }
+        }
+
+        for cell in eroded {
+            let index = self.index(cell);
+            self.cells[index].walkable = false;
+        }
+    }
+
+    /// Whether agents can walk between two neighbouring cells.
+    fn connected(&self, a: UVec2, b: UVec2) -> bool {
+        let (a, b) = (self.cell(a), self.cell(b));
+
+        match (a.height, b.height) {
+            (Some(a_height), Some(b_height)) if a.walkable && b.walkable => {
+                (a_height - b_height).abs() <= self.settings.max_climb()
+            }
+            _ => false,
+        }
+    }
+
+    fn is_free(&self, cell: UVec2) -> bool {
+        self.cell(cell).walkable && self.cell_polygons[self.index(cell)].is_none()
+    }
+
+    /// Covers the walkable cells of each tile with rectangles, growing each one along X and then
+    /// along Z while all of its cells are connected.
+    fn build_tiles(&mut self) {
+        let tile_cells = self.settings.tile_cells;
+        let tiles = (self.size + tile_cells - 1) / tile_cells;
+
+        for tile_z in 0..tiles.y {
+            for tile_x in 0..tiles.x {
+                let coordinates = UVec2::new(tile_x, tile_z);
+                let area = URect::from_corners(
+                    coordinates * tile_cells,
+                    ((coordinates + 1) * tile_cells).min(self.size),
+                );
+                let first = self.polygons.len();
+
+                for z in area.min.y..area.max.y {
+                    for x in area.min.x..area.max.x {
+                        if self.is_free(UVec2::new(x, z)) {
+                            self.add_polygon(UVec2::new(x, z), area);
+                        }
+                    }
+                }
+
+                self.tiles.push(NavTile {
+                    coordinates,
+                    polygons: first..self.polygons.len(),
+                });
+            }
+        }
+    }
+
+    fn add_polygon(&mut self, first: UVec2, area: URect) {
+        let mut max_x = first.x + 1;
+        while max_x < area.max.x && {
+            let cell = UVec2::new(max_x, first.y);
+            self.is_free(cell) && self.connected(cell - UVec2::X, cell)
+        } {
+            max_x += 1;
+        }
+
+        let mut max_z = first.y + 1;
+        while max_z < area.max.y
+            && (first.x..max_x).all(|x| {
+                let cell = UVec2::new(x, max_z);
+                self.is_free(cell)
+                    && self.connected(cell - UVec2::Y, cell)
+                    && (x == first.x || self.connected(cell - UVec2::X, cell))
+            })
+        {
+            max_z += 1;
+        }
+
+        let cells = URect::new(first.x, first.y, max_x, max_z);
+        let index = self.polygons.len();
+        let middle = (cells.min + cells.max) / 2;
+        let center = self.cell_center(cells.min) - self.settings.cell_size / 2.
+            + cells.size().as_vec2() * self.settings.cell_size / 2.;
+
+        for z in cells.min.y..cells.max.y {
+            for x in cells.min.x..cells.max.x {
+                let cell = self.index(UVec2::new(x, z));
+                self.cell_polygons[cell] = Some(index);
+            }
+        }
+
+        self.polygons.push(NavPolygon {
+            cells,
+            tile: self.tiles.len(),
+            center: center.extend(self.cell(middle).height.unwrap_or(0.)).xzy(),
+            portals: Vec::new(),
+        });
+    }
+
+    /// Adds a portal for every stretch of edges between two connected polygons.
+    fn connect(&mut self) {
+        // Cells along the edges, by polygons, direction and line the edges are on
+        let mut edges: BTreeMap<(usize, usize, bool, u32), Vec<u32>> = BTreeMap::new();
+
+        for z in 0..self.size.y {
+            for x in 0..self.size.x {
+                let cell = UVec2::new(x, z);
+                let Some(polygon) = self.cell_polygons[self.index(cell)] else {
+                    continue;
+                };
+
+                for (along_x, next) in [(true, cell + UVec2::X), (false, cell + UVec2::Y)] {
+                    if next.cmpge(self.size).any() {
+                        continue;
+                    }
+
+                    let Some(other) = self.cell_polygons[self.index(next)] else {
+                        continue;
+                    };
+
+                    if other != polygon && self.connected(cell, next) {
+                        let (line, position) = if along_x { (next.x, z) } else { (next.y, x) };
+                        edges
+                            .entry((polygon, other, along_x, line))
+                            .or_default()
+                            .push(position);
+                    }
+                }
+            }
+        }
+
+        for ((polygon, other, along_x, line), positions) in edges {
+            let mut runs: Vec<(u32, u32)> = Vec::new();
+            for position in positions {
+                match runs.last_mut() {
+                    Some((_, end)) if *end + 1 == position => *end = position,
+                    _ => runs.push((position, position)),
+                }
+            }
+
+            for (start, end) in runs {
+                let corner = |position: u32, first: u32| {
+                    let (inside, outside, corner) = if along_x {
+                        (
+                            UVec2::new(line - 1, first),
+                            UVec2::new(line, first),
+                            UVec2::new(line, position),
+                        )
+                    } else {
+                        (
+                            UVec2::new(first, line - 1),
+                            UVec2::new(first, line),
+                            UVec2::new(position, line),
+                        )
+                    };
+                    let height = (self.cell(inside).height.unwrap_or(0.)
+                        + self.cell(outside).height.unwrap_or(0.))
+                        / 2.;
+                    let point = self.origin + corner.as_vec2() * self.settings.cell_size;
+
+                    Vec3::new(point.x, height, point.y)
+                };
+
+                let portal = Portal {
+                    to: other,
+                    start: corner(start, start),
+                    end: corner(end + 1, end),
+                };
+
+                self.polygons[polygon].portals.push(portal);
+                self.polygons[other].portals.push(Portal {
+                    to: polygon,
+                    ..portal
+                });
+            }
+        }
+    }
+
+    /// Polygon under `point`, or under the closest walkable cell around it, so agents pushed
+    /// against an obstacle still find their way.
+    pub fn polygon_at(&self, point: Vec3) -> Option<usize> {
+        let cell = ((point.xz() - self.origin) / self.settings.cell_size)
+            .floor()
+            .as_ivec2();
+        let polygon = |cell: IVec2| {
+            (cell.cmpge(IVec2::ZERO).all() && cell.cmplt(self.size.as_ivec2()).all())
+                .then(|| self.cell_polygons[self.index(cell.as_uvec2())])
+                .flatten()
+        };
+
+        polygon(cell).or_else(|| {
+            (-1..=1)
+                .flat_map(|z| (-1..=1).map(move |x| cell + IVec2::new(x, z)))
+                .filter_map(|cell| Some((cell, polygon(cell)?)))
+                .min_by(|(a, _), (b, _)| {
+                    let distance =
+                        |cell: &IVec2| self.cell_center(cell.as_uvec2()).distance(point.xz());
+                    distance(a).total_cmp(&distance(b))
+                })
+                .map(|(_, polygon)| polygon)
+        })
+    }
+
+    /// Shortest walkable path from `start` to `goal`, if they are connected.
+    pub fn find_path(&self, start: Vec3, goal: Vec3) -> Option<NavPath> {
+        let (from, to) = (self.polygon_at(start)?, self.polygon_at(goal)?);
+        let on_ground = |point: Vec3| {
+            Vec3::new(
+                point.x,
+                self.height_at(point.xz()).unwrap_or(point.y),
+                point.z,
+            )
+        };
+        let (start, goal) = (on_ground(start), on_ground(goal));
+
+        // Polygons with the portal they are entered through, as the polygon on the other side
+        // and the index of the portal there. Walking between the middle of the portals follows
+        // the corridor much closer than the centers of long polygons
+        let entry = |(_, entered): &(usize, Option<(usize, usize)>)| {
+            entered.map_or(start, |(previous, portal)| {
+                let portal = &self.polygons[previous].portals[portal];
+                (portal.start + portal.end) / 2.
+            })
+        };
+
+        let (path, _) = astar(
+            (from, None),
+            |&(polygon, _)| polygon == to,
+            |node| {
+                let (polygon, point) = (node.0, entry(node));
+                self.polygons[polygon]
+                    .portals
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, portal)| {
+                        let middle = (portal.start + portal.end) / 2.;
+                        ((portal.to, Some((polygon, index))), point.distance(middle))
+                    })
+            },
+            |node| entry(node).xz().distance(goal.xz()),
+        )?;
+
+        let mut portals = vec![(start, start)];
+        for pair in path.windows(2) {
+            let (polygon, _) = pair[0];
+            let (_, Some((_, index))) = pair[1] else {
+                unreachable!("Only the start isn't entered through a portal");
+            };
+            let portal = &self.polygons[polygon].portals[index];
+
+            // Left first, looking out of the polygon through the portal
+            let forward =
+                (portal.start + portal.end).xz() / 2. - self.polygons[polygon].center.xz();
+            portals.push(if forward.perp_dot((portal.start - portal.end).xz()) > 0. {
+                (portal.start, portal.end)
+            } else {
+                (portal.end, portal.start)
+            });
+        }
+        portals.push((goal, goal));
+
+        Some(NavPath {
+            corridor: path.into_iter().map(|(polygon, _)| polygon).collect(),
+            waypoints: funnel(&portals),
+        })
+    }
+}
+
+/// Height of the triangle `abc` at `point` on the XZ plane, if it's inside of it.
+fn height_in_triangle(point: Vec2, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    let area = (b.xz() - a.xz()).perp_dot(c.xz() - a.xz());
+    if area.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let weight_a = (c.xz() - b.xz()).perp_dot(point - b.xz()) / area;
+    let weight_b = (a.xz() - c.xz()).perp_dot(point - c.xz()) / area;
+    let weight_c = 1. - weight_a - weight_b;
+
+    [weight_a, weight_b, weight_c]
+        .iter()
+        .all(|&weight| weight >= -EDGE_TOLERANCE)
+        .then_some(a.y * weight_a + b.y * weight_b + c.y * weight_c)
+}
+
+/// Shortest path through `portals`, pairs of left and right ends from the start to the goal,
+/// with the simple stupid funnel algorithm.
+fn funnel(portals: &[(Vec3, Vec3)]) -> Vec<Vec3> {
+    // Positive when `point` is on the left of the line from `origin` to `towards` on the XZ plane
+    let side = |origin: Vec3, towards: Vec3, point: Vec3| {
+        (towards - origin).xz().perp_dot((point - origin).xz())
+    };
+
+    let (start, goal) = (portals[0].0, portals[portals.len() - 1].0);
+    let mut points = vec![start];
+    let (mut apex, mut left, mut right) = (start, start, start);
+    let (mut left_index, mut right_index) = (0, 0);
+    let mut i = 1;
+
+    while i < portals.len() {
+        let (next_left, next_right) = portals[i];
+
+        // Narrows the funnel from the right, or turns around its left side
+        if side(apex, right, next_right) >= 0. {
+            if apex == right || side(apex, left, next_right) < 0. {
+                right = next_right;
+                right_index = i;
+            } else {
+                points.push(left);
+                (apex, right, right_index) = (left, left, left_index);
+                i = left_index + 1;
+                continue;
+            }
+        }
+
+        // Narrows the funnel from the left, or turns around its right side
+        if side(apex, left, next_left) <= 0. {
+            if apex == left || side(apex, right, next_left) > 0. {
+                left = next_left;
+                left_index = i;
+            } else {
+                points.push(right);
+                (apex, left, left_index) = (right, right, right_index);
+                i = right_index + 1;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    if points.last() != Some(&goal) {
+        points.push(goal);
+    }
+
+    points
+}
+
+/// Builds the navmesh of terrain chunks that don't have one, from their ground and the colliders
+/// of their props. Runs after transforms are propagated, so the new props are in place.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn build_navmeshes(
+    mut commands: Commands,
+    chunk_q: Query<Entity, (With<TerrainChunk>, Without<NavMesh>)>,
+    children_q: Query<&Children>,
+    transform_q: Query<&GlobalTransform>,
+    mesh_q: Query<&Handle<Mesh>>,
+    ground_q: Query<&Handle<Mesh>, With<VoxelChunk>>,
+    // Characters move around, they aren't obstacles
+    collider_q: Query<&Collider, (Without<Sensor>, Without<KinematicCharacterController>)>,
+    meshes: Res<Assets<Mesh>>,
+    map_info: Res<MapInfo>,
+    settings: Res<NavMeshSettings>,
+) {
+    // Walking around a planet needs more than one up
+    if !settings.enabled || map_info.mode == TerrainMode::Planet {
+        return;
+    }
+
+    for chunk in &chunk_q {
+        let Ok(chunk_transform) = transform_q.get(chunk) else {
+            continue;
+        };
+        let local = |entity: Entity| {
+            transform_q
+                .get(entity)
+                .map(|transform| transform.reparented_to(chunk_transform))
+                .unwrap_or_default()
+        };
+
+        let mut input = NavMeshInput::new(
+            Rect::from_center_size(Vec2::ZERO, Vec2::splat(map_info.size)),
+            map_info.sea_level,
+        );
+
+        // The heightfield is the mesh of the chunk itself
+        if let Some(mesh) = mesh_q.get(chunk).ok().and_then(|handle| meshes.get(handle)) {
+            input.add_mesh(mesh, &Transform::IDENTITY);
+        }
+
+        for descendant in children_q.iter_descendants(chunk) {
+            if let Some(mesh) = ground_q
+                .get(descendant)
+                .ok()
+                .and_then(|handle| meshes.get(handle))
+            {
+                input.add_mesh(mesh, &local(descendant));
+            }
+
+            if let Ok(collider) = collider_q.get(descendant) {
+                input
+                    .obstacles
+                    .extend(Obstacle::from_collider(collider, &local(descendant)));
+            }
+        }
+
+        commands
+            .entity(chunk)
+            .insert(NavMesh::build(&input, &settings));
+    }
+}
+
+/// Removes the navmesh of chunks whose height map was edited, or all of them when the settings
+/// change, so they are built again.
+pub(super) fn invalidate_navmeshes(
+    mut commands: Commands,
+    chunk_q: Query<(Entity, Option<Ref<HeightMap>>), With<NavMesh>>,
+    settings: Res<NavMeshSettings>,
+) {
+    for (chunk, height_map) in &chunk_q {
+        let edited =
+            height_map.is_some_and(|height_map| height_map.is_changed() && !height_map.is_added());
+
+        if edited || settings.is_changed() {
+            commands.entity(chunk).remove::<NavMesh>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use crate::{terrain::height_map::NOISE_EXTENT, utils::noise::Noise};
+
+    use super::*;
+
+    const SETTINGS: NavMeshSettings = NavMeshSettings {
+        enabled: true,
+        cell_size: 0.5,
+        tile_cells: 8,
+        max_slope: FRAC_PI_4,
+        step_height: 0.25,
+        agent_radius: 0.3,
+        agent_height: 1.5,
+    };
+
+    fn input() -> NavMeshInput {
+        NavMeshInput::new(Rect::new(-10., -10., 10., 10.), -100.)
+    }
+
+    /// Ground from `min` to `max` on the XZ plane, at `height` of each point.
+    fn ground(input: &mut NavMeshInput, min: Vec2, max: Vec2, height: impl Fn(Vec2) -> f32) {
+        let corner = |x: f32, z: f32| Vec3::new(x, height(Vec2::new(x, z)), z);
+        let corners = [
+            corner(min.x, min.y),
+            corner(min.x, max.y),
+            corner(max.x, max.y),
+            corner(max.x, min.y),
+        ];
+
+        input.triangles.push([corners[0], corners[1], corners[2]]);
+        input.triangles.push([corners[0], corners[2], corners[3]]);
+    }
+
+    /// Noise rising from 0 to 1 along X.
+    struct Slope;
+
+    impl Noise for Slope {
+        type Input = (f32, f32);
+        type Output = f32;
+
+        fn get(&self, (x, _): (f32, f32)) -> f32 {
+            x / NOISE_EXTENT
+        }
+    }
+
+    /// Navmesh of the mesh of a 20 by 20 height map rising `rise` along X, with holes.
+    fn height_map_navmesh(rise: f32, holes: &[Rect]) -> NavMesh {
+        let mut height_map = HeightMap::new(20., 81, 0., rise, Slope);
+        for hole in holes {
+            height_map.add_hole(hole.min, hole.max);
+        }
+
+        let mut input = NavMeshInput::new(Rect::new(-9., -9., 9., 9.), -100.);
+        input.add_mesh(&Mesh::from(height_map), &Transform::IDENTITY);
+
+        NavMesh::build(&input, &SETTINGS)
+    }
+
+    fn flat(input: &mut NavMeshInput) {
+        ground(input, Vec2::splat(-10.), Vec2::splat(10.), |_| 0.);
+    }
+
+    fn ramp(angle: f32) -> NavMesh {
+        let mut input = input();
+        ground(&mut input, Vec2::splat(-10.), Vec2::splat(10.), |point| {
+            point.x * angle.tan()
+        });
+
+        NavMesh::build(&input, &SETTINGS)
+    }
+
+    #[test]
+    fn flat_ground() {
+        let mut input = input();
+        flat(&mut input);
+        let navmesh = NavMesh::build(&input, &SETTINGS);
+
+        assert_eq!(navmesh.size(), UVec2::splat(40));
+        assert!(navmesh.cell(UVec2::new(20, 20)).walkable);
+
+        let (start, goal) = (Vec3::new(-8., 0., -8.), Vec3::new(8., 0., 8.));
+        let path = navmesh
+            .find_path(start, goal)
+            .expect("No path on flat ground");
+        assert_eq!(path.waypoints.first(), Some(&start));
+        assert_eq!(path.waypoints.last(), Some(&goal));
+        // Straight there, even if it passes by corners of polygons
+        for waypoint in &path.waypoints {
+            assert!(
+                (waypoint.x - waypoint.z).abs() < 1e-4,
+                "{waypoint} off the line"
+            );
+        }
+    }
+
+    #[test]
+    fn max_slope_filters_steep_ground() {
+        let gentle = ramp(30_f32.to_radians());
+        let steep = ramp(60_f32.to_radians());
+
+        assert!(gentle.cell(UVec2::new(20, 20)).walkable);
+        assert!(gentle
+            .find_path(Vec3::new(-8., 0., 0.), Vec3::new(8., 0., 0.))
+            .is_some());
+
+        assert!(steep.cell(UVec2::new(20, 20)).height.is_some());
+        assert!(!steep.cell(UVec2::new(20, 20)).walkable);
+        assert!(steep.polygons.is_empty());
+    }
+
+    #[test]
+    fn cliffs_block_portals() {
+        let mut input = input();
+        ground(&mut input, Vec2::splat(-10.), Vec2::new(0., 10.), |_| 0.);
+        ground(&mut input, Vec2::new(0., -10.), Vec2::splat(10.), |_| 5.);
+        let navmesh = NavMesh::build(&input, &SETTINGS);
+
+        for polygon in &navmesh.polygons {
+            for portal in &polygon.portals {
+                let other = &navmesh.polygons[portal.to];
+                assert_eq!(polygon.center.x < 0., other.center.x < 0., "{portal:?}");
+            }
+        }
+
+        let (low, high) = (Vec3::new(-5., 0., 0.), Vec3::new(5., 5., 0.));
+        assert!(navmesh.find_path(low, Vec3::new(-5., 0., 8.)).is_some());
+        assert!(navmesh.find_path(low, high).is_none());
+    }
+
+    #[test]
+    fn obstacles_carve_cells() {
+        let mut input = input();
+        flat(&mut input);
+        let obstacle = |center: Vec2, top: f32| Obstacle {
+            center,
+            radius: 1.,
+            bottom: 0.,
+            top,
+        };
+        input.obstacles = vec![
+            obstacle(Vec2::ZERO, 3.),
+            // Low enough to step over
+            obstacle(Vec2::splat(5.), 0.1),
+        ];
+        let navmesh = NavMesh::build(&input, &SETTINGS);
+        let walkable = |point: Vec2| navmesh.cell(navmesh.cell_at(point).unwrap()).walkable;
+
+        assert!(!walkable(Vec2::ZERO));
+        assert!(!walkable(Vec2::new(1.1, 0.)));
+        assert!(walkable(Vec2::new(2., 0.)));
+        assert!(walkable(Vec2::splat(5.)));
+    }
+
+    #[test]
+    fn paths_go_around_walls() {
+        // Wall along x = 0, with a way around it past z = 4
+        let mut input = input();
+        flat(&mut input);
+        ground(
+            &mut input,
+            Vec2::new(-0.5, -10.),
+            Vec2::new(0.5, 4.),
+            |_| 3.,
+        );
+        let navmesh = NavMesh::build(&input, &SETTINGS);
+
+        let path = navmesh
+            .find_path(Vec3::new(-5., 0., -5.), Vec3::new(5., 0., -5.))
+            .expect("No path around the wall");
+
+        assert!(path.waypoints.len() > 2);
+        for pair in path.waypoints.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert!(a.y.abs() < 0.01, "{a} on the wall");
+
+            if (a.x < 0.5) != (b.x < 0.5) || (a.x > -0.5) != (b.x > -0.5) {
+                // Where the segment is at either side of the wall
+                for x in [-0.5, 0.5] {
+                    let t = (x - a.x) / (b.x - a.x);
+                    if (0. ..=1.).contains(&t) {
+                        assert!(a.z + (b.z - a.z) * t > 4., "{a} to {b} through the wall");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn height_map_slopes() {
+        let (start, goal) = (Vec3::new(-8., 0., 0.), Vec3::new(8., 0., 0.));
+
+        // About 14 degrees
+        let gentle = height_map_navmesh(5., &[]);
+        let middle = gentle.cell_at(Vec2::ZERO).unwrap();
+        assert!(gentle.cell(middle).walkable);
+        assert!((gentle.cell(middle).height.unwrap() - 2.5).abs() < 0.2);
+        assert!(gentle.find_path(start, goal).is_some());
+
+        // About 63 degrees
+        let steep = height_map_navmesh(40., &[]);
+        assert!(!steep.cell(steep.cell_at(Vec2::ZERO).unwrap()).walkable);
+        assert!(steep.polygons.is_empty());
+    }
+
+    #[test]
+    fn height_map_holes() {
+        let hole = Rect::new(-2., -6., 2., 6.);
+        let navmesh = height_map_navmesh(1., &[hole]);
+
+        let cell = navmesh.cell(navmesh.cell_at(Vec2::ZERO).unwrap());
+        assert_eq!(cell.height, None);
+        assert!(!cell.walkable);
+
+        let path = navmesh
+            .find_path(Vec3::new(-6., 0., 0.), Vec3::new(6., 0., 0.))
+            .expect("No path around the hole");
+        for pair in path.waypoints.windows(2) {
+            // Samples along each segment, none of them over the hole
+            for t in (0..=20).map(|step| step as f32 / 20.) {
+                let point = pair[0].lerp(pair[1], t).xz();
+                assert!(
+                    !hole.contains(point),
+                    "{} to {} over the hole",
+                    pair[0],
+                    pair[1]
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use bevy::{
+    color::palettes::css::DEEP_SKY_BLUE,
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+use bevy_rapier3d::prelude::*;
+
+use crate::utils::{
+    noise::{perlin_3d::Perlin3D, Noise},
+    seed::Seed,
+};
+
+use super::{biome::Biome, MapInfo};
+
+/// Distance on the unit sphere used to estimate normals.
+const NORMAL_EPSILON: f32 = 1e-3;
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct PlanetConfig {
+    /// Radius at height 0. Terrain heights are added to it.
+    pub radius: f32,
+    /// Cells along each side of a chunk.
+    pub chunk_cells: u32,
+    /// Chunks along each side of a cube face.
+    pub chunks_per_face: u32,
+    /// How many hills there are around the planet.
+    pub frequency: f32,
+    /// Acceleration towards the center of the planet.
+    pub gravity: f32,
+}
+
+impl Default for PlanetConfig {
+    fn default() -> Self {
+        PlanetConfig {
+            radius: 40.,
+            chunk_cells: 48,
+            chunks_per_face: 4,
+            frequency: 2.5,
+            gravity: 9.81,
+        }
+    }
+}
+
+/// Gravity pulling everything towards `center`, used instead of the rapier gravity while the
+/// terrain is a planet.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct RadialGravity {
+    pub center: Vec3,
+    pub strength: f32,
+}
+
+impl RadialGravity {
+    pub fn at(&self, position: Vec3) -> Vec3 {
+        (self.center - position).normalize_or_zero() * self.strength
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PosX,
+        CubeFace::NegX,
+        CubeFace::PosY,
+        CubeFace::NegY,
+        CubeFace::PosZ,
+        CubeFace::NegZ,
+    ];
+
+    pub fn normal(self) -> Vec3 {
+        match self {
+            CubeFace::PosX => Vec3::X,
+            CubeFace::NegX => Vec3::NEG_X,
+            CubeFace::PosY => Vec3::Y,
+            CubeFace::NegY => Vec3::NEG_Y,
+            CubeFace::PosZ => Vec3::Z,
+            CubeFace::NegZ => Vec3::NEG_Z,
+        }
+    }
+
+    /// Directions of the grid of the face, whose cross product is the normal so triangles face
+    /// outwards.
+    fn axes(self) -> (Vec3, Vec3) {
+        match self {
+            CubeFace::PosX => (Vec3::Y, Vec3::Z),
+            CubeFace::NegX => (Vec3::Z, Vec3::Y),
+            CubeFace::PosY => (Vec3::Z, Vec3::X),
+            CubeFace::NegY => (Vec3::X, Vec3::Z),
+            CubeFace::PosZ => (Vec3::X, Vec3::Y),
+            CubeFace::NegZ => (Vec3::Y, Vec3::X),
+        }
+    }
+
+    /// Point of the unit sphere for the sample `(x, y)` of a face with `cells` cells per side.
+    ///
+    /// Samples on the edges of two faces give exactly the same point, so there are no cracks.
+    pub fn direction(self, x: u32, y: u32, cells: u32) -> Vec3 {
+        let (a, b) = self.axes();
+        // Integer numerators so opposite samples are exactly opposite
+        let coordinate = |i: u32| (2 * i as i64 - cells as i64) as f32 / cells as f32;
+
+        cube_to_sphere(self.normal() + a * coordinate(x) + b * coordinate(y))
+    }
+}
+
+/// Maps a point on the surface of the cube from -1 to 1 onto the unit sphere, with cells of more
+/// even sizes than normalizing it.
+pub fn cube_to_sphere(point: Vec3) -> Vec3 {
+    let squared = point * point;
+    // Sums in the same order for every face, so shared edges match exactly
+    let scale = |a: f32, b: f32| (1. - (a + b) / 2. + a * b / 3.).max(0.).sqrt();
+
+    Vec3::new(
+        point.x * scale(squared.y, squared.z),
+        point.y * scale(squared.z, squared.x),
+        point.z * scale(squared.x, squared.y),
+    )
+}
+
+/// Terrain height at each point of the unit sphere.
+pub struct PlanetNoise {
+    noise: Perlin3D,
+    frequency: f32,
+    min_depth: f32,
+    max_depth: f32,
+}
+
+impl PlanetNoise {
+    pub fn new(map_info: &MapInfo, config: &PlanetConfig, seed: Seed) -> Self {
+        PlanetNoise {
+            noise: Perlin3D::new(&[(0.75, 1.), (0.25, 2.)], 256, Some(seed.value())),
+            frequency: config.frequency,
+            min_depth: map_info.min_depth,
+            max_depth: map_info.max_depth,
+        }
+    }
+}
+
+impl Noise for PlanetNoise {
+    type Input = Vec3;
+    type Output = f32;
+
+    fn get(&self, direction: Vec3) -> f32 {
+        // Offset so the poles aren't on a lattice point of the noise
+        let point = direction * self.frequency + 0.5;
+
+        self.min_depth
+            + (self.max_depth - self.min_depth) * self.noise.get((point.x, point.y, point.z))
+    }
+}
+
+/// Heights of one face of the planet, like a `HeightMap` bent over a sixth of the sphere.
+pub struct PlanetFace {
+    pub face: CubeFace,
+    /// Cells along each side.
+    pub cells: u32,
+    /// `(cells + 1)²` heights, row by row.
+    pub heights: Vec<f32>,
+}
+
+impl PlanetFace {
+    pub fn new(face: CubeFace, cells: u32, noise: &PlanetNoise) -> Self {
+        let heights = (0..=cells)
+            .flat_map(|y| (0..=cells).map(move |x| (x, y)))
+            .map(|(x, y)| noise.get(face.direction(x, y, cells)))
+            .collect();
+
+        PlanetFace {
+            face,
+            cells,
+            heights,
+        }
+    }
+
+    pub fn height(&self, x: u32, y: u32) -> f32 {
+        self.heights[(x + y * (self.cells + 1)) as usize]
+    }
+
+    /// Position of a sample, relative to the center of the planet.
+    pub fn position(&self, x: u32, y: u32, radius: f32) -> Vec3 {
+        self.face.direction(x, y, self.cells) * (radius + self.height(x, y))
+    }
+}
+
+/// Piece of a planet face, spawned with its own mesh and collider.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct PlanetChunk {
+    pub face: CubeFace,
+    pub coordinates: UVec2,
+}
+
+/// Where the center of the planet is, so its top is around the origin like the flat terrain.
+pub fn planet_center(config: &PlanetConfig) -> Vec3 {
+    Vec3::NEG_Y * config.radius
+}
+
+/// Spawns the six faces of the planet in chunks, and a sea sphere.
+pub(super) fn spawn_planet(
+    parent: &mut ChildBuilder,
+    map_info: &MapInfo,
+    config: &PlanetConfig,
+    seed: Seed,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    let config = &PlanetConfig {
+        chunk_cells: config.chunk_cells.max(1),
+        chunks_per_face: config.chunks_per_face,
+        ..*config
+    };
+    let noise = PlanetNoise::new(map_info, config, seed);
+    let cells = config.chunk_cells * config.chunks_per_face;
+    let material = materials.add(StandardMaterial {
+        perceptual_roughness: 1.,
+        ..default()
+    });
+
+    parent
+        .spawn(SpatialBundle::from_transform(Transform::from_translation(
+            planet_center(config),
+        )))
+        .with_children(|planet| {
+            for face in CubeFace::ALL {
+                let heights = PlanetFace::new(face, cells, &noise);
+
+                for y in 0..config.chunks_per_face {
+                    for x in 0..config.chunks_per_face {
+                        let coordinates = UVec2::new(x, y);
+                        let mesh = chunk_mesh(&heights, coordinates, map_info, config, &noise);
+                        let collider =
+                            Collider::from_bevy_mesh(&mesh, &ComputedColliderShape::TriMesh)
+                                .expect("Planet chunk mesh is not a valid trimesh");
+
+                        planet
+                            .spawn(PbrBundle {
+                                mesh: meshes.add(mesh),
+                                material: material.clone(),
+                                ..default()
+                            })
+                            .insert((PlanetChunk { face, coordinates }, collider));
+                    }
+                }
+            }
+
+            if map_info.sea_level > map_info.min_depth {
+                planet.spawn(PbrBundle {
+                    mesh: meshes.add(
+                        Sphere::new(config.radius + map_info.sea_level)
+                            .mesh()
+                            .ico(6)
+                            .expect("Too many subdivisions"),
+                    ),
+                    material: materials.add(StandardMaterial {
+                        base_color: Color::from(DEEP_SKY_BLUE).with_alpha(0.6),
+                        alpha_mode: AlphaMode::Blend,
+                        perceptual_roughness: 0.1,
+                        reflectance: 0.3,
+                        ..default()
+                    }),
+                    ..default()
+                });
+            }
+        });
+}
+
+fn chunk_mesh(
+    face: &PlanetFace,
+    coordinates: UVec2,
+    map_info: &MapInfo,
+    config: &PlanetConfig,
+    noise: &PlanetNoise,
+) -> Mesh {
+    let first = coordinates * config.chunk_cells;
+    let side = config.chunk_cells + 1;
+
+    let mut positions = Vec::with_capacity((side * side) as usize);
+    let mut normals = Vec::with_capacity(positions.capacity());
+    let mut colors = Vec::with_capacity(positions.capacity());
+
+    for y in first.y..first.y + side {
+        for x in first.x..first.x + side {
+            let direction = face.face.direction(x, y, face.cells);
+            let height = face.height(x, y);
+            let normal = surface_normal(direction, config.radius, noise);
+
+            // Slope relative to the local up, the biomes expect the up of a flat map
+            let local_normal = Quat::from_rotation_arc(direction, Vec3::Y) * normal;
+            let biome =
+                Biome::classify(height, local_normal, map_info.sea_level, map_info.max_depth);
+
+            positions.push(face.position(x, y, config.radius));
+            normals.push(normal);
+            colors.push(biome.color().to_linear().to_f32_array());
+        }
+    }
+
+    let mut indices = Vec::with_capacity((config.chunk_cells * config.chunk_cells * 6) as usize);
+    for y in 0..config.chunk_cells {
+        for x in 0..config.chunk_cells {
+            let corner = x + y * side;
+            let (right, up) = (corner + 1, corner + side);
+
+            indices.extend([corner, right, up + 1, corner, up + 1, up]);
+        }
+    }
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+/// Normal of the surface from the noise around `direction`, which doesn't depend on the face so
+/// the lighting is continuous across face edges.
+fn surface_normal(direction: Vec3, radius: f32, noise: &PlanetNoise) -> Vec3 {
+    let surface = |direction: Vec3| direction * (radius + noise.get(direction));
+
+    let tangent = direction.any_orthonormal_vector() * NORMAL_EPSILON;
+    let bitangent = direction.cross(tangent);
+
+    let along_tangent =
+        surface((direction + tangent).normalize()) - surface((direction - tangent).normalize());
+    let along_bitangent =
+        surface((direction + bitangent).normalize()) - surface((direction - bitangent).normalize());
+
+    let normal = along_tangent.cross(along_bitangent).normalize_or_zero();
+
+    // Pointing outwards whatever the handedness of the tangents
+    if normal.dot(direction) < 0. {
+        -normal
+    } else {
+        normal
+    }
+}
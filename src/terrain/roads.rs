@@ -0,0 +1,466 @@
+use std::collections::HashMap;
+
+use bevy::{
+    math::FloatExt,
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{pathfinding::astar, sampling::poisson_disk, seed::Seed};
+
+use super::{height_map::HeightMap, MapInfo};
+
+/// How high the road mesh is above the terrain, so they don't flicker.
+const ROAD_OFFSET: f32 = 0.03;
+/// How high roads stay above the sea, crossing it on a causeway.
+const SEA_CLEARANCE: f32 = 0.1;
+/// Settlements need ground at least this flat.
+const MIN_SETTLEMENT_NORMAL_Y: f32 = 0.9;
+
+/// Moves on the search grid, in order around a circle so turns are differences of indices.
+const DIRECTIONS: [IVec2; 8] = [
+    IVec2::new(1, 0),
+    IVec2::new(1, 1),
+    IVec2::new(0, 1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, 0),
+    IVec2::new(-1, -1),
+    IVec2::new(0, -1),
+    IVec2::new(1, -1),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoadSettings {
+    /// Settlements connected by roads.
+    pub settlements: usize,
+    /// Width of the flat part of the road.
+    pub width: f32,
+    /// Distance beyond the road over which the terrain blends back to its own height.
+    pub falloff: f32,
+    /// Side of the cells of the grid roads are searched on, in world units.
+    pub cell_size: f32,
+    /// Extra cost per unit of length, for each unit of slope squared.
+    pub slope_cost: f32,
+    /// Extra cost per unit of length under the sea.
+    pub water_cost: f32,
+    /// Extra cost of each 45° turn, squared for sharper turns.
+    pub curvature_cost: f32,
+    /// Distance between the control points of the smoothed road.
+    pub spline_spacing: f32,
+}
+
+impl Default for RoadSettings {
+    fn default() -> Self {
+        RoadSettings {
+            settlements: 5,
+            width: 1.,
+            falloff: 1.5,
+            cell_size: 0.5,
+            slope_cost: 40.,
+            water_cost: 20.,
+            curvature_cost: 0.5,
+            spline_spacing: 2.,
+        }
+    }
+}
+
+/// Smoothed road between two settlements, spawned with its mesh as a child of the terrain chunk.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct Road {
+    /// Points along the middle of the road, at the height the terrain is flattened to.
+    pub points: Vec<Vec3>,
+    pub width: f32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RoadNetwork {
+    pub settlements: Vec<Vec2>,
+    pub roads: Vec<Road>,
+}
+
+/// Picks places for settlements and connects them with roads that follow the terrain.
+pub fn plan_roads(
+    height_map: &HeightMap,
+    map_info: &MapInfo,
+    settings: &RoadSettings,
+    seed: Seed,
+) -> RoadNetwork {
+    let settlements = settlement_sites(height_map, map_info, settings, seed);
+    let grid = SearchGrid::new(height_map, map_info.sea_level, settings);
+
+    let roads = connections(&settlements)
+        .into_iter()
+        .filter_map(|(a, b)| grid.find_path(settlements[a], settlements[b]))
+        .filter(|path| path.len() > 1)
+        .map(|path| Road {
+            points: graded(&smooth(&path, settings), height_map, map_info.sea_level),
+            width: settings.width,
+        })
+        .collect();
+
+    RoadNetwork { settlements, roads }
+}
+
+/// Flat places above the sea, spread over the map.
+fn settlement_sites(
+    height_map: &HeightMap,
+    map_info: &MapInfo,
+    settings: &RoadSettings,
+    seed: Seed,
+) -> Vec<Vec2> {
+    if settings.settlements == 0 {
+        return Vec::new();
+    }
+
+    let mut rng = seed.rng();
+    let margin = settings.width + settings.falloff;
+    let region = Rect::from_center_size(
+        Vec2::ZERO,
+        Vec2::splat((map_info.size - margin * 2.).max(0.)),
+    );
+    let spacing = map_info.size / (settings.settlements as f32).sqrt() / 2.;
+    let last = (height_map.samples - 1) as f32;
+
+    let mut sites: Vec<Vec2> = poisson_disk(region, spacing, &mut rng)
+        .into_iter()
+        .filter(|site| {
+            let sample = height_map
+                .sample_coordinates(site.x, site.y)
+                .round()
+                .clamp(Vec2::ZERO, Vec2::splat(last));
+
+            height_map.height_at(site.x, site.y) > map_info.sea_level
+                && height_map.normal(sample.x as usize, sample.y as usize).y
+                    >= MIN_SETTLEMENT_NORMAL_Y
+        })
+        .collect();
+
+    sites.shuffle(&mut rng);
+    sites.truncate(settings.settlements);
+    sites
+}
+
+/// Pairs of sites joined by the shortest roads that connect all of them, with Prim's algorithm.
+fn connections(sites: &[Vec2]) -> Vec<(usize, usize)> {
+    let mut connected = vec![false; sites.len()];
+    let mut pairs = Vec::with_capacity(sites.len().saturating_sub(1));
+
+    if let Some(first) = connected.first_mut() {
+        *first = true;
+    }
+
+    while pairs.len() + 1 < sites.len() {
+        let closest = (0..sites.len())
+            .filter(|&from| connected[from])
+            .flat_map(|from| {
+                (0..sites.len())
+                    .filter(|&to| !connected[to])
+                    .map(move |to| (from, to))
+            })
+            .min_by(|&(a, b), &(c, d)| {
+                sites[a]
+                    .distance_squared(sites[b])
+                    .total_cmp(&sites[c].distance_squared(sites[d]))
+            });
+
+        let Some((from, to)) = closest else {
+            break;
+        };
+
+        connected[to] = true;
+        pairs.push((from, to));
+    }
+
+    pairs
+}
+
+/// Coarser grid over the samples of a height map, where roads are searched.
+struct SearchGrid<'a> {
+    height_map: &'a HeightMap,
+    sea_level: f32,
+    settings: &'a RoadSettings,
+    /// Samples between two cells.
+    step: usize,
+    /// Cells along each side.
+    cells: u32,
+}
+
+impl<'a> SearchGrid<'a> {
+    fn new(height_map: &'a HeightMap, sea_level: f32, settings: &'a RoadSettings) -> Self {
+        let step = (settings.cell_size / height_map.unit_size())
+            .round()
+            .max(1.) as usize;
+
+        SearchGrid {
+            height_map,
+            sea_level,
+            settings,
+            step,
+            cells: ((height_map.samples - 1) / step + 1) as u32,
+        }
+    }
+
+    fn position(&self, cell: UVec2) -> Vec3 {
+        self.height_map
+            .position(cell.x as usize * self.step, cell.y as usize * self.step)
+    }
+
+    fn cell_at(&self, point: Vec2) -> UVec2 {
+        (self.height_map.sample_coordinates(point.x, point.y) / self.step as f32)
+            .round()
+            .clamp(Vec2::ZERO, Vec2::splat((self.cells - 1) as f32))
+            .as_uvec2()
+    }
+
+    /// Cost of going from `cell` to its neighbour in `direction`, after arriving at `cell` from
+    /// `previous_direction`.
+    fn cost(&self, cell: UVec2, direction: usize, previous_direction: Option<usize>) -> f32 {
+        let from = self.position(cell);
+        let to = self.position((cell.as_ivec2() + DIRECTIONS[direction]).as_uvec2());
+        let length = from.xz().distance(to.xz());
+        let slope = (to.y - from.y).abs() / length;
+
+        let mut cost = length * (1. + self.settings.slope_cost * slope * slope);
+
+        if to.y < self.sea_level {
+            cost += length * self.settings.water_cost;
+        }
+
+        if let Some(previous_direction) = previous_direction {
+            let turn = (direction + 8 - previous_direction) % 8;
+            let turn = turn.min(8 - turn) as f32;
+            cost += self.settings.curvature_cost * turn * turn;
+        }
+
+        cost
+    }
+
+    /// Cheapest path between two points on the grid, as positions on the ground. Nodes keep the
+    /// direction they were reached from, so turns can cost more.
+    fn find_path(&self, start: Vec2, goal: Vec2) -> Option<Vec<Vec2>> {
+        let (start, goal) = (self.cell_at(start), self.cell_at(goal));
+        let goal_position = self.position(goal).xz();
+        let size = IVec2::splat(self.cells as i32);
+
+        let (path, _) = astar(
+            (start, None),
+            |&(cell, _)| cell == goal,
+            |&(cell, previous_direction): &(UVec2, Option<usize>)| {
+                DIRECTIONS
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, &offset)| {
+                        let next = cell.as_ivec2() + offset;
+                        next.cmpge(IVec2::ZERO).all() && next.cmplt(size).all()
+                    })
+                    .map(move |(direction, &offset)| {
+                        let next = (cell.as_ivec2() + offset).as_uvec2();
+                        (
+                            (next, Some(direction)),
+                            self.cost(cell, direction, previous_direction),
+                        )
+                    })
+            },
+            |&(cell, _)| self.position(cell).xz().distance(goal_position),
+        )?;
+
+        Some(
+            path.into_iter()
+                .map(|(cell, _)| self.position(cell).xz())
+                .collect(),
+        )
+    }
+}
+
+/// Catmull-Rom spline through points of `path` about `spline_spacing` apart, sampled about every
+/// half road width.
+fn smooth(path: &[Vec2], settings: &RoadSettings) -> Vec<Vec2> {
+    let (Some(&first), Some(&last)) = (path.first(), path.last()) else {
+        return Vec::new();
+    };
+
+    let mut controls = vec![first];
+    let mut travelled = 0.;
+    for segment in path.windows(2) {
+        travelled += segment[0].distance(segment[1]);
+
+        if travelled >= settings.spline_spacing {
+            controls.push(segment[1]);
+            travelled = 0.;
+        }
+    }
+
+    // The end is always a control point, replacing the last one if it's too close
+    if controls.len() > 1 && travelled < settings.spline_spacing / 2. {
+        controls.pop();
+    }
+    controls.push(last);
+
+    if controls.len() < 3 {
+        return controls;
+    }
+
+    let length: f32 = controls
+        .windows(2)
+        .map(|segment| segment[0].distance(segment[1]))
+        .sum();
+    let subdivisions = (length / (settings.width / 2.).max(0.1)).ceil() as usize;
+
+    CubicCardinalSpline::new_catmull_rom(controls)
+        .to_curve()
+        .iter_positions(subdivisions)
+        .collect()
+}
+
+/// Heights of the terrain under `points`, averaged along the road so it goes up and down
+/// gently, and kept above the sea.
+fn graded(points: &[Vec2], height_map: &HeightMap, sea_level: f32) -> Vec<Vec3> {
+    let heights: Vec<f32> = points
+        .iter()
+        .map(|point| height_map.height_at(point.x, point.y))
+        .collect();
+    let window = 3;
+
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, point)| {
+            let around = &heights[i.saturating_sub(window)..(i + window + 1).min(heights.len())];
+            let height = around.iter().sum::<f32>() / around.len() as f32;
+
+            Vec3::new(point.x, height.max(sea_level + SEA_CLEARANCE), point.y)
+        })
+        .collect()
+}
+
+/// Flattens the terrain under `road` to its height, blending back to the terrain over `falloff`.
+pub fn carve_road(height_map: &mut HeightMap, road: &Road, falloff: f32) {
+    let half_width = road.width / 2.;
+    let reach = half_width + falloff;
+    let last = (height_map.samples - 1) as f32;
+
+    // Distance from each sample to the closest point of the road, and the height there
+    let mut closest: HashMap<(usize, usize), (f32, f32)> = HashMap::new();
+
+    for segment in road.points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let min = height_map
+            .sample_coordinates(a.x.min(b.x) - reach, a.z.min(b.z) - reach)
+            .floor()
+            .clamp(Vec2::ZERO, Vec2::splat(last));
+        let max = height_map
+            .sample_coordinates(a.x.max(b.x) + reach, a.z.max(b.z) + reach)
+            .ceil()
+            .clamp(Vec2::ZERO, Vec2::splat(last));
+        let along = b.xz() - a.xz();
+
+        for x in min.x as usize..=max.x as usize {
+            for z in min.y as usize..=max.y as usize {
+                let point = height_map.position(x, z).xz();
+                let t = if along == Vec2::ZERO {
+                    0.
+                } else {
+                    ((point - a.xz()).dot(along) / along.length_squared()).clamp(0., 1.)
+                };
+                let on_road = a.lerp(b, t);
+                let distance = on_road.xz().distance(point);
+
+                if distance >= reach {
+                    continue;
+                }
+
+                closest
+                    .entry((x, z))
+                    .and_modify(|best| {
+                        if distance < best.0 {
+                            *best = (distance, on_road.y);
+                        }
+                    })
+                    .or_insert((distance, on_road.y));
+            }
+        }
+    }
+
+    for ((x, z), (distance, road_height)) in closest {
+        let t = ((distance - half_width) / falloff.max(f32::EPSILON)).clamp(0., 1.);
+        let blend = 1. - t * t * (3. - 2. * t);
+        let height = &mut height_map.height_map[x][z];
+
+        *height = height.lerp(road_height, blend);
+    }
+}
+
+impl Road {
+    /// Strip along the road, lying on the terrain of `height_map`.
+    pub fn mesh(&self, height_map: &HeightMap) -> Mesh {
+        let last = self.points.len().saturating_sub(1);
+        let mut positions = Vec::with_capacity(self.points.len() * 2);
+        let mut uvs = Vec::with_capacity(positions.capacity());
+        let mut travelled = 0.;
+
+        for (i, point) in self.points.iter().enumerate() {
+            let tangent = (self.points[(i + 1).min(last)] - self.points[i.saturating_sub(1)])
+                .xz()
+                .normalize_or_zero();
+            let side = tangent.perp() * self.width / 2.;
+
+            if i > 0 {
+                travelled += point.xz().distance(self.points[i - 1].xz());
+            }
+
+            for (edge, u) in [(point.xz() - side, 0.), (point.xz() + side, 1.)] {
+                let height = height_map.height_at(edge.x, edge.y) + ROAD_OFFSET;
+
+                positions.push([edge.x, height, edge.y]);
+                uvs.push([u, travelled / self.width]);
+            }
+        }
+
+        let mut indices = Vec::with_capacity(last * 6);
+        for i in 0..last as u32 {
+            let (left, right, next_left, next_right) = (i * 2, i * 2 + 1, i * 2 + 2, i * 2 + 3);
+
+            indices.extend([left, right, next_left, next_left, right, next_right]);
+        }
+
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(Indices::U32(indices))
+        .with_computed_normals()
+    }
+}
+
+/// Spawns the mesh of every road as children of a terrain chunk.
+pub(super) fn spawn_roads(
+    parent: &mut ChildBuilder,
+    height_map: &HeightMap,
+    network: &RoadNetwork,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    if network.roads.is_empty() {
+        return;
+    }
+
+    let material = materials.add(StandardMaterial {
+        base_color: Color::srgb(0.45, 0.38, 0.3),
+        perceptual_roughness: 1.,
+        ..default()
+    });
+
+    for road in &network.roads {
+        parent
+            .spawn(PbrBundle {
+                mesh: meshes.add(road.mesh(height_map)),
+                material: material.clone(),
+                ..default()
+            })
+            .insert(road.clone());
+    }
+}
@@ -0,0 +1,415 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    color::palettes::css::{DARK_OLIVEGREEN, FOREST_GREEN, GRAY, SADDLE_BROWN},
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+};
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::utils::{sampling::poisson_disk, seed::Seed};
+
+use super::{biome::Biome, creatures::CreatureKind, height_map::HeightMap, MapInfo, TerrainChunk};
+
+const TRUNK_HEIGHT: f32 = 1.;
+const TRUNK_RADIUS: f32 = 0.1;
+const CANOPY_HEIGHT: f32 = 1.6;
+const CANOPY_RADIUS: f32 = 0.6;
+const ROCK_RADIUS: f32 = 0.5;
+const GRASS_BLADES: usize = 5;
+const GRASS_HEIGHT: f32 = 0.4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PropKind {
+    Tree,
+    Rock,
+    GrassTuft,
+}
+
+impl PropKind {
+    /// How much of the prop is below the terrain, as a fraction of its scale, so it doesn't float
+    /// on slopes.
+    fn sink(self) -> f32 {
+        match self {
+            PropKind::Tree => 0.05,
+            PropKind::Rock => 0.3,
+            PropKind::GrassTuft => 0.02,
+        }
+    }
+
+    fn scale(self, scale: f32) -> Vec3 {
+        match self {
+            // Flattened a bit so they look less like balls
+            PropKind::Rock => Vec3::new(1.2, 0.7, 1.) * scale,
+            PropKind::Tree | PropKind::GrassTuft => Vec3::splat(scale),
+        }
+    }
+}
+
+/// Prop scattered on a terrain chunk, spawned as its child.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Prop {
+    pub kind: PropKind,
+}
+
+/// Where and how densely a kind of prop, or of anything else placed on the ground, is placed.
+#[derive(Clone, Debug)]
+pub struct ScatterRule<K = PropKind> {
+    pub kind: K,
+    pub biomes: Vec<Biome>,
+    pub min_height: f32,
+    pub max_height: f32,
+    /// Surfaces whose normal points less upwards than this are too steep.
+    pub min_normal_y: f32,
+    /// Minimum distance between two props of this rule.
+    pub spacing: f32,
+    /// Fraction of the evenly spaced candidates that are kept, between 0 and 1.
+    pub density: f32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+}
+
+impl<K> ScatterRule<K> {
+    fn accepts(&self, height: f32, normal: Vec3, biome: Biome) -> bool {
+        self.biomes.contains(&biome)
+            && (self.min_height..=self.max_height).contains(&height)
+            && normal.y >= self.min_normal_y
+    }
+
+    /// Points of the ground where the rule places something.
+    ///
+    /// Every candidate point draws its random values whether it's kept or not, so editing the
+    /// terrain only changes the placements where it was edited.
+    pub(super) fn placements(
+        &self,
+        height_map: &HeightMap,
+        map_info: &MapInfo,
+        seed: Seed,
+    ) -> Vec<Placement> {
+        let mut rng = seed.rng();
+        let last = (height_map.samples - 1) as f32;
+        let region = Rect::from_center_size(Vec2::ZERO, Vec2::splat(map_info.size));
+        let mut placements = Vec::new();
+
+        for point in poisson_disk(region, self.spacing, &mut rng) {
+            let keep = rng.gen::<f32>() < self.density;
+            let yaw = rng.gen::<f32>() * TAU;
+            let scale = rng.gen_range(self.min_scale..=self.max_scale);
+
+            if !keep || !height_map.has_ground(point.x, point.y) {
+                continue;
+            }
+
+            let height = height_map.height_at(point.x, point.y);
+            let sample = height_map
+                .sample_coordinates(point.x, point.y)
+                .round()
+                .clamp(Vec2::ZERO, Vec2::splat(last));
+            let normal = height_map.normal(sample.x as usize, sample.y as usize);
+            let biome = Biome::classify(height, normal, map_info.sea_level, map_info.max_depth);
+
+            if self.accepts(height, normal, biome) {
+                placements.push(Placement {
+                    position: Vec3::new(point.x, height, point.y),
+                    yaw,
+                    scale,
+                });
+            }
+        }
+
+        placements
+    }
+}
+
+/// Point of the ground picked by a `ScatterRule`, with a random rotation and scale.
+pub(super) struct Placement {
+    pub(super) position: Vec3,
+    pub(super) yaw: f32,
+    pub(super) scale: f32,
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct ScatterConfig {
+    pub enabled: bool,
+    pub rules: Vec<ScatterRule>,
+    pub creatures: Vec<ScatterRule<CreatureKind>>,
+}
+
+impl Default for ScatterConfig {
+    fn default() -> Self {
+        ScatterConfig {
+            enabled: true,
+            rules: vec![
+                ScatterRule {
+                    kind: PropKind::Tree,
+                    biomes: vec![Biome::Forest],
+                    min_height: f32::MIN,
+                    max_height: f32::MAX,
+                    min_normal_y: 0.85,
+                    spacing: 2.,
+                    density: 0.8,
+                    min_scale: 0.7,
+                    max_scale: 1.3,
+                },
+                ScatterRule {
+                    kind: PropKind::Tree,
+                    biomes: vec![Biome::Grassland],
+                    min_height: f32::MIN,
+                    max_height: f32::MAX,
+                    min_normal_y: 0.9,
+                    spacing: 3.,
+                    density: 0.1,
+                    min_scale: 0.6,
+                    max_scale: 1.,
+                },
+                ScatterRule {
+                    kind: PropKind::Rock,
+                    biomes: vec![Biome::Beach, Biome::Grassland, Biome::Mountain],
+                    min_height: f32::MIN,
+                    max_height: f32::MAX,
+                    min_normal_y: 0.5,
+                    spacing: 4.,
+                    density: 0.3,
+                    min_scale: 0.2,
+                    max_scale: 0.7,
+                },
+                ScatterRule {
+                    kind: PropKind::GrassTuft,
+                    biomes: vec![Biome::Grassland, Biome::Forest],
+                    min_height: f32::MIN,
+                    max_height: f32::MAX,
+                    min_normal_y: 0.8,
+                    spacing: 0.9,
+                    density: 0.6,
+                    min_scale: 0.6,
+                    max_scale: 1.,
+                },
+            ],
+            creatures: vec![
+                ScatterRule {
+                    kind: CreatureKind::Rabbit,
+                    biomes: vec![Biome::Beach, Biome::Grassland],
+                    min_height: f32::MIN,
+                    max_height: f32::MAX,
+                    min_normal_y: 0.85,
+                    spacing: 6.,
+                    density: 0.3,
+                    min_scale: 0.8,
+                    max_scale: 1.2,
+                },
+                ScatterRule {
+                    kind: CreatureKind::Deer,
+                    biomes: vec![Biome::Grassland, Biome::Forest],
+                    min_height: f32::MIN,
+                    max_height: f32::MAX,
+                    min_normal_y: 0.85,
+                    spacing: 10.,
+                    density: 0.3,
+                    min_scale: 0.9,
+                    max_scale: 1.1,
+                },
+                ScatterRule {
+                    kind: CreatureKind::Goat,
+                    biomes: vec![Biome::Mountain],
+                    min_height: f32::MIN,
+                    max_height: f32::MAX,
+                    min_normal_y: 0.6,
+                    spacing: 10.,
+                    density: 0.4,
+                    min_scale: 0.9,
+                    max_scale: 1.1,
+                },
+            ],
+        }
+    }
+}
+
+/// Meshes and materials shared by every prop of a kind, so they are drawn in batches.
+#[derive(Resource)]
+pub struct PropAssets {
+    trunk: Handle<Mesh>,
+    canopy: Handle<Mesh>,
+    rock: Handle<Mesh>,
+    grass: Handle<Mesh>,
+    bark_material: Handle<StandardMaterial>,
+    leaves_material: Handle<StandardMaterial>,
+    rock_material: Handle<StandardMaterial>,
+    grass_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for PropAssets {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let trunk = meshes.add(Cylinder::new(TRUNK_RADIUS, TRUNK_HEIGHT));
+        let canopy = meshes.add(Cone {
+            radius: CANOPY_RADIUS,
+            height: CANOPY_HEIGHT,
+        });
+        let rock = meshes.add(
+            Sphere::new(ROCK_RADIUS)
+                .mesh()
+                .ico(1)
+                .expect("Too many subdivisions"),
+        );
+        let grass = meshes.add(grass_tuft_mesh());
+
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let bark_material = materials.add(Color::from(SADDLE_BROWN));
+        let leaves_material = materials.add(Color::from(FOREST_GREEN));
+        let rock_material = materials.add(StandardMaterial {
+            base_color: Color::from(GRAY),
+            perceptual_roughness: 1.,
+            ..default()
+        });
+        let grass_material = materials.add(StandardMaterial {
+            base_color: Color::from(DARK_OLIVEGREEN),
+            // Blades are single triangles, seen from both sides
+            cull_mode: None,
+            double_sided: true,
+            ..default()
+        });
+
+        PropAssets {
+            trunk,
+            canopy,
+            rock,
+            grass,
+            bark_material,
+            leaves_material,
+            rock_material,
+            grass_material,
+        }
+    }
+}
+
+/// A few thin triangles around the origin, leaning outwards.
+fn grass_tuft_mesh() -> Mesh {
+    let mut positions = Vec::with_capacity(GRASS_BLADES * 3);
+
+    for blade in 0..GRASS_BLADES {
+        let angle = blade as f32 / GRASS_BLADES as f32 * TAU;
+        let direction = Vec3::new(angle.cos(), 0., angle.sin());
+        let side = direction.cross(Vec3::Y) * 0.03;
+        let tip = direction * 0.12 + Vec3::Y * GRASS_HEIGHT * (0.8 + 0.2 * (blade % 2) as f32);
+
+        positions.extend([(-side).to_array(), side.to_array(), tip.to_array()]);
+    }
+
+    let vertices = positions.len();
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    // Lit like the ground under them
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 1., 0.]; vertices])
+    .with_inserted_indices(Indices::U32((0..vertices as u32).collect()))
+}
+
+/// Places the props of every rule on the terrain, as children of its chunk.
+pub(super) fn spawn_props(
+    parent: &mut ChildBuilder,
+    height_map: &HeightMap,
+    map_info: &MapInfo,
+    config: &ScatterConfig,
+    seed: Seed,
+    assets: &PropAssets,
+) {
+    if !config.enabled {
+        return;
+    }
+
+    for (index, rule) in config.rules.iter().enumerate() {
+        for placement in rule.placements(height_map, map_info, seed.derive_index(index as u64)) {
+            let scale = rule.kind.scale(placement.scale);
+            let mut position = placement.position;
+            position.y -= rule.kind.sink() * scale.y;
+
+            let transform = Transform::from_translation(position)
+                .with_rotation(Quat::from_rotation_y(placement.yaw))
+                .with_scale(scale);
+
+            spawn_prop(parent, rule.kind, transform, assets);
+        }
+    }
+}
+
+fn spawn_prop(
+    parent: &mut ChildBuilder,
+    kind: PropKind,
+    transform: Transform,
+    assets: &PropAssets,
+) {
+    match kind {
+        PropKind::Tree => {
+            parent
+                .spawn(SpatialBundle::from_transform(transform))
+                .insert(Prop { kind })
+                .with_children(|tree| {
+                    tree.spawn(PbrBundle {
+                        mesh: assets.trunk.clone(),
+                        material: assets.bark_material.clone(),
+                        transform: Transform::from_xyz(0., TRUNK_HEIGHT / 2., 0.),
+                        ..default()
+                    })
+                    .insert(Collider::cylinder(TRUNK_HEIGHT / 2., TRUNK_RADIUS));
+
+                    tree.spawn(PbrBundle {
+                        mesh: assets.canopy.clone(),
+                        material: assets.leaves_material.clone(),
+                        transform: Transform::from_xyz(
+                            0.,
+                            TRUNK_HEIGHT * 0.8 + CANOPY_HEIGHT / 2.,
+                            0.,
+                        ),
+                        ..default()
+                    });
+                });
+        }
+        PropKind::Rock => {
+            parent
+                .spawn(PbrBundle {
+                    mesh: assets.rock.clone(),
+                    material: assets.rock_material.clone(),
+                    transform,
+                    ..default()
+                })
+                .insert((Prop { kind }, Collider::ball(ROCK_RADIUS)));
+        }
+        PropKind::GrassTuft => {
+            parent
+                .spawn(PbrBundle {
+                    mesh: assets.grass.clone(),
+                    material: assets.grass_material.clone(),
+                    transform,
+                    ..default()
+                })
+                .insert(Prop { kind });
+        }
+    }
+}
+
+/// Keeps the props on the ground while the terrain under them is edited.
+pub(super) fn snap_props(
+    chunk_q: Query<(Ref<HeightMap>, &Children), With<TerrainChunk>>,
+    mut prop_q: Query<(&Prop, &mut Transform)>,
+) {
+    for (height_map, children) in &chunk_q {
+        if !height_map.is_changed() || height_map.is_added() {
+            continue;
+        }
+
+        let mut props = prop_q.iter_many_mut(children);
+        while let Some((prop, mut transform)) = props.fetch_next() {
+            let height = height_map.height_at(transform.translation.x, transform.translation.z);
+            let sunk = height - prop.kind.sink() * transform.scale.y;
+
+            // Avoid marking every prop as changed on each edit
+            if transform.translation.y != sunk {
+                transform.translation.y = sunk;
+            }
+        }
+    }
+}
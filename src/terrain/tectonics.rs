@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{
+    noise::{cellular::Cellular, perlin::Perlin, Noise},
+    seed::Seed,
+};
+
+use super::{
+    height_map::{HeightMap, NOISE_EXTENT},
+    MapInfo,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TectonicSettings {
+    /// Plates along each side of the map.
+    pub plates: u32,
+    /// Chance of a plate being under the sea.
+    pub ocean_ratio: f32,
+    /// Elevation raised where plates collide, relative to the height range.
+    pub mountain_height: f32,
+    /// Elevation lowered where plates move apart, relative to the height range.
+    pub rift_depth: f32,
+    /// How far mountains and rifts reach from the boundaries, in plates.
+    pub boundary_width: f32,
+    /// Amplitude of the fractal noise added on top, relative to the height range.
+    pub detail: f32,
+}
+
+impl Default for TectonicSettings {
+    fn default() -> Self {
+        TectonicSettings {
+            plates: 4,
+            ocean_ratio: 0.4,
+            mountain_height: 0.45,
+            rift_depth: 0.2,
+            boundary_width: 0.2,
+            detail: 0.12,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlateKind {
+    /// Thin and low, sinks under continental plates when they collide.
+    Oceanic,
+    Continental,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plate {
+    pub kind: PlateKind,
+    /// Direction and speed of the plate, at most 1.
+    pub velocity: Vec2,
+    /// Elevation of the plate away from its boundaries, between 0 and 1.
+    pub elevation: f32,
+}
+
+impl Plate {
+    fn random(ocean_ratio: f32, seed: Seed) -> Self {
+        let mut rng = seed.rng();
+        let kind = if rng.gen::<f32>() < ocean_ratio {
+            PlateKind::Oceanic
+        } else {
+            PlateKind::Continental
+        };
+        let elevation = match kind {
+            PlateKind::Oceanic => rng.gen_range(0.1..0.25),
+            PlateKind::Continental => rng.gen_range(0.5..0.6),
+        };
+
+        Plate {
+            kind,
+            velocity: Vec2::from_angle(rng.gen_range(0. ..std::f32::consts::TAU))
+                * rng.gen_range(0.2..1.),
+            elevation,
+        }
+    }
+}
+
+/// Base elevation of continents from tectonic plates, between 0 and 1.
+///
+/// Plates are the cells of a `Cellular` noise, each moving in its own direction. Mountains rise
+/// where they collide and rifts open where they move apart. Input coordinates are the ones
+/// `HeightMap::new` samples with.
+pub struct Tectonics {
+    settings: TectonicSettings,
+    cells: Cellular<(f32, f32)>,
+    plates: Vec<Plate>,
+}
+
+impl Tectonics {
+    pub fn new(settings: TectonicSettings, seed: Seed) -> Self {
+        let settings = TectonicSettings {
+            plates: settings.plates.max(1),
+            ..settings
+        };
+        let cells = Cellular::new(
+            settings.plates as u64,
+            settings.plates as u64,
+            Some(seed.derive("cells").value()),
+        );
+        let plates = (0..cells.len())
+            .map(|index| Plate::random(settings.ocean_ratio, seed.derive_index(index as u64)))
+            .collect();
+
+        Tectonics {
+            settings,
+            cells,
+            plates,
+        }
+    }
+
+    pub fn settings(&self) -> &TectonicSettings {
+        &self.settings
+    }
+
+    /// Plate at the given coordinates.
+    pub fn plate_at(&self, input: (f32, f32)) -> &Plate {
+        let [closest, ..] = self.cells.closest_points(self.cell_coordinates(input));
+        &self.plates[closest.index]
+    }
+
+    fn cell_coordinates(&self, input: (f32, f32)) -> (f32, f32) {
+        let scale = self.settings.plates as f32 / NOISE_EXTENT;
+        (input.0 * scale, input.1 * scale)
+    }
+
+    /// Uplift along the boundary between two plates, negative where they move apart. Each
+    /// weight goes from 0 away from the boundary to 1 inside the plate.
+    fn boundary_uplift(
+        &self,
+        (a, a_weight): (&Plate, f32),
+        (b, b_weight): (&Plate, f32),
+        towards_b: Vec2,
+    ) -> f32 {
+        let TectonicSettings {
+            mountain_height,
+            rift_depth,
+            ..
+        } = self.settings;
+        // Positive when the plates move towards each other
+        let convergence = (a.velocity - b.velocity).dot(towards_b) / 2.;
+        let influence = a_weight * b_weight;
+
+        if convergence < 0. {
+            return rift_depth * convergence * influence;
+        }
+
+        let uplift = match (a.kind, b.kind) {
+            // The ocean floor sinks under the continent, leaving a trench before the mountains
+            (PlateKind::Oceanic, PlateKind::Continental) => {
+                subduction(mountain_height, rift_depth, a_weight, b_weight)
+            }
+            (PlateKind::Continental, PlateKind::Oceanic) => {
+                subduction(mountain_height, rift_depth, b_weight, a_weight)
+            }
+            // Island arcs are lower than continental ranges
+            (PlateKind::Oceanic, PlateKind::Oceanic) => mountain_height / 2. * influence,
+            (PlateKind::Continental, PlateKind::Continental) => mountain_height * influence,
+        };
+
+        uplift * convergence
+    }
+}
+
+/// Mountains on the continental side of the boundary, and a trench in the ocean before them.
+fn subduction(
+    mountain_height: f32,
+    trench_depth: f32,
+    ocean_weight: f32,
+    continent_weight: f32,
+) -> f32 {
+    ocean_weight
+        * (mountain_height * continent_weight.powi(4)
+            - trench_depth * 4. * continent_weight * (1. - continent_weight))
+}
+
+impl Noise for Tectonics {
+    type Input = (f32, f32);
+    type Output = f32;
+
+    fn get(&self, input: (f32, f32)) -> f32 {
+        let points = self.cells.closest_points(self.cell_coordinates(input));
+        let width = self.settings.boundary_width.max(f32::EPSILON);
+
+        // 1 for the closest plate, down to 0 for the ones farther than it by twice the width.
+        // Only depends on distances, so nothing jumps where the closest plates change
+        let weights = points.map(|point| {
+            (1. - (point.distance - points[0].distance) / (2. * width))
+                .max(0.)
+                .powi(2)
+        });
+        let near = points.len()
+            - weights
+                .iter()
+                .rev()
+                .take_while(|&&weight| weight == 0.)
+                .count();
+
+        let mut elevation = 0.;
+        for (point, weight) in points[..near].iter().zip(weights) {
+            elevation += self.plates[point.index].elevation * weight;
+        }
+        // Plates meet halfway between their elevations, so there are no cliffs
+        elevation /= weights[..near].iter().sum::<f32>();
+
+        for i in 0..near {
+            for j in i + 1..near {
+                let offset = Vec2::from(points[j].position) - Vec2::from(points[i].position);
+                let Some(towards_j) = offset.try_normalize() else {
+                    continue;
+                };
+
+                elevation += self.boundary_uplift(
+                    (&self.plates[points[i].index], weights[i]),
+                    (&self.plates[points[j].index], weights[j]),
+                    towards_j,
+                );
+            }
+        }
+
+        elevation.clamp(0., 1.)
+    }
+}
+
+/// Height map of continents shaped by tectonic plates, with fractal noise for the details.
+pub fn continents(map_info: &MapInfo, settings: &TectonicSettings, seed: Seed) -> HeightMap {
+    let range = map_info.max_depth - map_info.min_depth;
+    let mut height_map = HeightMap::new(
+        map_info.size,
+        map_info.samples,
+        map_info.min_depth,
+        map_info.max_depth,
+        Tectonics::new(*settings, seed.derive("plates")),
+    );
+
+    height_map.add_noise(
+        &Perlin::new(
+            &[(0.5, 1.), (0.25, 2.), (0.125, 4.), (0.075, 8.)],
+            256,
+            Some(seed.derive("detail").value()),
+        ),
+        settings.detail * range,
+    );
+
+    height_map
+}
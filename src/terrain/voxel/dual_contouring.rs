@@ -0,0 +1,154 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+    utils::HashMap,
+};
+
+use super::DensityGrid;
+
+/// Keeps the QEF solution close to the mass point when the crossing planes are nearly parallel.
+const QEF_REGULARIZATION: f32 = 0.05;
+
+/// Where the vertex of each cell crossed by the surface is placed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VertexPlacement {
+    /// Average of the edge crossings (surface nets). Smooth, rounds off edges.
+    MassPoint,
+    /// Closest point to the tangent planes at the edge crossings (dual contouring).
+    /// Keeps sharp edges and corners.
+    Qef,
+}
+
+/// Unit steps along each axis.
+const AXES: [UVec3; 3] = [UVec3::X, UVec3::Y, UVec3::Z];
+
+/// Meshes the surface with one vertex per crossed cell and one quad per crossed edge.
+///
+/// Quads need the four cells around their edge, and the gradients at the corners of those cells
+/// need the samples around them, so the grid must have two samples of padding on every side. Only
+/// edges starting inside the padding are meshed, and the ones on the last samples only along the
+/// axes where `last_chunk` is true, since the next chunk meshes them otherwise. This lets
+/// neighbouring chunks with overlapping padded grids join without gaps or duplicated faces.
+pub fn generate(grid: &DensityGrid, placement: VertexPlacement, last_chunk: BVec3) -> Mesh {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut indices = Vec::new();
+    let mut cell_vertices: HashMap<UVec3, u32> = HashMap::new();
+
+    let mut vertex_index = |cell: UVec3| {
+        *cell_vertices.entry(cell).or_insert_with(|| {
+            let (position, normal) = cell_vertex(grid, cell, placement);
+            positions.push(position);
+            normals.push(normal);
+
+            positions.len() as u32 - 1
+        })
+    };
+
+    // Past the last sample meshed along each axis
+    let end = grid.dimensions - UVec3::select(last_chunk, UVec3::splat(2), UVec3::splat(3));
+
+    for z in 2..end.z {
+        for y in 2..end.y {
+            for x in 2..end.x {
+                let sample = UVec3::new(x, y, z);
+                let solid = grid.get(sample) > 0.;
+
+                for axis in 0..3 {
+                    let next = sample + AXES[axis];
+
+                    if (grid.get(next) > 0.) == solid {
+                        continue;
+                    }
+
+                    // The other two axes, in an order such that b x c = axis
+                    let b = AXES[(axis + 1) % 3];
+                    let c = AXES[(axis + 2) % 3];
+                    let mut quad = [
+                        vertex_index(sample - b - c),
+                        vertex_index(sample - c),
+                        vertex_index(sample),
+                        vertex_index(sample - b),
+                    ];
+
+                    // Face towards the empty side
+                    if !solid {
+                        quad.reverse();
+                    }
+
+                    indices.extend([quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+                }
+            }
+        }
+    }
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+/// Position and normal of the vertex of a cell, from the points where the surface crosses its edges.
+fn cell_vertex(grid: &DensityGrid, cell: UVec3, placement: VertexPlacement) -> (Vec3, Vec3) {
+    let mut crossings = Vec::with_capacity(12);
+
+    for axis in 0..3 {
+        for i in 0..4 {
+            let offset = AXES[(axis + 1) % 3] * (i & 1) + AXES[(axis + 2) % 3] * (i >> 1);
+            let start = cell + offset;
+            let end = start + AXES[axis];
+            let (density_start, density_end) = (grid.get(start), grid.get(end));
+
+            if (density_start > 0.) == (density_end > 0.) {
+                continue;
+            }
+
+            let t = density_start / (density_start - density_end);
+            let position = grid.position(start).lerp(grid.position(end), t);
+            // Density grows towards the inside
+            let normal = -grid
+                .gradient(start)
+                .lerp(grid.gradient(end), t)
+                .normalize_or_zero();
+
+            crossings.push((position, normal));
+        }
+    }
+
+    let mass_point = crossings
+        .iter()
+        .map(|(position, _)| *position)
+        .sum::<Vec3>()
+        / crossings.len() as f32;
+    let normal = crossings
+        .iter()
+        .map(|(_, normal)| *normal)
+        .sum::<Vec3>()
+        .normalize_or_zero();
+
+    let position = match placement {
+        VertexPlacement::MassPoint => mass_point,
+        VertexPlacement::Qef => {
+            // Least squares of the distances to each tangent plane, solved around the mass point
+            // in cell units so the regularization doesn't depend on the cell size.
+            let mut ata = Mat3::from_diagonal(Vec3::splat(QEF_REGULARIZATION));
+            let mut atb = Vec3::ZERO;
+
+            for (position, normal) in &crossings {
+                let offset = (*position - mass_point) / grid.cell_size;
+                ata += Mat3::from_cols(*normal * normal.x, *normal * normal.y, *normal * normal.z);
+                atb += *normal * normal.dot(offset);
+            }
+
+            let cell_min = grid.position(cell);
+            let cell_max = grid.position(cell + UVec3::ONE);
+
+            (mass_point + ata.inverse() * atb * grid.cell_size).clamp(cell_min, cell_max)
+        }
+    };
+
+    (position, normal)
+}
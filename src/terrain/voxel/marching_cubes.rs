@@ -0,0 +1,211 @@
+use std::sync::OnceLock;
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+    utils::HashMap,
+};
+
+use super::DensityGrid;
+
+/// Corners of the unit cube, indexed by `x + 2 * y + 4 * z`.
+const CORNERS: [UVec3; 8] = [
+    UVec3::new(0, 0, 0),
+    UVec3::new(1, 0, 0),
+    UVec3::new(0, 1, 0),
+    UVec3::new(1, 1, 0),
+    UVec3::new(0, 0, 1),
+    UVec3::new(1, 0, 1),
+    UVec3::new(0, 1, 1),
+    UVec3::new(1, 1, 1),
+];
+
+/// Corners joined by each edge of the cube, the lower corner first. Grouped by axis: x, y, z.
+const EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+/// Corners of each face of the cube, in order around the face.
+const FACES: [[usize; 4]; 6] = [
+    [0, 1, 3, 2],
+    [4, 5, 7, 6],
+    [0, 1, 5, 4],
+    [2, 3, 7, 6],
+    [0, 2, 6, 4],
+    [1, 3, 7, 5],
+];
+
+static TRIANGLE_TABLE: OnceLock<Vec<Vec<[usize; 3]>>> = OnceLock::new();
+
+/// Triangles, as edges of the cube, for each of the 256 combinations of solid corners.
+fn triangle_table() -> &'static [Vec<[usize; 3]>] {
+    TRIANGLE_TABLE.get_or_init(|| (0..256).map(triangulate_case).collect())
+}
+
+fn edge_between(a: usize, b: usize) -> usize {
+    EDGES
+        .iter()
+        .position(|&edge| edge == (a, b) || edge == (b, a))
+        .expect("Corners are not joined by an edge")
+}
+
+/// Builds the triangles of a single case instead of relying on the usual hardcoded table.
+///
+/// The surface crosses every edge between a solid and an empty corner. On each face those
+/// crossings are joined in pairs, cutting off each run of solid corners on its own (this resolves
+/// ambiguous faces the same way from both cubes sharing it, keeping the surface watertight).
+/// Following the joins gives closed polygons that are then triangulated as fans.
+fn triangulate_case(case: usize) -> Vec<[usize; 3]> {
+    let solid = |corner: usize| case & (1 << corner) != 0;
+    let mut links: [Vec<usize>; 12] = Default::default();
+
+    for face in FACES {
+        for k in 0..4 {
+            let previous = face[(k + 3) % 4];
+
+            // Only look at the start of each run of solid corners
+            if !solid(face[k]) || solid(previous) {
+                continue;
+            }
+
+            let mut last = k;
+            while solid(face[(last + 1) % 4]) {
+                last = (last + 1) % 4;
+            }
+
+            let entry = edge_between(previous, face[k]);
+            let exit = edge_between(face[last], face[(last + 1) % 4]);
+
+            links[entry].push(exit);
+            links[exit].push(entry);
+        }
+    }
+
+    let mut visited = [false; 12];
+    let mut triangles = Vec::new();
+
+    for start in 0..12 {
+        if visited[start] || links[start].is_empty() {
+            continue;
+        }
+
+        let mut polygon = vec![start];
+        let mut previous = start;
+        let mut current = links[start][0];
+        visited[start] = true;
+
+        while current != start {
+            visited[current] = true;
+            polygon.push(current);
+
+            let next = if links[current][0] == previous {
+                links[current][1]
+            } else {
+                links[current][0]
+            };
+            previous = current;
+            current = next;
+        }
+
+        // Orient the polygon so its normal points from the solid corners to the empty ones
+        let mut outwards = Vec3::ZERO;
+        let mut normal = Vec3::ZERO;
+
+        for (i, &edge) in polygon.iter().enumerate() {
+            let (a, b) = EDGES[edge];
+            let (inner, outer) = if solid(a) { (a, b) } else { (b, a) };
+            outwards += CORNERS[outer].as_vec3() - CORNERS[inner].as_vec3();
+
+            let current = edge_midpoint(edge);
+            let next = edge_midpoint(polygon[(i + 1) % polygon.len()]);
+            normal += current.cross(next);
+        }
+
+        if normal.dot(outwards) < 0. {
+            polygon.reverse();
+        }
+
+        for i in 1..(polygon.len() - 1) {
+            triangles.push([polygon[0], polygon[i], polygon[i + 1]]);
+        }
+    }
+
+    triangles
+}
+
+fn edge_midpoint(edge: usize) -> Vec3 {
+    let (a, b) = EDGES[edge];
+    (CORNERS[a].as_vec3() + CORNERS[b].as_vec3()) / 2.
+}
+
+/// Extracts the surface where the density crosses zero. Normals come from the density gradient.
+pub fn generate(grid: &DensityGrid) -> Mesh {
+    let table = triangle_table();
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut indices = Vec::new();
+    // Vertices are shared between neighbouring cubes, keyed by lower corner and axis of the edge
+    let mut edge_vertices: HashMap<(UVec3, usize), u32> = HashMap::new();
+
+    for z in 0..(grid.dimensions.z - 1) {
+        for y in 0..(grid.dimensions.y - 1) {
+            for x in 0..(grid.dimensions.x - 1) {
+                let cube = UVec3::new(x, y, z);
+
+                let case = CORNERS
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &corner)| grid.get(cube + corner) > 0.)
+                    .fold(0, |case, (i, _)| case | 1 << i);
+
+                for triangle in &table[case] {
+                    for &edge in triangle {
+                        let (a, b) = EDGES[edge];
+                        let (corner_a, corner_b) = (cube + CORNERS[a], cube + CORNERS[b]);
+
+                        let index =
+                            *edge_vertices
+                                .entry((corner_a, edge / 4))
+                                .or_insert_with(|| {
+                                    let (density_a, density_b) =
+                                        (grid.get(corner_a), grid.get(corner_b));
+                                    let t = density_a / (density_a - density_b);
+
+                                    let position =
+                                        grid.position(corner_a).lerp(grid.position(corner_b), t);
+                                    let gradient =
+                                        grid.gradient(corner_a).lerp(grid.gradient(corner_b), t);
+
+                                    positions.push(position);
+                                    // Density grows towards the inside
+                                    normals.push(-gradient.normalize_or_zero());
+
+                                    positions.len() as u32 - 1
+                                });
+
+                        indices.push(index);
+                    }
+                }
+            }
+        }
+    }
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_indices(Indices::U32(indices))
+}
@@ -0,0 +1,359 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{
+    noise::{perlin_3d::Perlin3D, Noise},
+    seed::Seed,
+};
+
+use super::{height_map::HeightMap, material::TerrainMaterial, MapInfo};
+use dual_contouring::VertexPlacement;
+
+pub mod dual_contouring;
+pub mod marching_cubes;
+
+/// Algorithm used to turn the density grid of each chunk into a mesh.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum VoxelMesher {
+    #[default]
+    MarchingCubes,
+    /// One vertex per cell instead of one per edge, with more regular triangles. Still rounds off
+    /// cliffs.
+    SurfaceNets,
+    /// Like surface nets, keeping sharp edges and corners.
+    DualContouring,
+}
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VoxelConfig {
+    pub cell_size: f32,
+    /// Cells along each side of a chunk.
+    pub chunk_cells: u32,
+    /// How far below `MapInfo::min_depth` the volume extends.
+    pub underground_depth: f32,
+    /// Noise value above which there are caves, between 0 and 1.
+    pub cave_threshold: f32,
+    pub cave_frequency: f32,
+    pub mesher: VoxelMesher,
+}
+
+impl Default for VoxelConfig {
+    fn default() -> Self {
+        VoxelConfig {
+            cell_size: 0.25,
+            chunk_cells: 32,
+            underground_depth: 4.,
+            cave_threshold: 0.6,
+            cave_frequency: 0.2,
+            mesher: VoxelMesher::MarchingCubes,
+        }
+    }
+}
+
+/// Marks each meshed piece of a voxel terrain, by its position in the chunk grid.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct VoxelChunk {
+    pub coordinates: UVec3,
+}
+
+/// Density samples on a regular grid. Positive values are solid, negative ones are empty.
+pub struct DensityGrid {
+    pub origin: Vec3,
+    pub cell_size: f32,
+    /// Samples along each axis.
+    pub dimensions: UVec3,
+    pub values: Vec<f32>,
+}
+
+impl DensityGrid {
+    pub fn new<T: Noise<Input = (f32, f32, f32), Output = f32>>(
+        origin: Vec3,
+        cell_size: f32,
+        dimensions: UVec3,
+        density: &T,
+    ) -> Self {
+        let mut values = Vec::with_capacity((dimensions.x * dimensions.y * dimensions.z) as usize);
+
+        for z in 0..dimensions.z {
+            for y in 0..dimensions.y {
+                for x in 0..dimensions.x {
+                    let position = origin + UVec3::new(x, y, z).as_vec3() * cell_size;
+                    values.push(density.get(position.into()));
+                }
+            }
+        }
+
+        DensityGrid {
+            origin,
+            cell_size,
+            dimensions,
+            values,
+        }
+    }
+
+    pub fn get(&self, sample: UVec3) -> f32 {
+        self.values
+            [(sample.x + self.dimensions.x * (sample.y + self.dimensions.y * sample.z)) as usize]
+    }
+
+    pub fn position(&self, sample: UVec3) -> Vec3 {
+        self.origin + sample.as_vec3() * self.cell_size
+    }
+
+    /// Central differences, one sided at the borders of the grid.
+    pub fn gradient(&self, sample: UVec3) -> Vec3 {
+        let max = self.dimensions - UVec3::ONE;
+        let mut gradient = Vec3::ZERO;
+
+        for axis in 0..3 {
+            let mut previous = sample;
+            let mut next = sample;
+            previous[axis] = sample[axis].saturating_sub(1);
+            next[axis] = (sample[axis] + 1).min(max[axis]);
+
+            let distance = (next[axis] - previous[axis]) as f32 * self.cell_size;
+            if distance > 0. {
+                gradient[axis] = (self.get(next) - self.get(previous)) / distance;
+            }
+        }
+
+        gradient
+    }
+}
+
+/// Solid below the surface of a height map, carved by 3D noise caves.
+pub struct TerrainDensity<'a> {
+    height_map: &'a HeightMap,
+    caves: Perlin3D,
+    cave_threshold: f32,
+    cave_frequency: f32,
+}
+
+impl<'a> TerrainDensity<'a> {
+    pub fn new(height_map: &'a HeightMap, config: &VoxelConfig, seed: Seed) -> Self {
+        TerrainDensity {
+            height_map,
+            caves: Perlin3D::new(&[(0.75, 1.), (0.25, 2.)], 256, Some(seed.value())),
+            cave_threshold: config.cave_threshold,
+            cave_frequency: config.cave_frequency,
+        }
+    }
+}
+
+impl Noise for TerrainDensity<'_> {
+    type Input = (f32, f32, f32);
+    type Output = f32;
+
+    fn get(&self, (x, y, z): (f32, f32, f32)) -> f32 {
+        let surface = self.height_map.height_at(x, z) - y;
+        let frequency = self.cave_frequency;
+        // Roughly the distance to the cave walls
+        let caves = (self.cave_threshold
+            - self
+                .caves
+                .get((x * frequency, y * frequency, z * frequency)))
+            / frequency;
+
+        // Open all the way down over the dungeon entrance
+        let hole = self.height_map.hole_distance(x, z);
+
+        surface.min(caves).min(hole)
+    }
+}
+
+/// Mesh of the chunk at `coordinates` of a volume of `cells` cells starting at `origin`.
+pub fn chunk_mesh<T: Noise<Input = (f32, f32, f32), Output = f32>>(
+    density: &T,
+    origin: Vec3,
+    cells: UVec3,
+    coordinates: UVec3,
+    config: &VoxelConfig,
+) -> Mesh {
+    let first_cell = coordinates * config.chunk_cells;
+    // Chunks share their border samples so there are no gaps between them
+    let dimensions = (cells - first_cell).min(UVec3::splat(config.chunk_cells)) + 1;
+
+    let chunk_origin = origin + first_cell.as_vec3() * config.cell_size;
+
+    match config.mesher {
+        VoxelMesher::MarchingCubes => {
+            let grid = DensityGrid::new(chunk_origin, config.cell_size, dimensions, density);
+
+            marching_cubes::generate(&grid)
+        }
+        VoxelMesher::SurfaceNets | VoxelMesher::DualContouring => {
+            // Two samples of padding around the chunk
+            let grid = DensityGrid::new(
+                chunk_origin - config.cell_size * 2.,
+                config.cell_size,
+                dimensions + 4,
+                density,
+            );
+            let placement = if config.mesher == VoxelMesher::SurfaceNets {
+                VertexPlacement::MassPoint
+            } else {
+                VertexPlacement::Qef
+            };
+
+            dual_contouring::generate(&grid, placement, (first_cell + dimensions - 1).cmpge(cells))
+        }
+    }
+}
+
+/// Meshes the volume under the map in chunks, each one with its own trimesh collider.
+pub(super) fn spawn_voxel_chunks(
+    parent: &mut ChildBuilder,
+    height_map: &HeightMap,
+    map_info: &MapInfo,
+    config: &VoxelConfig,
+    seed: Seed,
+    material: Handle<TerrainMaterial>,
+    meshes: &mut Assets<Mesh>,
+) {
+    let density = TerrainDensity::new(height_map, config, seed);
+
+    let bottom = map_info.min_depth - config.underground_depth;
+    // Leave room above the highest peak so the surface is closed
+    let top = map_info.max_depth + config.cell_size * 2.;
+    let cells = UVec3::new(
+        (map_info.size / config.cell_size).ceil() as u32,
+        ((top - bottom) / config.cell_size).ceil() as u32,
+        (map_info.size / config.cell_size).ceil() as u32,
+    );
+    let chunks = (cells + config.chunk_cells - 1) / config.chunk_cells;
+    let origin = Vec3::new(-map_info.size / 2., bottom, -map_info.size / 2.);
+
+    for z in 0..chunks.z {
+        for y in 0..chunks.y {
+            for x in 0..chunks.x {
+                let coordinates = UVec3::new(x, y, z);
+                let mesh = chunk_mesh(&density, origin, cells, coordinates, config);
+
+                if mesh.count_vertices() == 0 {
+                    continue;
+                }
+
+                let collider = Collider::from_bevy_mesh(&mesh, &ComputedColliderShape::TriMesh)
+                    .expect("Voxel chunk mesh is not a valid trimesh");
+
+                parent
+                    .spawn(MaterialMeshBundle {
+                        mesh: meshes.add(mesh),
+                        material: material.clone(),
+                        ..default()
+                    })
+                    .insert((VoxelChunk { coordinates }, collider));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{render::mesh::VertexAttributeValues, utils::HashMap};
+
+    use super::*;
+
+    /// Solid inside a sphere.
+    struct Sphere(f32);
+
+    impl Noise for Sphere {
+        type Input = (f32, f32, f32);
+        type Output = f32;
+
+        fn get(&self, input: (f32, f32, f32)) -> f32 {
+            self.0 - Vec3::from(input).length()
+        }
+    }
+
+    /// Solid inside an axis aligned box.
+    struct Cube(Vec3);
+
+    impl Noise for Cube {
+        type Input = (f32, f32, f32);
+        type Output = f32;
+
+        fn get(&self, input: (f32, f32, f32)) -> f32 {
+            (self.0 - Vec3::from(input).abs()).min_element()
+        }
+    }
+
+    /// Meshes a volume around the origin in 3x3x3 chunks, and counts the triangles around each
+    /// edge of the merged meshes. Vertices are merged by position, since the cells on the borders
+    /// of the chunks are meshed by both of them.
+    fn edge_faces<T: Noise<Input = (f32, f32, f32), Output = f32>>(
+        density: &T,
+        mesher: VoxelMesher,
+    ) -> HashMap<(IVec3, IVec3), u32> {
+        let config = VoxelConfig {
+            cell_size: 0.25,
+            chunk_cells: 8,
+            mesher,
+            ..default()
+        };
+        let cells = UVec3::splat(24);
+        let origin = Vec3::splat(-3.);
+        let mut edges = HashMap::new();
+
+        for z in 0..3 {
+            for y in 0..3 {
+                for x in 0..3 {
+                    let mesh = chunk_mesh(density, origin, cells, UVec3::new(x, y, z), &config);
+                    let Some(VertexAttributeValues::Float32x3(positions)) =
+                        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+                    else {
+                        panic!("Voxel meshes have positions");
+                    };
+                    let key =
+                        |index: usize| (Vec3::from(positions[index]) * 1e4).round().as_ivec3();
+                    let indices: Vec<usize> = mesh.indices().unwrap().iter().collect();
+
+                    for triangle in indices.chunks_exact(3) {
+                        for i in 0..3 {
+                            let (a, b) = (key(triangle[i]), key(triangle[(i + 1) % 3]));
+                            let edge = if a.to_array() < b.to_array() {
+                                (a, b)
+                            } else {
+                                (b, a)
+                            };
+                            *edges.entry(edge).or_insert(0) += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        edges
+    }
+
+    fn assert_watertight(edges: &HashMap<(IVec3, IVec3), u32>) {
+        assert!(!edges.is_empty());
+
+        let open = edges.values().filter(|&&faces| faces != 2).count();
+        assert_eq!(
+            open,
+            0,
+            "{open} of {} edges aren't shared by two faces",
+            edges.len()
+        );
+    }
+
+    #[test]
+    fn surface_nets_sphere_is_watertight() {
+        assert_watertight(&edge_faces(&Sphere(2.1), VoxelMesher::SurfaceNets));
+    }
+
+    #[test]
+    fn dual_contouring_sphere_is_watertight() {
+        assert_watertight(&edge_faces(&Sphere(2.1), VoxelMesher::DualContouring));
+    }
+
+    #[test]
+    fn dual_contouring_box_is_watertight() {
+        assert_watertight(&edge_faces(
+            &Cube(Vec3::new(1.9, 1.3, 2.2)),
+            VoxelMesher::DualContouring,
+        ));
+    }
+}
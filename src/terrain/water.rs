@@ -0,0 +1,136 @@
+use bevy::{color::palettes::css::DEEP_SKY_BLUE, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+use crate::player::Player;
+
+use super::{height_map::HeightMap, MapInfo};
+
+/// Volume of water below `sea_level`, spawned as a sensor per terrain chunk.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct WaterBody {
+    pub sea_level: f32,
+}
+
+/// Present on the `Player` while it intersects a `WaterBody`.
+/// `depth` is the distance from the water surface down to the center of the player.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Submerged {
+    pub depth: f32,
+}
+
+/// Points of a chunk where the terrain crosses the sea level, in chunk local coordinates.
+#[derive(Component, Clone, Debug, Default)]
+pub struct Shoreline {
+    pub points: Vec<Vec3>,
+}
+
+impl Shoreline {
+    /// Samples above `sea_level` with at least one neighbour below it.
+    pub fn from_height_map(height_map: &HeightMap, sea_level: f32) -> Self {
+        let samples = height_map.samples;
+        let heights = &height_map.height_map;
+        let mut points = Vec::new();
+
+        for x in 0..samples {
+            for z in 0..samples {
+                if heights[x][z] < sea_level {
+                    continue;
+                }
+
+                let underwater_neighbour = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                    .into_iter()
+                    .filter_map(|(dx, dz)| {
+                        let nx = x.checked_add_signed(dx)?;
+                        let nz = z.checked_add_signed(dz)?;
+                        heights.get(nx)?.get(nz)
+                    })
+                    .any(|&height| height < sea_level);
+
+                if underwater_neighbour {
+                    points.push(height_map.position(x, z));
+                }
+            }
+        }
+
+        Shoreline { points }
+    }
+}
+
+/// Spawns the water surface and its sensor volume as children of a terrain chunk.
+pub(super) fn spawn_water(
+    parent: &mut ChildBuilder,
+    map_info: &MapInfo,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    if map_info.sea_level <= map_info.min_depth {
+        return;
+    }
+
+    let water_depth = map_info.sea_level - map_info.min_depth;
+
+    // Surface
+    parent.spawn(PbrBundle {
+        mesh: meshes.add(Plane3d::default().mesh().size(map_info.size, map_info.size)),
+        material: materials.add(StandardMaterial {
+            base_color: Color::from(DEEP_SKY_BLUE).with_alpha(0.6),
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: 0.1,
+            reflectance: 0.3,
+            ..default()
+        }),
+        transform: Transform::from_xyz(0., map_info.sea_level, 0.),
+        ..default()
+    });
+
+    // Volume
+    parent
+        .spawn(WaterBody {
+            sea_level: map_info.sea_level,
+        })
+        .insert(Collider::cuboid(
+            map_info.size / 2.,
+            water_depth / 2.,
+            map_info.size / 2.,
+        ))
+        .insert(Sensor)
+        // Sensors ignore kinematic bodies like the player by default
+        .insert(ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC)
+        .insert(TransformBundle::from(Transform::from_xyz(
+            0.,
+            map_info.sea_level - water_depth / 2.,
+            0.,
+        )));
+}
+
+pub(super) fn update_submerged(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    water_q: Query<(Entity, &WaterBody)>,
+    mut player_q: Query<(Entity, &Transform, Option<&mut Submerged>), With<Player>>,
+) {
+    let Ok((player, player_transform, submerged)) = player_q.get_single_mut() else {
+        return;
+    };
+
+    let water_level = water_q
+        .iter()
+        .filter(|(water, _)| rapier_context.intersection_pair(*water, player) == Some(true))
+        .map(|(_, water_body)| water_body.sea_level)
+        .reduce(f32::max);
+
+    match (water_level, submerged) {
+        (Some(sea_level), Some(mut submerged)) => {
+            submerged.depth = sea_level - player_transform.translation.y;
+        }
+        (Some(sea_level), None) => {
+            commands.entity(player).insert(Submerged {
+                depth: sea_level - player_transform.translation.y,
+            });
+        }
+        (None, Some(_)) => {
+            commands.entity(player).remove::<Submerged>();
+        }
+        (None, None) => {}
+    }
+}
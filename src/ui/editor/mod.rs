@@ -0,0 +1,344 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{
+    color::palettes::css::WHITE, prelude::*, time::common_conditions::on_timer,
+    window::PrimaryWindow,
+};
+use bevy_rapier3d::{plugin::RapierContext, prelude::*};
+
+use sculpt::{
+    apply_brush, brush_region, update_mesh_region, HeightEdit, Region, SculptBrush, SculptHistory,
+    SculptSettings,
+};
+
+use crate::{
+    camera::MainCamera,
+    input_handling::KeyBindings,
+    terrain::{
+        height_map::{HeightDelta, HeightMap},
+        TerrainChunk, TerrainCollider,
+    },
+    utils::noise::perlin::Perlin,
+    AppState,
+};
+
+pub mod sculpt;
+
+/// Rebuilding a heightfield collider is expensive, so it's done at most this often while sculpting.
+const COLLIDER_UPDATE_INTERVAL: f32 = 0.2;
+const MAX_BRUSH_DISTANCE: f32 = 200.;
+const RADIUS_STEP: f32 = 1.25;
+const STRENGTH_STEP: f32 = 1.25;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app //
+            .init_resource::<SculptSettings>()
+            .init_resource::<SculptHistory>()
+            .init_resource::<EditedRegions>()
+            .add_systems(
+                Update,
+                (toggle_editor, change_brush, sculpt, undo_redo)
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                update_terrain_colliders
+                    .run_if(on_timer(Duration::from_secs_f32(COLLIDER_UPDATE_INTERVAL))),
+            );
+    }
+}
+
+/// Stroke in progress, from pressing the mouse button until releasing it.
+struct Stroke {
+    chunk: Entity,
+    /// Samples changed so far, if any.
+    region: Option<Region>,
+    /// Heights of the samples under the brush so far, when the stroke started.
+    before: HashMap<(usize, usize), f32>,
+    flatten_height: f32,
+}
+
+impl Stroke {
+    /// Heights changed by the stroke, before and after it.
+    fn deltas(&self, height_map: &HeightMap) -> (Vec<HeightDelta>, Vec<HeightDelta>) {
+        let mut samples: Vec<_> = self
+            .before
+            .iter()
+            .filter(|(&(x, z), &height)| height_map.height_map[x][z] != height)
+            .collect();
+        samples.sort_unstable_by_key(|(&sample, _)| sample);
+
+        samples
+            .into_iter()
+            .map(|(&(x, z), &height)| {
+                let delta = |height| HeightDelta {
+                    x: x as u32,
+                    z: z as u32,
+                    height,
+                };
+                (delta(height), delta(height_map.height_map[x][z]))
+            })
+            .unzip()
+    }
+}
+
+/// Samples of every chunk edited since its colliders were last updated.
+#[derive(Resource, Default)]
+struct EditedRegions(HashMap<Entity, Region>);
+
+impl EditedRegions {
+    fn add(&mut self, chunk: Entity, region: &Region) {
+        self.0
+            .entry(chunk)
+            .and_modify(|edited| *edited = edited.union(region))
+            .or_insert_with(|| region.clone());
+    }
+}
+
+fn toggle_editor(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keybindings: Res<KeyBindings>,
+    mut settings: ResMut<SculptSettings>,
+) {
+    if let Some(editor_key) = keybindings.editor {
+        if keyboard_input.just_pressed(editor_key) {
+            settings.enabled = !settings.enabled;
+            info!("Terrain editor enabled: {}", settings.enabled);
+        }
+    }
+}
+
+fn change_brush(keyboard_input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<SculptSettings>) {
+    if !settings.enabled {
+        return;
+    }
+
+    let brushes = [
+        (KeyCode::Digit1, SculptBrush::Raise),
+        (KeyCode::Digit2, SculptBrush::Lower),
+        (KeyCode::Digit3, SculptBrush::Smooth),
+        (KeyCode::Digit4, SculptBrush::Flatten),
+        (KeyCode::Digit5, SculptBrush::NoiseStamp),
+    ];
+
+    for (key, brush) in brushes {
+        if keyboard_input.just_pressed(key) {
+            settings.brush = brush;
+            info!("Brush: {:?}", brush);
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::BracketLeft) {
+        settings.radius /= RADIUS_STEP;
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketRight) {
+        settings.radius *= RADIUS_STEP;
+    }
+    if keyboard_input.just_pressed(KeyCode::Minus) {
+        settings.strength /= STRENGTH_STEP;
+    }
+    if keyboard_input.just_pressed(KeyCode::Equal) {
+        settings.strength *= STRENGTH_STEP;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sculpt(
+    mut stroke: Local<Option<Stroke>>,
+    mut noise: Local<Option<Perlin>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    time: Res<Time>,
+    settings: Res<SculptSettings>,
+    mut history: ResMut<SculptHistory>,
+    mut edited_regions: ResMut<EditedRegions>,
+    rapier_context: Res<RapierContext>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    collider_q: Query<&Parent, With<TerrainCollider>>,
+    mut chunk_q: Query<(&mut HeightMap, &Handle<Mesh>, &GlobalTransform), With<TerrainChunk>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut gizmos: Gizmos,
+) {
+    if !settings.enabled || !mouse_input.pressed(MouseButton::Left) {
+        // Stroke finished, store it so it can be undone
+        if let Some(stroke) = stroke.take() {
+            if let (Some(region), Ok((height_map, _, _))) =
+                (stroke.region.clone(), chunk_q.get(stroke.chunk))
+            {
+                let (before, after) = stroke.deltas(height_map);
+
+                history.push(HeightEdit {
+                    chunk: stroke.chunk,
+                    region,
+                    before,
+                    after,
+                });
+            }
+        }
+
+        if !settings.enabled {
+            return;
+        }
+    }
+
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_q.get_single(), camera_q.get_single())
+    else {
+        return;
+    };
+
+    // Aim with the cursor when it's free, or with the center of the screen when it's grabbed
+    let viewport_position = if window.cursor.visible {
+        let Some(cursor_position) = window.cursor_position() else {
+            return;
+        };
+        cursor_position
+    } else {
+        Vec2::new(window.width(), window.height()) / 2.
+    };
+
+    let Some(ray) = camera.viewport_to_world(camera_transform, viewport_position) else {
+        return;
+    };
+
+    let Some((collider, distance)) = rapier_context.cast_ray(
+        ray.origin,
+        *ray.direction,
+        MAX_BRUSH_DISTANCE,
+        true,
+        // Props have fixed colliders too
+        QueryFilter::only_fixed()
+            .exclude_sensors()
+            .predicate(&|entity| collider_q.contains(entity)),
+    ) else {
+        return;
+    };
+
+    let Ok(chunk) = collider_q.get(collider).map(|parent| parent.get()) else {
+        return;
+    };
+    let Ok((mut height_map, mesh, chunk_transform)) = chunk_q.get_mut(chunk) else {
+        return;
+    };
+
+    let hit = ray.get_point(distance);
+    gizmos.circle(hit, Dir3::Y, settings.radius, WHITE);
+
+    if !mouse_input.pressed(MouseButton::Left) {
+        return;
+    }
+
+    let center = chunk_transform.affine().inverse().transform_point3(hit);
+
+    if !matches!(&*stroke, Some(stroke) if stroke.chunk == chunk) {
+        *stroke = Some(Stroke {
+            chunk,
+            region: None,
+            before: HashMap::new(),
+            flatten_height: center.y,
+        });
+    }
+
+    let stroke = stroke.as_mut().unwrap();
+    let noise = noise.get_or_insert_with(|| Perlin::new(&[(0.75, 1.), (0.25, 2.)], 256, None));
+
+    if let Some(region) = brush_region(&height_map, center, settings.radius) {
+        for (x, z) in region.samples() {
+            stroke
+                .before
+                .entry((x, z))
+                .or_insert(height_map.height_map[x][z]);
+        }
+    }
+
+    let Some(region) = apply_brush(
+        &mut height_map,
+        center,
+        &settings,
+        stroke.flatten_height,
+        noise,
+        time.delta_seconds(),
+    ) else {
+        return;
+    };
+
+    if let Some(mesh) = meshes.get_mut(mesh) {
+        update_mesh_region(mesh, &height_map, &region);
+    }
+
+    edited_regions.add(chunk, &region);
+    stroke.region = Some(match &stroke.region {
+        Some(stroke_region) => stroke_region.union(&region),
+        None => region,
+    });
+}
+
+fn undo_redo(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    keybindings: Res<KeyBindings>,
+    settings: Res<SculptSettings>,
+    mut history: ResMut<SculptHistory>,
+    mut edited_regions: ResMut<EditedRegions>,
+    mut chunk_q: Query<(&mut HeightMap, &Handle<Mesh>), With<TerrainChunk>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    let pressed = |key: Option<KeyCode>| key.is_some_and(|key| keyboard_input.just_pressed(key));
+
+    let mut apply = |edit: &HeightEdit, deltas: &[HeightDelta]| {
+        if let Ok((mut height_map, mesh)) = chunk_q.get_mut(edit.chunk) {
+            height_map.apply_deltas(deltas);
+            edited_regions.add(edit.chunk, &edit.region);
+
+            if let Some(mesh) = meshes.get_mut(mesh) {
+                update_mesh_region(mesh, &height_map, &edit.region);
+            }
+        }
+    };
+
+    if pressed(keybindings.undo) {
+        if let Some(edit) = history.undo() {
+            apply(&edit, &edit.before);
+            history.undone(edit);
+        }
+    } else if pressed(keybindings.redo) {
+        if let Some(edit) = history.redo() {
+            apply(&edit, &edit.after);
+            history.redone(edit);
+        }
+    }
+}
+
+/// Rebuilds the collider tiles of every chunk covering samples edited since the last update.
+fn update_terrain_colliders(
+    mut edited_regions: ResMut<EditedRegions>,
+    chunk_q: Query<(&HeightMap, &Children), With<TerrainChunk>>,
+    mut collider_q: Query<(&mut Collider, &TerrainCollider)>,
+) {
+    for (chunk, region) in edited_regions.0.drain() {
+        let Ok((height_map, children)) = chunk_q.get(chunk) else {
+            continue;
+        };
+
+        for &child in children {
+            let Ok((mut collider, &TerrainCollider { tile })) = collider_q.get_mut(child) else {
+                continue;
+            };
+            let tile_region = Region {
+                x: height_map.collider_tile_samples(tile.x),
+                z: height_map.collider_tile_samples(tile.y),
+            };
+
+            if tile_region.intersects(&region) {
+                *collider = height_map.tile_collider(tile).0;
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::ops::RangeInclusive;
+
+use bevy::{math::FloatExt, prelude::*, render::mesh::VertexAttributeValues};
+
+use crate::{
+    terrain::height_map::{HeightDelta, HeightMap},
+    utils::noise::{perlin::Perlin, Noise},
+};
+
+/// Frequency of the noise added by `SculptBrush::NoiseStamp`, per world unit.
+const NOISE_STAMP_FREQUENCY: f32 = 0.5;
+/// Maximum number of strokes that can be undone.
+const HISTORY_LENGTH: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SculptBrush {
+    Raise,
+    Lower,
+    /// Blends towards the average of the neighbouring heights.
+    Smooth,
+    /// Blends towards the height where the stroke started.
+    Flatten,
+    NoiseStamp,
+}
+
+/// How the strength of the brush decreases from its center to its radius.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Falloff {
+    Constant,
+    Linear,
+    Smooth,
+}
+
+impl Falloff {
+    /// Weight at `distance`, normalized so the radius of the brush is 1.
+    pub fn weight(self, distance: f32) -> f32 {
+        let t = (1. - distance).clamp(0., 1.);
+
+        match self {
+            Falloff::Constant => 1.,
+            Falloff::Linear => t,
+            Falloff::Smooth => t * t * (3. - 2. * t),
+        }
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct SculptSettings {
+    pub enabled: bool,
+    pub brush: SculptBrush,
+    pub radius: f32,
+    /// Height change per second at the center of the brush. Blend rate for smooth and flatten.
+    pub strength: f32,
+    pub falloff: Falloff,
+}
+
+impl Default for SculptSettings {
+    fn default() -> Self {
+        SculptSettings {
+            enabled: false,
+            brush: SculptBrush::Raise,
+            radius: 2.,
+            strength: 2.,
+            falloff: Falloff::Smooth,
+        }
+    }
+}
+
+/// Rectangle of samples of a `HeightMap`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: RangeInclusive<usize>,
+    pub z: RangeInclusive<usize>,
+}
+
+impl Region {
+    pub fn union(&self, other: &Region) -> Region {
+        Region {
+            x: *self.x.start().min(other.x.start())..=*self.x.end().max(other.x.end()),
+            z: *self.z.start().min(other.z.start())..=*self.z.end().max(other.z.end()),
+        }
+    }
+
+    /// Grows the region by `amount` samples on every side, without going out of the map.
+    fn expand(&self, amount: usize, samples: usize) -> Region {
+        Region {
+            x: self.x.start().saturating_sub(amount)..=(self.x.end() + amount).min(samples - 1),
+            z: self.z.start().saturating_sub(amount)..=(self.z.end() + amount).min(samples - 1),
+        }
+    }
+
+    pub fn intersects(&self, other: &Region) -> bool {
+        self.x.start() <= other.x.end()
+            && other.x.start() <= self.x.end()
+            && self.z.start() <= other.z.end()
+            && other.z.start() <= self.z.end()
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.x
+            .clone()
+            .flat_map(move |x| self.z.clone().map(move |z| (x, z)))
+    }
+
+    pub fn read(&self, height_map: &HeightMap) -> Vec<f32> {
+        self.samples()
+            .map(|(x, z)| height_map.height_map[x][z])
+            .collect()
+    }
+}
+
+/// Heights of the samples of a terrain chunk changed by a stroke, before and after it.
+pub struct HeightEdit {
+    pub chunk: Entity,
+    /// Samples around every change.
+    pub region: Region,
+    pub before: Vec<HeightDelta>,
+    pub after: Vec<HeightDelta>,
+}
+
+#[derive(Resource, Default)]
+pub struct SculptHistory {
+    undo: Vec<HeightEdit>,
+    redo: Vec<HeightEdit>,
+}
+
+impl SculptHistory {
+    pub fn push(&mut self, edit: HeightEdit) {
+        if self.undo.len() == HISTORY_LENGTH {
+            self.undo.remove(0);
+        }
+
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+
+    /// Takes the last edit to undo. Give it back with `undone` once it has been reverted.
+    pub fn undo(&mut self) -> Option<HeightEdit> {
+        self.undo.pop()
+    }
+
+    pub fn undone(&mut self, edit: HeightEdit) {
+        self.redo.push(edit);
+    }
+
+    /// Takes the last undone edit. Give it back with `redone` once it has been applied again.
+    pub fn redo(&mut self) -> Option<HeightEdit> {
+        self.redo.pop()
+    }
+
+    pub fn redone(&mut self, edit: HeightEdit) {
+        self.undo.push(edit);
+    }
+
+    /// Forgets every edit, for when the terrain they refer to is gone.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Samples within `radius` of `center`, in chunk local coordinates, if any.
+pub fn brush_region(height_map: &HeightMap, center: Vec3, radius: f32) -> Option<Region> {
+    let last = (height_map.samples - 1) as f32;
+    let radius = radius / height_map.unit_size();
+    let center = height_map.sample_coordinates(center.x, center.z);
+
+    let min = (center - radius).ceil().max(Vec2::ZERO);
+    let max = (center + radius).floor().min(Vec2::splat(last));
+
+    (min.x <= max.x && min.y <= max.y).then_some(Region {
+        x: min.x as usize..=max.x as usize,
+        z: min.y as usize..=max.y as usize,
+    })
+}
+
+/// Applies one step of the brush centered at `center`, in chunk local coordinates.
+/// Returns the region of the height map that changed.
+pub fn apply_brush(
+    height_map: &mut HeightMap,
+    center: Vec3,
+    settings: &SculptSettings,
+    flatten_height: f32,
+    noise: &Perlin,
+    delta_seconds: f32,
+) -> Option<Region> {
+    let region = brush_region(height_map, center, settings.radius)?;
+    let last = (height_map.samples - 1) as f32;
+    let unit_size = height_map.unit_size();
+    let radius = settings.radius / unit_size;
+    let center = height_map.sample_coordinates(center.x, center.z);
+    // Smoothing reads the heights from before this step
+    let previous = region.expand(1, height_map.samples);
+    let previous_heights = previous.read(height_map);
+    let previous_height = |x: usize, z: usize| {
+        let width = previous.z.end() - previous.z.start() + 1;
+        previous_heights[(x - previous.x.start()) * width + z - previous.z.start()]
+    };
+
+    for (x, z) in region.samples() {
+        let distance = (Vec2::new(x as f32, z as f32) - center).length() / radius;
+
+        if distance > 1. {
+            continue;
+        }
+
+        let weight = settings.falloff.weight(distance) * settings.strength * delta_seconds;
+        let height = &mut height_map.height_map[x][z];
+
+        match settings.brush {
+            SculptBrush::Raise => *height += weight,
+            SculptBrush::Lower => *height -= weight,
+            SculptBrush::Smooth => {
+                let neighbours = [
+                    (x.saturating_sub(1), z),
+                    ((x + 1).min(last as usize), z),
+                    (x, z.saturating_sub(1)),
+                    (x, (z + 1).min(last as usize)),
+                ];
+                let average = neighbours
+                    .iter()
+                    .map(|&(x, z)| previous_height(x, z))
+                    .sum::<f32>()
+                    / neighbours.len() as f32;
+
+                *height = height.lerp(average, weight.min(1.));
+            }
+            SculptBrush::Flatten => *height = height.lerp(flatten_height, weight.min(1.)),
+            SculptBrush::NoiseStamp => {
+                let value = noise.get((
+                    x as f32 * unit_size * NOISE_STAMP_FREQUENCY,
+                    z as f32 * unit_size * NOISE_STAMP_FREQUENCY,
+                ));
+
+                *height += (value - 0.5) * 2. * weight;
+            }
+        }
+    }
+
+    Some(region)
+}
+
+/// Copies the heights of `region` to the vertices of a mesh built from the height map, and
+/// recalculates the normals affected by them.
+pub fn update_mesh_region(mesh: &mut Mesh, height_map: &HeightMap, region: &Region) {
+    let samples = height_map.samples;
+
+    if let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+    {
+        for (x, z) in region.samples() {
+            positions[x * samples + z][1] = height_map.height_map[x][z];
+        }
+    }
+
+    if let Some(VertexAttributeValues::Float32x3(normals)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL)
+    {
+        for (x, z) in region.expand(1, samples).samples() {
+            normals[x * samples + z] = height_map.normal(x, z).to_array();
+        }
+    }
+}
@@ -0,0 +1,377 @@
+use std::{fmt, str::FromStr};
+
+use rand::Rng;
+
+use super::seed::Seed;
+
+pub const DEAD: u8 = 0;
+pub const ALIVE: u8 = 1;
+
+/// Cells counted as neighbours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Neighbourhood {
+    /// The 8 cells around.
+    #[default]
+    Moore,
+    /// The 4 cells sharing a side.
+    VonNeumann,
+}
+
+impl Neighbourhood {
+    fn offsets(self) -> &'static [(i32, i32)] {
+        match self {
+            Neighbourhood::Moore => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+            Neighbourhood::VonNeumann => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+        }
+    }
+}
+
+/// What the neighbours outside of the grid are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Border {
+    /// The grid wraps around, like a torus.
+    #[default]
+    Wrap,
+    /// The closest cell of the grid.
+    Clamp,
+    /// Always the same state, like walls around a cave.
+    Fixed(u8),
+}
+
+/// Birth and survival rule, like `B3/S23` for Conway's Life.
+///
+/// With more than 2 states, as in `B2/S/C3`, alive cells that don't survive go through the dying
+/// states `2..states` before they are dead. Dying cells can't be born again and don't count as
+/// neighbours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule {
+    /// Bit `n` is set when dead cells with `n` alive neighbours are born.
+    pub birth: u32,
+    /// Bit `n` is set when alive cells with `n` alive neighbours survive.
+    pub survival: u32,
+    pub states: u8,
+}
+
+impl Rule {
+    pub const LIFE: Rule = Rule::new(&[3], &[2, 3]);
+    /// Caves that grow from a noisy start, alive cells are walls. The 4-5 rule: walls stay with
+    /// at least 4 walls around them, and open cells fill with 5.
+    pub const CAVES: Rule = Rule::new(&[5, 6, 7, 8], &[4, 5, 6, 7, 8]);
+
+    pub const fn new(birth: &[u8], survival: &[u8]) -> Self {
+        Rule {
+            birth: mask(birth),
+            survival: mask(survival),
+            states: 2,
+        }
+    }
+
+    pub const fn with_states(mut self, states: u8) -> Self {
+        self.states = states;
+        self
+    }
+
+    fn next(&self, state: u8, alive_neighbours: u32) -> u8 {
+        match state {
+            DEAD if self.birth & (1 << alive_neighbours) != 0 => ALIVE,
+            DEAD => DEAD,
+            ALIVE if self.survival & (1 << alive_neighbours) != 0 => ALIVE,
+            ALIVE if self.states > 2 => 2,
+            ALIVE => DEAD,
+            dying => (dying + 1) % self.states,
+        }
+    }
+}
+
+const fn mask(counts: &[u8]) -> u32 {
+    let mut mask = 0;
+    let mut i = 0;
+
+    while i < counts.len() {
+        mask |= 1 << counts[i];
+        i += 1;
+    }
+
+    mask
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule::LIFE
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = |mask: u32| -> String {
+            (0..=8)
+                .filter(|n| mask & (1 << n) != 0)
+                .map(|n| char::from(b'0' + n as u8))
+                .collect()
+        };
+
+        write!(f, "B{}/S{}", digits(self.birth), digits(self.survival))?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleError {
+    /// A part of the rule isn't `B…`, `S…` or `C…`.
+    UnknownPart(String),
+    /// A neighbour count above 8.
+    InvalidCount(char),
+    /// Fewer than 2 states, or not a number.
+    InvalidStates(String),
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::UnknownPart(part) => write!(f, "invalid rule part: {part}"),
+            RuleError::InvalidCount(count) => write!(f, "invalid neighbour count: {count}"),
+            RuleError::InvalidStates(states) => write!(f, "invalid number of states: {states}"),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    /// Parses `B…/S…`, optionally followed by `/C…` with the number of states. Letters are case
+    /// insensitive and the parts can be in any order.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut rule = Rule {
+            birth: 0,
+            survival: 0,
+            states: 2,
+        };
+
+        let counts = |digits: &str| -> Result<u32, RuleError> {
+            digits.chars().try_fold(0, |mask, digit| match digit {
+                '0'..='8' => Ok(mask | 1 << (digit as u32 - '0' as u32)),
+                _ => Err(RuleError::InvalidCount(digit)),
+            })
+        };
+
+        for part in text.trim().split('/') {
+            let mut chars = part.chars();
+            let kind = chars.next().map(|c| c.to_ascii_uppercase());
+            let rest = chars.as_str();
+
+            match kind {
+                Some('B') => rule.birth = counts(rest)?,
+                Some('S') => rule.survival = counts(rest)?,
+                Some('C') => {
+                    rule.states = rest
+                        .parse()
+                        .ok()
+                        .filter(|&states| states >= 2)
+                        .ok_or_else(|| RuleError::InvalidStates(rest.to_string()))?
+                }
+                _ => return Err(RuleError::UnknownPart(part.to_string())),
+            }
+        }
+
+        Ok(rule)
+    }
+}
+
+/// Grid of cells updated all at once by a `Rule`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Automaton {
+    pub width: u32,
+    pub height: u32,
+    pub rule: Rule,
+    pub neighbourhood: Neighbourhood,
+    pub border: Border,
+    /// State of each cell, row by row.
+    pub cells: Vec<u8>,
+    next: Vec<u8>,
+}
+
+impl Automaton {
+    /// Automaton with every cell dead.
+    pub fn new(
+        width: u32,
+        height: u32,
+        rule: Rule,
+        neighbourhood: Neighbourhood,
+        border: Border,
+    ) -> Self {
+        let cells = vec![DEAD; (width * height) as usize];
+
+        Automaton {
+            width,
+            height,
+            rule,
+            neighbourhood,
+            border,
+            next: cells.clone(),
+            cells,
+        }
+    }
+
+    /// Makes each cell alive with a chance of `density`, and dead otherwise.
+    pub fn randomize(&mut self, density: f32, seed: Seed) {
+        let mut rng = seed.rng();
+
+        for cell in &mut self.cells {
+            *cell = if rng.gen::<f32>() < density {
+                ALIVE
+            } else {
+                DEAD
+            };
+        }
+    }
+
+    /// State of the cell at `(x, y)`, following `border` outside of the grid.
+    pub fn get(&self, x: i32, y: i32) -> u8 {
+        let (width, height) = (self.width as i32, self.height as i32);
+
+        let (x, y) = match self.border {
+            _ if (0..width).contains(&x) && (0..height).contains(&y) => (x, y),
+            Border::Wrap => (x.rem_euclid(width), y.rem_euclid(height)),
+            Border::Clamp => (x.clamp(0, width - 1), y.clamp(0, height - 1)),
+            Border::Fixed(state) => return state,
+        };
+
+        self.cells[(x + y * width) as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, state: u8) {
+        self.cells[(x + y * self.width) as usize] = state;
+    }
+
+    pub fn is_alive(&self, x: i32, y: i32) -> bool {
+        self.get(x, y) == ALIVE
+    }
+
+    /// Number of alive cells.
+    pub fn population(&self) -> usize {
+        self.cells.iter().filter(|&&cell| cell == ALIVE).count()
+    }
+
+    pub fn step(&mut self) {
+        let offsets = self.neighbourhood.offsets();
+        let mut next = std::mem::take(&mut self.next);
+
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                let alive_neighbours = offsets
+                    .iter()
+                    .filter(|&&(dx, dy)| self.is_alive(x + dx, y + dy))
+                    .count() as u32;
+
+                let index = (x + y * self.width as i32) as usize;
+                next[index] = self.rule.next(self.cells[index], alive_neighbours);
+            }
+        }
+
+        self.next = std::mem::replace(&mut self.cells, next);
+    }
+
+    pub fn run(&mut self, steps: u32) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn life(width: u32, height: u32, alive: &[(u32, u32)]) -> Automaton {
+        let mut automaton = Automaton::new(
+            width,
+            height,
+            Rule::LIFE,
+            Neighbourhood::Moore,
+            Border::Wrap,
+        );
+        for &(x, y) in alive {
+            automaton.set(x, y, ALIVE);
+        }
+
+        automaton
+    }
+
+    #[test]
+    fn blinker() {
+        let horizontal = life(5, 5, &[(1, 2), (2, 2), (3, 2)]);
+        let vertical = life(5, 5, &[(2, 1), (2, 2), (2, 3)]);
+        let mut automaton = horizontal.clone();
+
+        automaton.step();
+        assert_eq!(automaton.cells, vertical.cells);
+        automaton.step();
+        assert_eq!(automaton.cells, horizontal.cells);
+    }
+
+    #[test]
+    fn block() {
+        let block = life(4, 4, &[(1, 1), (2, 1), (1, 2), (2, 2)]);
+        let mut automaton = block.clone();
+
+        automaton.run(3);
+        assert_eq!(automaton.cells, block.cells);
+    }
+
+    #[test]
+    fn glider() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let mut automaton = life(8, 8, &glider);
+
+        automaton.run(4);
+        assert_eq!(
+            automaton.cells,
+            life(8, 8, &glider.map(|(x, y)| (x + 1, y + 1))).cells
+        );
+
+        // Through the border and back to the start
+        automaton.run(28);
+        assert_eq!(automaton.cells, life(8, 8, &glider).cells);
+    }
+
+    #[test]
+    fn rule_round_trip() {
+        assert_eq!("B3/S23".parse(), Ok(Rule::LIFE));
+        assert_eq!("s23/b3".parse(), Ok(Rule::LIFE));
+
+        for rule in [
+            Rule::LIFE,
+            Rule::CAVES,
+            Rule::new(&[2], &[]).with_states(3),
+            Rule::new(&[0, 8], &[1]),
+        ] {
+            assert_eq!(rule.to_string().parse(), Ok(rule));
+        }
+
+        assert_eq!(Rule::new(&[2], &[]).with_states(3).to_string(), "B2/S/C3");
+        assert_eq!("B39/S23".parse::<Rule>(), Err(RuleError::InvalidCount('9')));
+        assert_eq!(
+            "B3/S23/C1".parse::<Rule>(),
+            Err(RuleError::InvalidStates("1".to_string()))
+        );
+        assert_eq!(
+            "B3/X23".parse::<Rule>(),
+            Err(RuleError::UnknownPart("X23".to_string()))
+        );
+    }
+}
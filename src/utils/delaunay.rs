@@ -0,0 +1,122 @@
+use bevy::math::{DVec2, Vec2};
+
+/// Delaunay triangulation of `points`, with the Bowyer-Watson algorithm.
+///
+/// Triangles are indices into `points`, in counter-clockwise order. Fewer than 3 points, or points
+/// all on a line, give no triangles.
+pub fn triangulate(points: &[Vec2]) -> Vec<[usize; 3]> {
+    if points.len() < 3 {
+        return Vec::new();
+    }
+
+    let mut vertices: Vec<DVec2> = points.iter().map(|point| point.as_dvec2()).collect();
+
+    // Triangle around every point, removed at the end
+    let (min, max) = vertices
+        .iter()
+        .fold((DVec2::MAX, DVec2::MIN), |(min, max), &point| {
+            (min.min(point), max.max(point))
+        });
+    let center = (min + max) / 2.;
+    let extent = (max - min).max_element().max(1.) * 20.;
+    let first_super = vertices.len();
+    vertices.extend([
+        center + DVec2::new(-extent, -extent),
+        center + DVec2::new(extent, -extent),
+        center + DVec2::new(0., extent),
+    ]);
+
+    let mut triangles = vec![Triangle::new(
+        [first_super, first_super + 1, first_super + 2],
+        &vertices,
+    )];
+
+    for (index, &point) in vertices[..first_super].iter().enumerate() {
+        let (bad, good): (Vec<Triangle>, Vec<Triangle>) = triangles
+            .into_iter()
+            .partition(|triangle| triangle.circumcircle_contains(point));
+        triangles = good;
+
+        // Edges of the hole left by the bad triangles are the ones only one of them has
+        let edges: Vec<(usize, usize)> = bad.iter().flat_map(Triangle::edges).collect();
+        for &(a, b) in &edges {
+            let shared = edges
+                .iter()
+                .filter(|&&(c, d)| (a, b) == (c, d) || (a, b) == (d, c))
+                .count()
+                > 1;
+
+            if !shared {
+                triangles.push(Triangle::new([a, b, index], &vertices));
+            }
+        }
+    }
+
+    triangles
+        .into_iter()
+        .filter(|triangle| triangle.vertices.iter().all(|&v| v < first_super))
+        .map(|triangle| triangle.vertices)
+        .collect()
+}
+
+/// Edges of `triangles`, each once, with the lower index first.
+pub fn edges(triangles: &[[usize; 3]]) -> Vec<(usize, usize)> {
+    let mut edges: Vec<(usize, usize)> = triangles
+        .iter()
+        .flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)])
+        .map(|(a, b)| (a.min(b), a.max(b)))
+        .collect();
+
+    edges.sort_unstable();
+    edges.dedup();
+    edges
+}
+
+struct Triangle {
+    vertices: [usize; 3],
+    circumcenter: DVec2,
+    radius_squared: f64,
+}
+
+impl Triangle {
+    fn new(vertices: [usize; 3], points: &[DVec2]) -> Self {
+        let [mut a, mut b, c] = vertices;
+        let (pa, pb, pc) = (points[a], points[b], points[c]);
+
+        // Keep them counter-clockwise
+        if (pb - pa).perp_dot(pc - pa) < 0. {
+            std::mem::swap(&mut a, &mut b);
+        }
+
+        let d = 2. * (pa.x * (pb.y - pc.y) + pb.x * (pc.y - pa.y) + pc.x * (pa.y - pb.y));
+        let circumcenter = if d.abs() < f64::EPSILON {
+            // Degenerate, its circle contains nothing
+            DVec2::splat(f64::INFINITY)
+        } else {
+            let (la, lb, lc) = (
+                pa.length_squared(),
+                pb.length_squared(),
+                pc.length_squared(),
+            );
+            DVec2::new(
+                la * (pb.y - pc.y) + lb * (pc.y - pa.y) + lc * (pa.y - pb.y),
+                la * (pc.x - pb.x) + lb * (pa.x - pc.x) + lc * (pb.x - pa.x),
+            ) / d
+        };
+
+        Triangle {
+            vertices: [a, b, c],
+            circumcenter,
+            radius_squared: circumcenter.distance_squared(pa),
+        }
+    }
+
+    fn circumcircle_contains(&self, point: DVec2) -> bool {
+        self.circumcenter.distance_squared(point) < self.radius_squared
+    }
+
+    fn edges(&self) -> [(usize, usize); 3] {
+        let [a, b, c] = self.vertices;
+        [(a, b), (b, c), (c, a)]
+    }
+}
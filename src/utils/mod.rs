@@ -1 +1,7 @@
-pub mod noise;
\ No newline at end of file
+pub mod automaton;
+pub mod delaunay;
+pub mod noise;
+pub mod pathfinding;
+pub mod sampling;
+pub mod seed;
+pub mod wfc;
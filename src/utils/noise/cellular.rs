@@ -1,10 +1,21 @@
 use std::f32::consts::SQRT_2;
 
 use rand::prelude::*;
-use rand::rngs::StdRng;
+use rand_chacha::ChaCha8Rng;
 
 use super::Noise;
 
+/// Random point of a cell of a `Cellular` noise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FeaturePoint {
+    /// Identifies the point, the same for every input close to it.
+    pub index: usize,
+    /// Position in the coordinates of the input.
+    pub position: (f32, f32),
+    /// Distance from the input.
+    pub distance: f32,
+}
+
 pub struct Cellular<T> {
     width: u64,
     height: u64,
@@ -15,7 +26,7 @@ impl Cellular<(f32, f32)> {
     pub fn new(width: u64, height: u64, seed: Option<u64>) -> Self {
         let seed = seed.unwrap_or(0);
 
-        let mut rng = StdRng::seed_from_u64(seed);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
 
         let points = (0..((width + 2) * (height + 2)))
             .map(|_| (rng.gen::<f32>(), rng.gen::<f32>()))
@@ -27,6 +38,41 @@ impl Cellular<(f32, f32)> {
             points,
         }
     }
+
+    /// Number of feature points, the largest `FeaturePoint::index` is one less.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Feature points of the cells around `input`, the closest first. Like the cells of a
+    /// Voronoi diagram, the first one tells which cell the input is in.
+    pub fn closest_points(&self, input: (f32, f32)) -> [FeaturePoint; 9] {
+        let in_x = input.0.floor() as u64 % self.width;
+        let in_y = input.1.floor() as u64 % self.height;
+
+        let mut points = std::array::from_fn(|i| {
+            let (x, y) = (i as u64 % 3, i as u64 / 3);
+            let index = ((in_y + y) * (self.width + 2) + in_x + x) as usize;
+            let (ox, oy) = self.points[index];
+            let position = (
+                input.0.floor() + x as f32 + ox - 1.,
+                input.1.floor() + y as f32 + oy - 1.,
+            );
+
+            FeaturePoint {
+                index,
+                position,
+                distance: (position.0 - input.0).hypot(position.1 - input.1),
+            }
+        });
+
+        points.sort_by(|a: &FeaturePoint, b| a.distance.total_cmp(&b.distance));
+        points
+    }
 }
 
 impl Noise for Cellular<(f32, f32)> {
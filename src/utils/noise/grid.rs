@@ -0,0 +1,108 @@
+use bevy::math::FloatExt;
+
+use crate::terrain::height_map::NOISE_EXTENT;
+
+use super::Noise;
+
+/// Grid of values sampled with bilinear interpolation, like a height map loaded from a file.
+/// Input coordinates go from 0 to `NOISE_EXTENT` across the grid, so it covers a whole
+/// `HeightMap`, and positions outside of the grid are clamped to its border.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    width: usize,
+    depth: usize,
+    /// Row major, `values[z * width + x]`.
+    values: Vec<f32>,
+    /// Input coordinates of the last column and row.
+    extent: (f32, f32),
+}
+
+impl Grid {
+    /// Panics if `values` doesn't have `width * depth` elements or the grid is empty.
+    pub fn new(width: usize, depth: usize, values: Vec<f32>) -> Self {
+        assert!(width > 0 && depth > 0, "Grid can't be empty");
+        assert_eq!(
+            values.len(),
+            width * depth,
+            "Grid of {width}x{depth} needs {} values",
+            width * depth
+        );
+
+        Grid {
+            width,
+            depth,
+            values,
+            extent: (NOISE_EXTENT, NOISE_EXTENT),
+        }
+    }
+
+    /// Makes input coordinates go from 0 to `width` and `depth` across the grid instead.
+    pub fn with_extent(self, width: f32, depth: f32) -> Self {
+        Grid {
+            extent: (width, depth),
+            ..self
+        }
+    }
+
+    pub fn extent(&self) -> (f32, f32) {
+        self.extent
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    pub fn value(&self, x: usize, z: usize) -> f32 {
+        self.values[z * self.width + x]
+    }
+
+    /// Smallest and largest values of the grid.
+    pub fn range(&self) -> (f32, f32) {
+        self.values
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| {
+                (min.min(value), max.max(value))
+            })
+    }
+
+    /// Rescales the values to [0, 1], like the output of the other noises.
+    pub fn normalized(&self) -> Grid {
+        let (min, max) = self.range();
+        let range = if max > min { max - min } else { 1. };
+
+        Grid {
+            values: self
+                .values
+                .iter()
+                .map(|value| (value - min) / range)
+                .collect(),
+            ..*self
+        }
+    }
+}
+
+impl Noise for Grid {
+    type Input = (f32, f32);
+    type Output = f32;
+
+    fn get(&self, input: (f32, f32)) -> f32 {
+        let (last_x, last_z) = ((self.width - 1) as f32, (self.depth - 1) as f32);
+        let fx = (input.0 / self.extent.0 * last_x).clamp(0., last_x);
+        let fz = (input.1 / self.extent.1 * last_z).clamp(0., last_z);
+        let (x0, z0) = (fx.floor() as usize, fz.floor() as usize);
+        let (x1, z1) = ((x0 + 1).min(self.width - 1), (z0 + 1).min(self.depth - 1));
+        let (tx, tz) = (fx - x0 as f32, fz - z0 as f32);
+
+        self.value(x0, z0)
+            .lerp(self.value(x1, z0), tx)
+            .lerp(self.value(x0, z1).lerp(self.value(x1, z1), tx), tz)
+    }
+}
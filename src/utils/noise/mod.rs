@@ -3,7 +3,10 @@ use map::Map;
 pub mod map;
 
 pub mod cellular;
+pub mod grid;
 pub mod perlin;
+pub mod perlin_3d;
+pub mod reaction_diffusion;
 #[allow(dead_code)]
 pub mod value;
 
@@ -1,5 +1,6 @@
 use bevy::{math::FloatExt, prelude::Vec2};
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 
 use super::Noise;
 pub struct Perlin {
@@ -12,7 +13,7 @@ impl Perlin {
     pub fn new(layers: &[(f32, f32)], wrap: usize, seed: Option<u64>) -> Self {
         let seed = seed.unwrap_or(0);
         let mut permutation: Vec<usize> = (0..wrap).collect();
-        let mut rng = StdRng::seed_from_u64(seed);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
         permutation.shuffle(&mut rng);
 
         permutation.append(&mut permutation.clone());
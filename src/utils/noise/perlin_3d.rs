@@ -0,0 +1,104 @@
+use bevy::{math::FloatExt, prelude::Vec3};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use super::{perlin::fade, Noise};
+
+/// Three dimensional version of `Perlin`, used for volumetric features like caves.
+pub struct Perlin3D {
+    permutation: Vec<usize>,
+    wrap: usize,
+    layers: Vec<(f32, f32)>,
+}
+
+impl Perlin3D {
+    pub fn new(layers: &[(f32, f32)], wrap: usize, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or(0);
+        let mut permutation: Vec<usize> = (0..wrap).collect();
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        permutation.shuffle(&mut rng);
+
+        permutation.append(&mut permutation.clone());
+
+        let infl_sum: f32 = layers.iter().map(|(weight, _)| weight).sum();
+        let layers = layers
+            .iter()
+            .map(|(weight, compression_factor)| (weight / infl_sum, *compression_factor))
+            .collect();
+
+        Perlin3D {
+            permutation,
+            wrap,
+            layers,
+        }
+    }
+
+    fn hash(&self, x: usize, y: usize, z: usize) -> usize {
+        self.permutation[self.permutation[self.permutation[x] + y] + z]
+    }
+
+    // Directions to the edges of a cube, as in improved Perlin noise
+    fn get_constant_vector(v: usize) -> Vec3 {
+        match v % 12 {
+            0 => Vec3::new(1., 1., 0.),
+            1 => Vec3::new(-1., 1., 0.),
+            2 => Vec3::new(1., -1., 0.),
+            3 => Vec3::new(-1., -1., 0.),
+            4 => Vec3::new(1., 0., 1.),
+            5 => Vec3::new(-1., 0., 1.),
+            6 => Vec3::new(1., 0., -1.),
+            7 => Vec3::new(-1., 0., -1.),
+            8 => Vec3::new(0., 1., 1.),
+            9 => Vec3::new(0., -1., 1.),
+            10 => Vec3::new(0., 1., -1.),
+            _ => Vec3::new(0., -1., -1.),
+        }
+    }
+}
+
+impl Noise for Perlin3D {
+    type Input = (f32, f32, f32);
+    type Output = f32;
+
+    fn get(&self, input: (f32, f32, f32)) -> f32 {
+        let mut value = 0.;
+
+        for (weight, compression_factor) in &self.layers {
+            let x = input.0 * compression_factor;
+            let y = input.1 * compression_factor;
+            let z = input.2 * compression_factor;
+            let xf = x - x.floor();
+            let yf = y - y.floor();
+            let zf = z - z.floor();
+            let x = x.floor() as i64 as usize & (self.wrap - 1);
+            let y = y.floor() as i64 as usize & (self.wrap - 1);
+            let z = z.floor() as i64 as usize & (self.wrap - 1);
+
+            let corner = |dx: usize, dy: usize, dz: usize| {
+                let offset = Vec3::new(xf - dx as f32, yf - dy as f32, zf - dz as f32);
+
+                offset.dot(Perlin3D::get_constant_vector(self.hash(
+                    x + dx,
+                    y + dy,
+                    z + dz,
+                )))
+            };
+
+            let u = fade(xf);
+            let v = fade(yf);
+            let w = fade(zf);
+
+            let bottom = corner(0, 0, 0)
+                .lerp(corner(1, 0, 0), u)
+                .lerp(corner(0, 1, 0).lerp(corner(1, 1, 0), u), v);
+            let top = corner(0, 0, 1)
+                .lerp(corner(1, 0, 1), u)
+                .lerp(corner(0, 1, 1).lerp(corner(1, 1, 1), u), v);
+
+            value += bottom.lerp(top, w) * weight;
+        }
+
+        // Normalized to [0, 1]
+        ((1_f32 + value) / 2_f32).clamp(0., 1.)
+    }
+}
@@ -0,0 +1,261 @@
+use bevy::math::FloatExt;
+use rand::Rng;
+
+use crate::{terrain::height_map::NOISE_EXTENT, utils::seed::Seed};
+
+use super::{grid::Grid, Noise};
+
+/// Where the second chemical is added before the simulation starts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Seeding {
+    /// A square in the middle, with sides of `size` cells.
+    Center { size: usize },
+    /// Squares with sides of `size` cells at random places.
+    Spots { count: usize, size: usize },
+    /// Each cell with a chance of `density`.
+    Scattered { density: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrayScottSettings {
+    pub width: usize,
+    pub height: usize,
+    /// Rate at which the first chemical is added.
+    pub feed: f32,
+    /// Rate at which the second chemical is removed.
+    pub kill: f32,
+    pub diffusion_a: f32,
+    pub diffusion_b: f32,
+    pub time_step: f32,
+    pub iterations: usize,
+    pub seeding: Seeding,
+}
+
+impl Default for GrayScottSettings {
+    fn default() -> Self {
+        GrayScottSettings {
+            width: 128,
+            height: 128,
+            feed: 0.0545,
+            kill: 0.062,
+            diffusion_a: 1.,
+            diffusion_b: 0.5,
+            time_step: 1.,
+            iterations: 4000,
+            seeding: Seeding::Spots { count: 12, size: 6 },
+        }
+    }
+}
+
+impl GrayScottSettings {
+    /// Branches that grow and fill the space.
+    pub fn coral() -> Self {
+        GrayScottSettings::default()
+    }
+
+    /// Spots that split and spread, like animal skins.
+    pub fn spots() -> Self {
+        GrayScottSettings {
+            feed: 0.0367,
+            kill: 0.0649,
+            ..GrayScottSettings::default()
+        }
+    }
+
+    /// Winding lines, like lichen.
+    pub fn maze() -> Self {
+        GrayScottSettings {
+            feed: 0.029,
+            kill: 0.057,
+            ..GrayScottSettings::default()
+        }
+    }
+}
+
+/// Gray-Scott reaction-diffusion of two chemicals on a grid that wraps around.
+///
+/// As a noise it samples the concentration of the second chemical, rescaled to [0, 1]. Input
+/// coordinates go from 0 to `NOISE_EXTENT` across the grid, so it covers a whole `HeightMap`,
+/// and wrap around beyond it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GrayScott {
+    settings: GrayScottSettings,
+    a: Vec<f32>,
+    b: Vec<f32>,
+    next_a: Vec<f32>,
+    next_b: Vec<f32>,
+    /// Smallest and largest concentration of `b`.
+    range: (f32, f32),
+}
+
+impl GrayScott {
+    /// Seeds the grid and runs `settings.iterations` steps.
+    pub fn new(settings: GrayScottSettings, seed: Seed) -> Self {
+        let cells = settings.width.max(1) * settings.height.max(1);
+        let settings = GrayScottSettings {
+            width: settings.width.max(1),
+            height: settings.height.max(1),
+            ..settings
+        };
+
+        let mut simulation = GrayScott {
+            settings,
+            a: vec![1.; cells],
+            b: vec![0.; cells],
+            next_a: vec![0.; cells],
+            next_b: vec![0.; cells],
+            range: (0., 0.),
+        };
+
+        simulation.seed(seed);
+        simulation.run(settings.iterations);
+        simulation
+    }
+
+    pub fn settings(&self) -> &GrayScottSettings {
+        &self.settings
+    }
+
+    fn seed(&mut self, seed: Seed) {
+        let GrayScottSettings { width, height, .. } = self.settings;
+        let mut rng = seed.rng();
+
+        let square = |x: usize, y: usize, size: usize, b: &mut Vec<f32>| {
+            for dy in 0..size {
+                for dx in 0..size {
+                    b[(x + dx) % width + (y + dy) % height * width] = 1.;
+                }
+            }
+        };
+
+        match self.settings.seeding {
+            Seeding::Center { size } => square(
+                width.saturating_sub(size) / 2,
+                height.saturating_sub(size) / 2,
+                size,
+                &mut self.b,
+            ),
+            Seeding::Spots { count, size } => {
+                for _ in 0..count {
+                    let (x, y) = (rng.gen_range(0..width), rng.gen_range(0..height));
+                    square(x, y, size, &mut self.b);
+                }
+            }
+            Seeding::Scattered { density } => {
+                for b in &mut self.b {
+                    if rng.gen::<f32>() < density {
+                        *b = 1.;
+                    }
+                }
+            }
+        }
+
+        self.update_range();
+    }
+
+    pub fn step(&mut self) {
+        let GrayScottSettings {
+            width,
+            height,
+            feed,
+            kill,
+            diffusion_a,
+            diffusion_b,
+            time_step,
+            ..
+        } = self.settings;
+
+        for y in 0..height {
+            let (up, down) = ((y + height - 1) % height, (y + 1) % height);
+
+            for x in 0..width {
+                let (left, right) = ((x + width - 1) % width, (x + 1) % width);
+                let index = x + y * width;
+
+                // Weights of the 3x3 kernel, which add up to 0
+                let laplacian = |values: &[f32]| {
+                    let sides = values[left + y * width]
+                        + values[right + y * width]
+                        + values[x + up * width]
+                        + values[x + down * width];
+                    let corners = values[left + up * width]
+                        + values[right + up * width]
+                        + values[left + down * width]
+                        + values[right + down * width];
+
+                    sides * 0.2 + corners * 0.05 - values[index]
+                };
+
+                let (a, b) = (self.a[index], self.b[index]);
+                let reaction = a * b * b;
+
+                self.next_a[index] = (a
+                    + (diffusion_a * laplacian(&self.a) - reaction + feed * (1. - a)) * time_step)
+                    .clamp(0., 1.);
+                self.next_b[index] = (b
+                    + (diffusion_b * laplacian(&self.b) + reaction - (kill + feed) * b)
+                        * time_step)
+                    .clamp(0., 1.);
+            }
+        }
+
+        std::mem::swap(&mut self.a, &mut self.next_a);
+        std::mem::swap(&mut self.b, &mut self.next_b);
+        self.update_range();
+    }
+
+    pub fn run(&mut self, iterations: usize) {
+        for _ in 0..iterations {
+            self.step();
+        }
+    }
+
+    /// Concentration of the second chemical at a cell, between 0 and 1.
+    pub fn concentration(&self, x: usize, y: usize) -> f32 {
+        self.b[x % self.settings.width + y % self.settings.height * self.settings.width]
+    }
+
+    /// Concentrations of the second chemical, rescaled to [0, 1].
+    pub fn texture(&self) -> Grid {
+        Grid::new(self.settings.width, self.settings.height, self.b.clone()).normalized()
+    }
+
+    fn update_range(&mut self) {
+        self.range = self
+            .b
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| {
+                (min.min(value), max.max(value))
+            });
+    }
+}
+
+impl Noise for GrayScott {
+    type Input = (f32, f32);
+    type Output = f32;
+
+    fn get(&self, input: (f32, f32)) -> f32 {
+        let (width, height) = (self.settings.width, self.settings.height);
+        let fx = (input.0 / NOISE_EXTENT * width as f32).rem_euclid(width as f32);
+        let fy = (input.1 / NOISE_EXTENT * height as f32).rem_euclid(height as f32);
+        let (x0, y0) = (fx.floor() as usize, fy.floor() as usize);
+        let (x1, y1) = (x0 + 1, y0 + 1);
+        let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+
+        let value = self
+            .concentration(x0, y0)
+            .lerp(self.concentration(x1, y0), tx)
+            .lerp(
+                self.concentration(x0, y1)
+                    .lerp(self.concentration(x1, y1), tx),
+                ty,
+            );
+
+        let (min, max) = self.range;
+        if max > min {
+            (value - min) / (max - min)
+        } else {
+            0.
+        }
+    }
+}
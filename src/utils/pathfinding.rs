@@ -0,0 +1,100 @@
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, BinaryHeap, HashMap},
+    hash::Hash,
+};
+
+/// Cheapest path from `start` to any node for which `is_goal` is true, with the A* algorithm.
+///
+/// `neighbours` gives the nodes reachable from a node with the cost of going there, which can't
+/// be negative. `heuristic` estimates the cost left to a goal, and must never be higher than
+/// the real one for the path to be the cheapest. Returns the nodes of the path, from `start` to
+/// the goal, and its cost.
+pub fn astar<N, I>(
+    start: N,
+    is_goal: impl Fn(&N) -> bool,
+    mut neighbours: impl FnMut(&N) -> I,
+    heuristic: impl Fn(&N) -> f32,
+) -> Option<(Vec<N>, f32)>
+where
+    N: Clone + Eq + Hash,
+    I: IntoIterator<Item = (N, f32)>,
+{
+    // Best known cost of each node and the node it's reached from
+    let mut visited: HashMap<N, (f32, Option<N>)> = HashMap::from([(start.clone(), (0., None))]);
+    let mut open = BinaryHeap::from([Open {
+        estimate: heuristic(&start),
+        cost: 0.,
+        node: start,
+    }]);
+
+    while let Some(Open { cost, node, .. }) = open.pop() {
+        // Already reached more cheaply since it was queued
+        if cost > visited[&node].0 {
+            continue;
+        }
+
+        if is_goal(&node) {
+            let mut path = vec![node];
+            while let Some(previous) = visited[path.last().unwrap()].1.clone() {
+                path.push(previous);
+            }
+            path.reverse();
+
+            return Some((path, cost));
+        }
+
+        for (next, step_cost) in neighbours(&node) {
+            let next_cost = cost + step_cost;
+
+            match visited.entry(next.clone()) {
+                Entry::Occupied(entry) if entry.get().0 <= next_cost => continue,
+                Entry::Occupied(mut entry) => {
+                    entry.insert((next_cost, Some(node.clone())));
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert((next_cost, Some(node.clone())));
+                }
+            }
+
+            open.push(Open {
+                estimate: next_cost + heuristic(&next),
+                cost: next_cost,
+                node: next,
+            });
+        }
+    }
+
+    None
+}
+
+/// Node waiting to be expanded, the one with the lowest estimate first.
+struct Open<N> {
+    estimate: f32,
+    cost: f32,
+    node: N,
+}
+
+impl<N> PartialEq for Open<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<N> Eq for Open<N> {}
+
+impl<N> PartialOrd for Open<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N> Ord for Open<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, the heap pops the largest. Ties go to the most advanced path
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then(self.cost.total_cmp(&other.cost))
+    }
+}
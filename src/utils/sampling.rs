@@ -0,0 +1,227 @@
+use std::f32::consts::{SQRT_2, TAU};
+
+use bevy::math::{FloatExt, IVec2, Rect, UVec2, Vec2};
+use rand::Rng;
+
+use super::{noise::Noise, seed::Seed};
+
+/// Candidates tried around each active point before it's retired, as suggested by Bridson.
+pub const DEFAULT_ATTEMPTS: usize = 30;
+
+/// Points inside `region` no closer than `radius` to each other, spread evenly without visible
+/// patterns (Bridson's Poisson-disk sampling).
+pub fn poisson_disk(region: Rect, radius: f32, rng: &mut impl Rng) -> Vec<Vec2> {
+    bridson(region, radius, radius, |_| radius, DEFAULT_ATTEMPTS, rng)
+}
+
+/// Like `poisson_disk`, with the distance between points driven by `density`.
+/// Where `density` is 1 points are `min_radius` apart, where it's 0 they are `max_radius` apart.
+pub fn poisson_disk_variable<N: Noise<Input = (f32, f32), Output = f32>>(
+    region: Rect,
+    min_radius: f32,
+    max_radius: f32,
+    density: &N,
+    rng: &mut impl Rng,
+) -> Vec<Vec2> {
+    bridson(
+        region,
+        min_radius,
+        max_radius,
+        |point| max_radius.lerp(min_radius, density.get(point.into()).clamp(0., 1.)),
+        DEFAULT_ATTEMPTS,
+        rng,
+    )
+}
+
+/// One point per `cell_size` square of `region`, moved randomly inside its cell.
+/// `jitter` goes from 0 (cell centers) to 1 (anywhere in the cell).
+pub fn jittered_grid(region: Rect, cell_size: f32, jitter: f32, rng: &mut impl Rng) -> Vec<Vec2> {
+    let cells = (region.size() / cell_size).ceil().as_uvec2();
+    let mut points = Vec::with_capacity((cells.x * cells.y) as usize);
+
+    for y in 0..cells.y {
+        for x in 0..cells.x {
+            let center = region.min + (UVec2::new(x, y).as_vec2() + 0.5) * cell_size;
+            let offset = Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5) * jitter;
+            let point = center + offset * cell_size;
+
+            if region.contains(point) {
+                points.push(point);
+            }
+        }
+    }
+
+    points
+}
+
+/// Poisson-disk points of the chunk at `chunk`, in world coordinates, for chunks of
+/// `chunk_size` starting at the origin.
+///
+/// Every chunk is sampled on its own with a seed derived from its coordinates. Points closer
+/// than `radius` to a point of a neighbouring chunk with a higher priority are then dropped, and
+/// since the points of the neighbours can be generated again at any time, both chunks agree on
+/// which points remain. When `radius` is larger than `chunk_size`, chunks farther away than the
+/// direct neighbours are checked too.
+pub fn chunk_poisson_disk(seed: Seed, chunk: IVec2, chunk_size: f32, radius: f32) -> Vec<Vec2> {
+    if chunk_size <= 0. || radius <= 0. {
+        return Vec::new();
+    }
+
+    let candidates = |chunk: IVec2| {
+        let min = chunk.as_vec2() * chunk_size;
+        let region = Rect::from_corners(min, min + chunk_size);
+        let mut rng = seed.derive_chunk(chunk.x, chunk.y).rng();
+
+        poisson_disk(region, radius, &mut rng)
+    };
+    // Stable, but arbitrary, order between chunks
+    let priority = |chunk: IVec2| seed.derive("priority").derive_chunk(chunk.x, chunk.y);
+
+    let own_priority = priority(chunk);
+    // Chunks that can hold points closer than `radius` to this one
+    let reach = (radius / chunk_size).ceil() as i32;
+    let stronger_neighbours: Vec<Vec2> = (-reach..=reach)
+        .flat_map(|x| (-reach..=reach).map(move |y| chunk + IVec2::new(x, y)))
+        .filter(|&neighbour| neighbour != chunk && priority(neighbour).0 > own_priority.0)
+        .flat_map(candidates)
+        .collect();
+
+    candidates(chunk)
+        .into_iter()
+        .filter(|point| {
+            stronger_neighbours
+                .iter()
+                .all(|other| point.distance_squared(*other) >= radius * radius)
+        })
+        .collect()
+}
+
+/// Bridson's algorithm with a radius per point. Two points are at least as far apart as the
+/// larger of their radii, which are between `min_radius` and `max_radius`.
+fn bridson(
+    region: Rect,
+    min_radius: f32,
+    max_radius: f32,
+    radius_at: impl Fn(Vec2) -> f32,
+    attempts: usize,
+    rng: &mut impl Rng,
+) -> Vec<Vec2> {
+    if region.is_empty() || min_radius <= 0. {
+        return Vec::new();
+    }
+
+    // Small enough that a cell can't hold two points
+    let cell_size = min_radius / SQRT_2;
+    let cells = (region.size() / cell_size)
+        .ceil()
+        .as_ivec2()
+        .max(IVec2::ONE);
+    let search = (max_radius / cell_size).ceil() as i32;
+    let mut grid: Vec<Option<usize>> = vec![None; (cells.x * cells.y) as usize];
+
+    let cell_of = |point: Vec2| {
+        ((point - region.min) / cell_size)
+            .as_ivec2()
+            .clamp(IVec2::ZERO, cells - 1)
+    };
+
+    let cell_index = |cell: IVec2| (cell.y * cells.x + cell.x) as usize;
+
+    let first = region.min + Vec2::new(rng.gen(), rng.gen()) * region.size();
+    let mut points = vec![(first, radius_at(first))];
+    let mut active = vec![0];
+    grid[cell_index(cell_of(first))] = Some(0);
+
+    while !active.is_empty() {
+        let active_index = rng.gen_range(0..active.len());
+        let (center, center_radius) = points[active[active_index]];
+        let mut found = false;
+
+        for _ in 0..attempts {
+            // Uniform over the area of the annulus between the radius and twice the radius
+            let distance = center_radius * (1. + 3. * rng.gen::<f32>()).sqrt();
+            let candidate = center + Vec2::from_angle(rng.gen::<f32>() * TAU) * distance;
+
+            if !region.contains(candidate) {
+                continue;
+            }
+
+            let candidate_radius = radius_at(candidate);
+            let cell = cell_of(candidate);
+            let min_cell = (cell - search).max(IVec2::ZERO);
+            let max_cell = (cell + search).min(cells - 1);
+
+            let far_enough = (min_cell.y..=max_cell.y).all(|y| {
+                (min_cell.x..=max_cell.x).all(|x| match grid[cell_index(IVec2::new(x, y))] {
+                    Some(other) => {
+                        let (other, other_radius) = points[other];
+                        candidate.distance(other) >= candidate_radius.max(other_radius)
+                    }
+                    None => true,
+                })
+            });
+
+            if far_enough {
+                grid[cell_index(cell)] = Some(points.len());
+                active.push(points.len());
+                points.push((candidate, candidate_radius));
+                found = true;
+                break;
+            }
+        }
+
+        if !found {
+            active.swap_remove(active_index);
+        }
+    }
+
+    points.into_iter().map(|(point, _)| point).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points of a 4x4 block of chunks for a few seeds, and the distance between the closest
+    /// two points of the same seed.
+    fn closest_distance(chunk_size: f32, radius: f32) -> (usize, f32) {
+        (0..8)
+            .map(|seed| block_closest_distance(Seed(seed), chunk_size, radius))
+            .fold(
+                (0, f32::INFINITY),
+                |(total, closest), (points, distance)| (total + points, closest.min(distance)),
+            )
+    }
+
+    fn block_closest_distance(seed: Seed, chunk_size: f32, radius: f32) -> (usize, f32) {
+        let points: Vec<Vec2> = (0..4)
+            .flat_map(|x| (0..4).map(move |y| IVec2::new(x, y)))
+            .flat_map(|chunk| chunk_poisson_disk(seed, chunk, chunk_size, radius))
+            .collect();
+
+        let closest = points
+            .iter()
+            .enumerate()
+            .flat_map(|(i, a)| points[i + 1..].iter().map(move |b| a.distance(*b)))
+            .fold(f32::INFINITY, f32::min);
+
+        (points.len(), closest)
+    }
+
+    #[test]
+    fn chunks_agree_on_borders() {
+        let (points, closest) = closest_distance(10., 2.);
+
+        assert!(points > 0);
+        assert!(closest >= 2.);
+    }
+
+    #[test]
+    fn radius_larger_than_chunks() {
+        let (points, closest) = closest_distance(2., 5.);
+
+        assert!(points > 0);
+        assert!(closest >= 5.);
+        assert!(chunk_poisson_disk(Seed(7), IVec2::ZERO, 0., 5.).is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Seed that other, independent seeds can be derived from.
+///
+/// Derivation only uses FNV-1a and SplitMix64, and random numbers come from ChaCha8, whose
+/// results are all fixed, so the same world seed gives the same streams on every platform and
+/// compiler version.
+///
+/// ```ignore
+/// let biomes = world_seed.derive("biomes");
+/// let chunk = biomes.derive_chunk(3, -2);
+/// let noise = Perlin::new(&layers, 256, Some(chunk.value()));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Seed(pub u64);
+
+impl Seed {
+    /// Seed typed by a player. Numbers are used as they are, any other text is hashed.
+    pub fn from_text(text: &str) -> Seed {
+        let text = text.trim();
+
+        text.parse()
+            .map(Seed)
+            .unwrap_or_else(|_| Seed(split_mix(fnv1a(text.as_bytes()))))
+    }
+
+    pub fn random() -> Seed {
+        Seed(rand::thread_rng().gen())
+    }
+
+    pub fn value(self) -> u64 {
+        self.0
+    }
+
+    /// Seed for a subsystem, like `"terrain"` or `"biomes"`.
+    pub fn derive(self, label: &str) -> Seed {
+        self.combine(fnv1a(label.as_bytes()))
+    }
+
+    /// Seed for the `index`th element of something, like the `index`th tree of a chunk.
+    pub fn derive_index(self, index: u64) -> Seed {
+        self.combine(index)
+    }
+
+    /// Seed for the chunk at `(x, z)`.
+    pub fn derive_chunk(self, x: i32, z: i32) -> Seed {
+        self.combine(x as u32 as u64).combine(z as u32 as u64)
+    }
+
+    /// Random number generator for this seed. Unlike `StdRng`, its algorithm won't change
+    /// between versions of `rand`.
+    pub fn rng(self) -> ChaCha8Rng {
+        ChaCha8Rng::seed_from_u64(self.0)
+    }
+
+    fn combine(self, value: u64) -> Seed {
+        Seed(split_mix(self.0 ^ split_mix(value)))
+    }
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Seed of the whole world, every generator derives its own seed from it.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Deref, DerefMut)]
+pub struct WorldSeed(pub Seed);
+
+impl WorldSeed {
+    /// Seed given to the game with `--seed <text>`, like to the `procgen` tool, or 0.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> WorldSeed {
+        while let Some(arg) = args.next() {
+            if arg == "--seed" {
+                if let Some(text) = args.next() {
+                    return WorldSeed(Seed::from_text(&text));
+                }
+            }
+        }
+
+        WorldSeed::default()
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Finalizer of SplitMix64. Small changes in the input change about half of the output bits.
+fn split_mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Worlds must come out the same on every version, so these values must never change.
+    #[test]
+    fn streams_are_stable() {
+        let seed = Seed::from_text("stable")
+            .derive("terrain")
+            .derive_chunk(3, -2);
+
+        assert_eq!(seed.value(), 8921939986885676358);
+        assert_eq!(seed.rng().gen::<u64>(), 9474983295761477371);
+    }
+
+    #[test]
+    fn seed_from_args() {
+        let args = |args: &[&str]| WorldSeed::from_args(args.iter().map(|arg| arg.to_string()));
+
+        assert_eq!(args(&["--seed", "42"]), WorldSeed(Seed(42)));
+        assert_eq!(
+            args(&["--fullscreen", "--seed", "hills"]),
+            WorldSeed(Seed::from_text("hills"))
+        );
+        assert_eq!(args(&["--seed"]), WorldSeed::default());
+        assert_eq!(args(&[]), WorldSeed::default());
+    }
+}
@@ -0,0 +1,483 @@
+use std::{collections::VecDeque, fmt};
+
+use bevy::math::{IVec3, UVec3};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use super::seed::Seed;
+
+pub mod overlapping;
+pub mod tiled;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Side of a cell. Y is up in 3D grids, and the rows of an image in 2D ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::PosX,
+        Direction::NegX,
+        Direction::PosY,
+        Direction::NegY,
+        Direction::PosZ,
+        Direction::NegZ,
+    ];
+
+    pub fn offset(self) -> IVec3 {
+        match self {
+            Direction::PosX => IVec3::X,
+            Direction::NegX => IVec3::NEG_X,
+            Direction::PosY => IVec3::Y,
+            Direction::NegY => IVec3::NEG_Y,
+            Direction::PosZ => IVec3::Z,
+            Direction::NegZ => IVec3::NEG_Z,
+        }
+    }
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::PosX => Direction::NegX,
+            Direction::NegX => Direction::PosX,
+            Direction::PosY => Direction::NegY,
+            Direction::NegY => Direction::PosY,
+            Direction::PosZ => Direction::NegZ,
+            Direction::NegZ => Direction::PosZ,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Weights of the patterns, and which ones can be next to each other.
+#[derive(Clone, Debug)]
+pub struct Rules {
+    weights: Vec<f32>,
+    /// `propagator[pattern][direction]` is a bitset of the patterns allowed on that side.
+    propagator: Vec<[Vec<u64>; 6]>,
+}
+
+impl Rules {
+    /// Rules where nothing can be next to anything, until allowed with `allow`.
+    pub fn new(weights: Vec<f32>) -> Self {
+        let words = weights.len().div_ceil(WORD_BITS);
+        let propagator = vec![std::array::from_fn(|_| vec![0; words]); weights.len()];
+
+        Rules {
+            weights,
+            propagator,
+        }
+    }
+
+    pub fn pattern_count(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /// Allows `neighbour` on the `direction` side of `pattern`, and `pattern` on the opposite side
+    /// of `neighbour`.
+    pub fn allow(&mut self, pattern: usize, direction: Direction, neighbour: usize) {
+        set_bit(&mut self.propagator[pattern][direction.index()], neighbour);
+        set_bit(
+            &mut self.propagator[neighbour][direction.opposite().index()],
+            pattern,
+        );
+    }
+
+    pub fn allows(&self, pattern: usize, direction: Direction, neighbour: usize) -> bool {
+        has_bit(&self.propagator[pattern][direction.index()], neighbour)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WfcSettings {
+    /// Cells along each axis. Grids with a depth of 1 are 2D and only use the X and Y directions.
+    pub size: UVec3,
+    /// Whether opposite borders of the grid are neighbours.
+    pub periodic: bool,
+    /// Times the grid is started over with a new seed after a contradiction it can't back out of.
+    pub attempts: usize,
+    /// Decisions remembered to undo after a contradiction. 0 restarts right away.
+    pub backtrack_depth: usize,
+}
+
+impl Default for WfcSettings {
+    fn default() -> Self {
+        WfcSettings {
+            size: UVec3::new(32, 32, 1),
+            periodic: false,
+            attempts: 10,
+            backtrack_depth: 32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WfcError {
+    /// The rules have no patterns.
+    NoPatterns,
+    /// Every attempt ended in a contradiction.
+    Contradiction { attempts: usize },
+}
+
+impl fmt::Display for WfcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WfcError::NoPatterns => write!(f, "there are no patterns to place"),
+            WfcError::Contradiction { attempts } => {
+                write!(f, "no solution found after {attempts} attempts")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WfcError {}
+
+/// Pattern of every cell of the grid, indexed by `x + width * (y + height * z)`.
+pub fn solve(rules: &Rules, settings: &WfcSettings, seed: Seed) -> Result<Vec<usize>, WfcError> {
+    if rules.pattern_count() == 0 {
+        return Err(WfcError::NoPatterns);
+    }
+
+    let attempts = settings.attempts.max(1);
+
+    for attempt in 0..attempts {
+        let mut solver = Solver::new(rules, settings, seed.derive_index(attempt as u64).rng());
+
+        if let Some(result) = solver.run() {
+            return Ok(result);
+        }
+    }
+
+    Err(WfcError::Contradiction { attempts })
+}
+
+/// Patterns still possible in every cell.
+struct Wave {
+    bits: Vec<u64>,
+    remaining: Vec<usize>,
+    // Kept up to date to get the entropy of a cell without going through its patterns
+    sum_weights: Vec<f32>,
+    sum_weight_logs: Vec<f32>,
+    entropies: Vec<f32>,
+}
+
+impl Wave {
+    fn update_entropy(&mut self, cell: usize) {
+        let sum = self.sum_weights[cell];
+        self.entropies[cell] = sum.ln() - self.sum_weight_logs[cell] / sum;
+    }
+}
+
+struct Solver<'a> {
+    rules: &'a Rules,
+    settings: &'a WfcSettings,
+    rng: ChaCha8Rng,
+    /// Small value added to the entropy of each cell, to break ties between cells with the same
+    /// patterns.
+    noise: Vec<f32>,
+    words: usize,
+    weight_logs: Vec<f32>,
+    directions: &'static [Direction],
+    wave: Wave,
+    /// Cells whose neighbours have to be checked.
+    pending: Vec<usize>,
+    is_pending: Vec<bool>,
+    /// Every pattern banned, in order, to undo decisions.
+    trail: Vec<(usize, usize)>,
+    /// Length of the trail before each recent decision, with the cell and pattern chosen.
+    history: VecDeque<(usize, usize, usize)>,
+}
+
+impl<'a> Solver<'a> {
+    fn new(rules: &'a Rules, settings: &'a WfcSettings, mut rng: ChaCha8Rng) -> Self {
+        let patterns = rules.pattern_count();
+        let cells = (settings.size.x * settings.size.y * settings.size.z) as usize;
+        let words = patterns.div_ceil(WORD_BITS);
+
+        let mut full = vec![u64::MAX; words];
+        let last_bits = patterns % WORD_BITS;
+        if last_bits > 0 {
+            full[words - 1] = (1 << last_bits) - 1;
+        }
+
+        let weight_logs: Vec<f32> = rules
+            .weights
+            .iter()
+            .map(|&weight| {
+                if weight > 0. {
+                    weight * weight.ln()
+                } else {
+                    0.
+                }
+            })
+            .collect();
+        let sum_weights: f32 = rules.weights.iter().sum();
+        let sum_weight_logs: f32 = weight_logs.iter().sum();
+
+        let directions: &'static [Direction] = if settings.size.z == 1 {
+            &Direction::ALL[..4]
+        } else {
+            &Direction::ALL
+        };
+
+        Solver {
+            rules,
+            settings,
+            noise: (0..cells).map(|_| rng.gen::<f32>() * 1e-4).collect(),
+            rng,
+            words,
+            weight_logs,
+            directions,
+            wave: Wave {
+                bits: full.repeat(cells),
+                remaining: vec![patterns; cells],
+                sum_weights: vec![sum_weights; cells],
+                sum_weight_logs: vec![sum_weight_logs; cells],
+                entropies: vec![sum_weights.ln() - sum_weight_logs / sum_weights; cells],
+            },
+            pending: Vec::new(),
+            is_pending: vec![false; cells],
+            trail: Vec::new(),
+            history: VecDeque::new(),
+        }
+    }
+
+    fn run(&mut self) -> Option<Vec<usize>> {
+        // Patterns that can't be next to anything in some direction can only be on the border
+        if !self.propagate_all() {
+            return None;
+        }
+
+        // Past this, starting over with another seed is usually faster
+        let mut backtracks_left = self.wave.remaining.len();
+
+        loop {
+            let Some(cell) = self.lowest_entropy_cell() else {
+                return Some(self.result());
+            };
+
+            let pattern = self.choose_pattern(cell);
+
+            if self.settings.backtrack_depth > 0 {
+                if self.history.len() == self.settings.backtrack_depth {
+                    self.history.pop_front();
+                }
+                self.history.push_back((self.trail.len(), cell, pattern));
+            }
+
+            let mut consistent = self.collapse(cell, pattern) && self.propagate();
+
+            while !consistent {
+                // Undo the last decision, and take that pattern out of the cell
+                if backtracks_left == 0 {
+                    return None;
+                }
+                backtracks_left -= 1;
+
+                let (trail_length, cell, pattern) = self.history.pop_back()?;
+                self.undo(trail_length);
+                self.clear_pending();
+                consistent = self.ban(cell, pattern) && self.propagate();
+            }
+        }
+    }
+
+    fn lowest_entropy_cell(&self) -> Option<usize> {
+        let mut lowest = None;
+        let mut lowest_entropy = f32::MAX;
+
+        for cell in 0..self.wave.remaining.len() {
+            if self.wave.remaining[cell] <= 1 {
+                continue;
+            }
+
+            let entropy = self.wave.entropies[cell] + self.noise[cell];
+
+            if entropy < lowest_entropy {
+                lowest_entropy = entropy;
+                lowest = Some(cell);
+            }
+        }
+
+        lowest
+    }
+
+    fn choose_pattern(&mut self, cell: usize) -> usize {
+        let mut choice = self.rng.gen::<f32>() * self.wave.sum_weights[cell];
+        let mut last = 0;
+
+        for pattern in self.patterns_of(cell) {
+            choice -= self.rules.weights[pattern];
+            last = pattern;
+
+            if choice < 0. {
+                break;
+            }
+        }
+
+        last
+    }
+
+    fn patterns_of(&self, cell: usize) -> impl Iterator<Item = usize> + '_ {
+        let words = &self.wave.bits[cell * self.words..(cell + 1) * self.words];
+
+        words
+            .iter()
+            .enumerate()
+            .flat_map(|(index, &word)| set_bits(word).map(move |bit| index * WORD_BITS + bit))
+    }
+
+    /// Bans every pattern of `cell` but `pattern`.
+    fn collapse(&mut self, cell: usize, pattern: usize) -> bool {
+        let others: Vec<usize> = self.patterns_of(cell).filter(|&p| p != pattern).collect();
+
+        others.into_iter().all(|other| self.ban(cell, other))
+    }
+
+    /// Removes `pattern` from `cell`. Returns false when the cell has no patterns left.
+    fn ban(&mut self, cell: usize, pattern: usize) -> bool {
+        let word = &mut self.wave.bits[cell * self.words + pattern / WORD_BITS];
+        let mask = 1 << (pattern % WORD_BITS);
+
+        if *word & mask == 0 {
+            return self.wave.remaining[cell] > 0;
+        }
+
+        *word &= !mask;
+        self.wave.remaining[cell] -= 1;
+        self.wave.sum_weights[cell] -= self.rules.weights[pattern];
+        self.wave.sum_weight_logs[cell] -= self.weight_logs[pattern];
+        self.wave.update_entropy(cell);
+        if !self.is_pending[cell] {
+            self.is_pending[cell] = true;
+            self.pending.push(cell);
+        }
+
+        if self.settings.backtrack_depth > 0 {
+            self.trail.push((cell, pattern));
+        }
+
+        self.wave.remaining[cell] > 0
+    }
+
+    /// Puts back the patterns banned since the trail was `length` long.
+    fn undo(&mut self, length: usize) {
+        for (cell, pattern) in self.trail.drain(length..).rev() {
+            self.wave.bits[cell * self.words + pattern / WORD_BITS] |= 1 << (pattern % WORD_BITS);
+            self.wave.remaining[cell] += 1;
+            self.wave.sum_weights[cell] += self.rules.weights[pattern];
+            self.wave.sum_weight_logs[cell] += self.weight_logs[pattern];
+            self.wave.update_entropy(cell);
+        }
+    }
+
+    fn propagate_all(&mut self) -> bool {
+        self.pending.extend(0..self.wave.remaining.len());
+        self.is_pending.fill(true);
+        self.propagate()
+    }
+
+    /// Removes the patterns that are no longer allowed by any pattern of a neighbour.
+    fn propagate(&mut self) -> bool {
+        let mut allowed = vec![0; self.words];
+
+        while let Some(cell) = self.pending.pop() {
+            self.is_pending[cell] = false;
+
+            for &direction in self.directions {
+                let Some(neighbour) = self.neighbour(cell, direction) else {
+                    continue;
+                };
+
+                allowed.fill(0);
+                for pattern in self.patterns_of(cell) {
+                    let pattern_allowed = &self.rules.propagator[pattern][direction.index()];
+                    for (word, allowed_word) in allowed.iter_mut().zip(pattern_allowed) {
+                        *word |= allowed_word;
+                    }
+                }
+
+                for (word_index, allowed_word) in allowed.iter().enumerate() {
+                    let banned =
+                        self.wave.bits[neighbour * self.words + word_index] & !allowed_word;
+
+                    for bit in set_bits(banned) {
+                        if !self.ban(neighbour, word_index * WORD_BITS + bit) {
+                            self.clear_pending();
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    fn clear_pending(&mut self) {
+        for cell in self.pending.drain(..) {
+            self.is_pending[cell] = false;
+        }
+    }
+
+    fn neighbour(&self, cell: usize, direction: Direction) -> Option<usize> {
+        let size = self.settings.size.as_ivec3();
+        let cell = cell as i32;
+        let position = IVec3::new(
+            cell % size.x,
+            cell / size.x % size.y,
+            cell / (size.x * size.y),
+        ) + direction.offset();
+
+        let position = if self.settings.periodic {
+            position.rem_euclid(size)
+        } else if position.cmplt(IVec3::ZERO).any() || position.cmpge(size).any() {
+            return None;
+        } else {
+            position
+        };
+
+        Some((position.x + size.x * (position.y + size.y * position.z)) as usize)
+    }
+
+    fn result(&self) -> Vec<usize> {
+        (0..self.wave.remaining.len())
+            .map(|cell| self.patterns_of(cell).next().unwrap_or(0))
+            .collect()
+    }
+}
+
+fn set_bit(bits: &mut [u64], index: usize) {
+    bits[index / WORD_BITS] |= 1 << (index % WORD_BITS);
+}
+
+/// Indices of the bits of `word` that are set, from the lowest.
+fn set_bits(mut word: u64) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if word == 0 {
+            return None;
+        }
+
+        let bit = word.trailing_zeros() as usize;
+        word &= word - 1;
+        Some(bit)
+    })
+}
+
+fn has_bit(bits: &[u64], index: usize) -> bool {
+    bits[index / WORD_BITS] & (1 << (index % WORD_BITS)) != 0
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use bevy::math::UVec3;
+
+use crate::utils::seed::Seed;
+
+use super::{solve, Direction, Rules, WfcError, WfcSettings};
+
+/// Overlapping model: every `n`×`n` window of a 2D sample is a pattern, and two patterns can be
+/// next to each other when they agree where they overlap. Solved grids look locally like the
+/// sample.
+#[derive(Clone, Debug)]
+pub struct OverlappingModel {
+    pub n: usize,
+    /// Values of each pattern, row by row.
+    pub patterns: Vec<Vec<u32>>,
+    pub rules: Rules,
+}
+
+impl OverlappingModel {
+    /// Patterns of `sample`, whose values are stored row by row.
+    ///
+    /// With `periodic_sample` the windows wrap around the borders of the sample. `symmetry` is how
+    /// many of the 8 rotations and reflections of each window are added too, from 1 to 8.
+    pub fn new(
+        sample: &[u32],
+        width: usize,
+        height: usize,
+        n: usize,
+        periodic_sample: bool,
+        symmetry: usize,
+    ) -> Self {
+        let mut patterns: Vec<Vec<u32>> = Vec::new();
+        let mut weights: Vec<f32> = Vec::new();
+        let mut indices: HashMap<Vec<u32>, usize> = HashMap::new();
+
+        let (max_x, max_y) = if periodic_sample {
+            (width, height)
+        } else {
+            (
+                (width + 1).saturating_sub(n),
+                (height + 1).saturating_sub(n),
+            )
+        };
+
+        for y in 0..max_y {
+            for x in 0..max_x {
+                let window = pattern(n, |dx, dy| {
+                    sample[(x + dx) % width + (y + dy) % height * width]
+                });
+
+                for variant in symmetries(&window, n)
+                    .into_iter()
+                    .take(symmetry.clamp(1, 8))
+                {
+                    // Counted in order of appearance, so the same sample always gives the same
+                    // pattern indices
+                    match indices.get(&variant) {
+                        Some(&index) => weights[index] += 1.,
+                        None => {
+                            indices.insert(variant.clone(), patterns.len());
+                            patterns.push(variant);
+                            weights.push(1.);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut rules = Rules::new(weights);
+
+        for (a, first) in patterns.iter().enumerate() {
+            for (b, second) in patterns.iter().enumerate() {
+                for direction in [Direction::PosX, Direction::PosY] {
+                    if agrees(first, second, n, direction) {
+                        rules.allow(a, direction, b);
+                    }
+                }
+            }
+        }
+
+        OverlappingModel { n, patterns, rules }
+    }
+
+    /// Grid of `width`×`height` sample values, row by row.
+    pub fn solve(
+        &self,
+        width: u32,
+        height: u32,
+        periodic: bool,
+        seed: Seed,
+    ) -> Result<Vec<u32>, WfcError> {
+        let settings = WfcSettings {
+            size: UVec3::new(width, height, 1),
+            periodic,
+            ..WfcSettings::default()
+        };
+
+        let cells = solve(&self.rules, &settings, seed)?;
+
+        // Each cell shows the corner of its pattern, the rest overlaps with its neighbours
+        Ok(cells
+            .into_iter()
+            .map(|pattern| self.patterns[pattern][0])
+            .collect())
+    }
+}
+
+fn pattern(n: usize, value: impl Fn(usize, usize) -> u32) -> Vec<u32> {
+    (0..n * n).map(|i| value(i % n, i / n)).collect()
+}
+
+/// The window, then alternating reflections and quarter turns of it.
+fn symmetries(window: &[u32], n: usize) -> Vec<Vec<u32>> {
+    let rotate = |p: &[u32]| pattern(n, |x, y| p[n - 1 - y + x * n]);
+    let reflect = |p: &[u32]| pattern(n, |x, y| p[n - 1 - x + y * n]);
+
+    let mut variants = vec![window.to_vec()];
+    for i in 1..8 {
+        let previous = &variants[if i % 2 == 1 { i - 1 } else { i - 2 }];
+        let variant = if i % 2 == 1 {
+            reflect(previous)
+        } else {
+            rotate(previous)
+        };
+        variants.push(variant);
+    }
+
+    variants
+}
+
+/// Whether `second`, one cell away from `first` in `direction`, has the same values where they
+/// overlap.
+fn agrees(first: &[u32], second: &[u32], n: usize, direction: Direction) -> bool {
+    let offset = direction.offset();
+    let (dx, dy) = (offset.x as isize, offset.y as isize);
+    let n = n as isize;
+
+    let x_range = dx.max(0)..(n + dx).min(n);
+    let y_range = dy.max(0)..(n + dy).min(n);
+
+    y_range.clone().all(|y| {
+        x_range
+            .clone()
+            .all(|x| first[(x + y * n) as usize] == second[(x - dx + (y - dy) * n) as usize])
+    })
+}
@@ -0,0 +1,95 @@
+use super::{Direction, Rules};
+
+/// Tile of a simple tiled model. Two tiles can be next to each other when the sockets of the sides
+/// that touch are the same.
+///
+/// 2D grids ignore the Z sockets.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub name: String,
+    pub weight: f32,
+    /// Socket of each side, in the order of `Direction::ALL`.
+    pub sockets: [u32; 6],
+}
+
+impl Tile {
+    pub fn new(name: &str, weight: f32, sockets: [u32; 6]) -> Self {
+        Tile {
+            name: name.to_string(),
+            weight,
+            sockets,
+        }
+    }
+
+    /// Tile for 2D grids, with the sockets of its `+X`, `-X`, `+Y` and `-Y` sides.
+    pub fn new_2d(name: &str, weight: f32, [pos_x, neg_x, pos_y, neg_y]: [u32; 4]) -> Self {
+        Tile::new(name, weight, [pos_x, neg_x, pos_y, neg_y, 0, 0])
+    }
+
+    pub fn socket(&self, direction: Direction) -> u32 {
+        self.sockets[direction.index()]
+    }
+
+    /// The same tile turned a quarter to the left around the Z axis, from `+X` towards `+Y`.
+    pub fn rotated(&self) -> Tile {
+        let [pos_x, neg_x, pos_y, neg_y, pos_z, neg_z] = self.sockets;
+
+        Tile {
+            name: format!("{}'", self.name),
+            weight: self.weight,
+            sockets: [neg_y, pos_y, pos_x, neg_x, pos_z, neg_z],
+        }
+    }
+}
+
+/// Set of tiles to solve a grid with.
+#[derive(Clone, Debug, Default)]
+pub struct TiledModel {
+    pub tiles: Vec<Tile>,
+}
+
+impl TiledModel {
+    pub fn new(tiles: Vec<Tile>) -> Self {
+        TiledModel { tiles }
+    }
+
+    /// Adds `tile` and its rotations. Rotations that look the same are left out, so symmetric
+    /// tiles aren't picked more often.
+    pub fn with_rotations(mut self, tile: Tile) -> Self {
+        let mut rotations: Vec<Tile> = Vec::with_capacity(4);
+        let mut rotated = tile;
+
+        for _ in 0..4 {
+            let next = rotated.rotated();
+
+            if rotations
+                .iter()
+                .all(|other| other.sockets != rotated.sockets)
+            {
+                rotations.push(rotated);
+            }
+
+            rotated = next;
+        }
+
+        self.tiles.extend(rotations);
+        self
+    }
+
+    /// Adjacency rules where tiles with matching sockets can be next to each other.
+    pub fn rules(&self) -> Rules {
+        let mut rules = Rules::new(self.tiles.iter().map(|tile| tile.weight).collect());
+
+        for (a, tile) in self.tiles.iter().enumerate() {
+            for (b, other) in self.tiles.iter().enumerate() {
+                for direction in Direction::ALL {
+                    if tile.socket(direction) == other.socket(direction.opposite()) {
+                        rules.allow(a, direction, b);
+                    }
+                }
+            }
+        }
+
+        rules
+    }
+}